@@ -38,6 +38,11 @@ impl FontMetrics {
         (self.char_width(c) as f32 / self.units_per_em as f32) * font_size
     }
 
+    /// Get the ascender height in points (distance from the top of a line to its baseline)
+    pub fn ascent(&self, font_size: f32) -> f32 {
+        (self.ascender as f32 / self.units_per_em as f32) * font_size
+    }
+
     /// Get line height in points
     pub fn line_height(&self, font_size: f32, line_height_multiplier: f32) -> f32 {
         font_size * line_height_multiplier
@@ -0,0 +1,1855 @@
+//! HTML/CSS subset front end.
+//!
+//! Converts a restricted HTML subset (div, p, span, b, i, img, table, tr, td,
+//! ul/ol/li, h1-h6) with inline `style` attributes and `<style>` blocks into a
+//! `JsonNode` tree that the regular layout pipeline can render.
+//!
+//! This is a pragmatic converter for email-style templates, not a browser:
+//! selectors are limited to `tag`, `.class`, `#id` and combinations of those,
+//! and CSS lengths are converted to points (1px = 0.75pt).
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::layout::{
+    parse_angle, split_grid_tracks, Bookmark, BorderStyle, BoxShadow, Color, CrossAlign, Dimension, Direction, Display,
+    DocumentOptions, FontStyle, FontWeight, Gradient, GradientStop, GradientType, GridPlacement, GridTrack, JsonNode,
+    ListStyle, MainAlign, NodeType, ObjectFit, Overflow, Position, Role, Style, TextAlign, TransformFunction,
+    TransformOrigin, VerticalAlign,
+};
+use crate::svg::parse_color;
+
+// ============================================================================
+// PAYLOAD
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct HtmlPayload {
+    pub html: String,
+    #[serde(flatten)]
+    pub document: DocumentOptions,
+}
+
+// ============================================================================
+// PUBLIC API
+// ============================================================================
+
+/// Convert an HTML document (or fragment) into a `page` node
+pub fn html_to_node(html: &str) -> Result<JsonNode, String> {
+    let (dom, css) = parse_html(html)?;
+    let converter = Converter {
+        rules: parse_stylesheet(&css),
+    };
+
+    let html_element = find_element(&dom, "html");
+    let body = find_element(&dom, "body")
+        .or(html_element)
+        .unwrap_or(&dom);
+
+    let mut page = converter.convert_page(body);
+    page.lang = body.attr("lang").or_else(|| html_element.and_then(|e| e.attr("lang"))).map(str::to_string);
+    Ok(page)
+}
+
+// ============================================================================
+// DOM
+// ============================================================================
+
+#[derive(Debug, Clone)]
+enum HtmlNode {
+    Element(HtmlElement),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct HtmlElement {
+    tag: String,
+    attrs: HashMap<String, String>,
+    children: Vec<HtmlNode>,
+}
+
+impl HtmlElement {
+    fn new(tag: &str) -> Self {
+        HtmlElement {
+            tag: tag.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(|s| s.as_str())
+    }
+
+    fn has_class(&self, class: &str) -> bool {
+        self.attr("class")
+            .map(|c| c.split_whitespace().any(|c| c == class))
+            .unwrap_or(false)
+    }
+
+    fn child_elements(&self) -> impl Iterator<Item = &HtmlElement> {
+        self.children.iter().filter_map(|c| match c {
+            HtmlNode::Element(e) => Some(e),
+            HtmlNode::Text(_) => None,
+        })
+    }
+}
+
+fn find_element<'a>(root: &'a HtmlElement, tag: &str) -> Option<&'a HtmlElement> {
+    if root.tag == tag {
+        return Some(root);
+    }
+    root.child_elements().find_map(|child| find_element(child, tag))
+}
+
+// ============================================================================
+// HTML PARSER
+// ============================================================================
+
+/// Elements that never have content or a closing tag
+const VOID_TAGS: &[&str] = &["img", "br", "hr", "meta", "link", "input", "col", "source", "wbr"];
+
+/// Elements laid out as inline runs inside a block
+const INLINE_TAGS: &[&str] = &[
+    "span", "b", "strong", "i", "em", "u", "a", "small", "font", "label", "sub", "sup", "code",
+];
+
+/// Block-level elements that implicitly close an open `<p>`
+const BLOCK_TAGS: &[&str] = &[
+    "div", "p", "table", "ul", "ol", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "section",
+    "header", "footer", "article",
+];
+
+/// Parse HTML into a DOM tree, returning the root and the concatenated `<style>` contents
+fn parse_html(html: &str) -> Result<(HtmlElement, String), String> {
+    if html.trim().is_empty() {
+        return Err("HTML document is empty".to_string());
+    }
+
+    let mut stack: Vec<HtmlElement> = vec![HtmlElement::new("#root")];
+    let mut css = String::new();
+    let mut pos = 0;
+
+    while pos < html.len() {
+        let rest = &html[pos..];
+        let lt = match rest.find('<') {
+            Some(lt) => lt,
+            None => {
+                push_text(&mut stack, rest);
+                break;
+            }
+        };
+        if lt > 0 {
+            push_text(&mut stack, &rest[..lt]);
+        }
+        pos += lt;
+        let rest = &html[pos..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            pos += 4 + comment.find("-->").map(|e| e + 3).unwrap_or(comment.len());
+            continue;
+        }
+
+        // A '<' that doesn't start a tag is literal text ("a < b")
+        let next = rest[1..].chars().next().unwrap_or(' ');
+        if !(next.is_ascii_alphabetic() || matches!(next, '/' | '!' | '?')) {
+            push_text(&mut stack, "<");
+            pos += 1;
+            continue;
+        }
+
+        let end = find_tag_end(rest).ok_or_else(|| format!("unterminated tag at byte {}", pos))?;
+        let tag_src = &rest[1..end];
+        pos += end + 1;
+
+        // Doctype and processing instructions
+        if tag_src.starts_with('!') || tag_src.starts_with('?') {
+            continue;
+        }
+
+        if let Some(name) = tag_src.strip_prefix('/') {
+            close_tag(&mut stack, &name.trim().to_ascii_lowercase());
+            continue;
+        }
+
+        let (element, self_closing) = parse_open_tag(tag_src);
+
+        // Raw text elements: keep stylesheets, drop scripts
+        if element.tag == "style" || element.tag == "script" {
+            let close = format!("</{}", element.tag);
+            let body_len = html[pos..]
+                .to_ascii_lowercase()
+                .find(&close)
+                .unwrap_or(html.len() - pos);
+            if element.tag == "style" {
+                css.push_str(&html[pos..pos + body_len]);
+                css.push('\n');
+            }
+            pos += body_len;
+            pos = html[pos..].find('>').map(|gt| pos + gt + 1).unwrap_or(html.len());
+            continue;
+        }
+
+        close_implied(&mut stack, &element.tag);
+
+        if self_closing || VOID_TAGS.contains(&element.tag.as_str()) {
+            stack.last_mut().unwrap().children.push(HtmlNode::Element(element));
+        } else {
+            stack.push(element);
+        }
+    }
+
+    while stack.len() > 1 {
+        pop_element(&mut stack);
+    }
+
+    Ok((stack.pop().unwrap(), css))
+}
+
+/// Find the '>' closing a tag, ignoring any inside quoted attribute values
+fn find_tag_end(s: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Parse the inside of an opening tag (`div class="x"`); returns the element and
+/// whether it was written self-closing (`<br/>`)
+fn parse_open_tag(src: &str) -> (HtmlElement, bool) {
+    let src = src.trim();
+    let self_closing = src.ends_with('/');
+    let src = src.trim_end_matches('/');
+
+    let name_end = src.find(|c: char| c.is_whitespace()).unwrap_or(src.len());
+    let mut element = HtmlElement::new(&src[..name_end].to_ascii_lowercase());
+
+    let mut chars = src[name_end..].chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            break;
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            match chars.peek().copied() {
+                Some(q) if q == '"' || q == '\'' => {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if c == q {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                }
+                _ => {
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() {
+                            break;
+                        }
+                        value.push(c);
+                        chars.next();
+                    }
+                }
+            }
+        }
+
+        element
+            .attrs
+            .insert(name.to_ascii_lowercase(), decode_entities(&value));
+    }
+
+    (element, self_closing)
+}
+
+fn push_text(stack: &mut [HtmlElement], text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(top) = stack.last_mut() {
+        top.children.push(HtmlNode::Text(decode_entities(text)));
+    }
+}
+
+fn pop_element(stack: &mut Vec<HtmlElement>) {
+    if let Some(element) = stack.pop() {
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(HtmlNode::Element(element));
+        }
+    }
+}
+
+/// Close the innermost open element with this tag (and anything opened inside it)
+fn close_tag(stack: &mut Vec<HtmlElement>, tag: &str) {
+    if let Some(idx) = stack.iter().rposition(|e| e.tag == tag) {
+        if idx == 0 {
+            return;
+        }
+        while stack.len() > idx {
+            pop_element(stack);
+        }
+    }
+}
+
+/// Apply HTML's implied end tags, e.g. `<li>` closes a previous open `<li>`
+fn close_implied(stack: &mut Vec<HtmlElement>, tag: &str) {
+    let closes: &[&str] = match tag {
+        "li" => &["li", "p"],
+        "td" | "th" => &["td", "th", "p"],
+        "tr" => &["tr", "td", "th", "p"],
+        t if BLOCK_TAGS.contains(&t) => &["p"],
+        _ => &[],
+    };
+
+    while stack.len() > 1 && closes.contains(&stack.last().unwrap().tag.as_str()) {
+        pop_element(stack);
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let semi = match rest.find(';') {
+            Some(s) if s <= 10 => s,
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "copy" => Some('©'),
+            "reg" => Some('®'),
+            "euro" => Some('€'),
+            "bull" => Some('•'),
+            "middot" => Some('·'),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            _ => {
+                if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse::<u32>().ok().and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+        };
+
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// ============================================================================
+// CSS
+// ============================================================================
+
+#[derive(Debug, Clone, Default)]
+struct Selector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Selector {
+    /// Parse a compound selector like `td.price` or `#header`.
+    /// Combinators, attribute selectors and pseudo-classes are not supported.
+    fn parse(src: &str) -> Option<Selector> {
+        let src = src.trim();
+        if src.is_empty() || src.contains(|c: char| c.is_whitespace() || "[]:>+~".contains(c)) {
+            return None;
+        }
+
+        let mut selector = Selector::default();
+        let mut rest = src;
+
+        let tag_end = rest.find(['.', '#']).unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        if !tag.is_empty() && tag != "*" {
+            selector.tag = Some(tag.to_ascii_lowercase());
+        }
+        rest = &rest[tag_end..];
+
+        while !rest.is_empty() {
+            let kind = rest.chars().next().unwrap();
+            let body = &rest[1..];
+            let end = body.find(['.', '#']).unwrap_or(body.len());
+            let name = &body[..end];
+            if name.is_empty() {
+                return None;
+            }
+            if kind == '.' {
+                selector.classes.push(name.to_string());
+            } else {
+                selector.id = Some(name.to_string());
+            }
+            rest = &body[end..];
+        }
+
+        Some(selector)
+    }
+
+    fn matches(&self, element: &HtmlElement) -> bool {
+        if let Some(ref tag) = self.tag {
+            if *tag != element.tag {
+                return false;
+            }
+        }
+        if let Some(ref id) = self.id {
+            if element.attr("id") != Some(id.as_str()) {
+                return false;
+            }
+        }
+        self.classes.iter().all(|c| element.has_class(c))
+    }
+
+    fn specificity(&self) -> u32 {
+        self.id.as_ref().map_or(0, |_| 100)
+            + self.classes.len() as u32 * 10
+            + self.tag.as_ref().map_or(0, |_| 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CssRule {
+    selector: Selector,
+    specificity: u32,
+    order: usize,
+    declarations: Vec<(String, String)>,
+}
+
+/// Parse a stylesheet into rules sorted in cascade order (lowest priority first)
+fn parse_stylesheet(css: &str) -> Vec<CssRule> {
+    let css = strip_css_comments(css);
+    let mut rules: Vec<CssRule> = Vec::new();
+    let mut rest = css.as_str();
+
+    while let Some(open) = rest.find('{') {
+        // Statements like `@charset "utf-8";` end up in front of the next selector
+        let prelude = rest[..open].rsplit(';').next().unwrap_or("").trim();
+
+        let mut depth = 0;
+        let mut close = None;
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = match close {
+            Some(c) => c,
+            None => break,
+        };
+
+        let block = &rest[open + 1..close];
+        rest = &rest[close + 1..];
+
+        // @media, @font-face etc. are ignored
+        if prelude.starts_with('@') {
+            continue;
+        }
+
+        let declarations = parse_declarations(block);
+        for part in prelude.split(',') {
+            if let Some(selector) = Selector::parse(part) {
+                rules.push(CssRule {
+                    specificity: selector.specificity(),
+                    selector,
+                    order: rules.len(),
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+    }
+
+    rules.sort_by_key(|r| (r.specificity, r.order));
+    rules
+}
+
+fn strip_css_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse `prop: value; prop: value` into lowercase property names and trimmed values
+fn parse_declarations(block: &str) -> Vec<(String, String)> {
+    block
+        .split(';')
+        .filter_map(|decl| {
+            let (prop, value) = decl.split_once(':')?;
+            let prop = prop.trim().to_ascii_lowercase();
+            let value = value.trim().trim_end_matches("!important").trim();
+            if prop.is_empty() || value.is_empty() {
+                return None;
+            }
+            Some((prop, value.to_string()))
+        })
+        .collect()
+}
+
+/// Default styles a browser would apply to these elements
+fn user_agent_declarations(tag: &str) -> &'static [(&'static str, &'static str)] {
+    match tag {
+        "h1" => &[("font-size", "2em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "h2" => &[("font-size", "1.5em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "h3" => &[("font-size", "1.17em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "h4" => &[("font-size", "1em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "h5" => &[("font-size", "0.83em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "h6" => &[("font-size", "0.67em"), ("font-weight", "bold"), ("margin-bottom", "0.5em")],
+        "p" => &[("margin-bottom", "1em")],
+        "b" | "strong" | "th" => &[("font-weight", "bold")],
+        "i" | "em" => &[("font-style", "italic")],
+        "a" => &[("color", "#0000ee")],
+        "ul" => &[("padding-left", "40px"), ("margin-bottom", "1em")],
+        "ol" => &[("padding-left", "40px"), ("margin-bottom", "1em"), ("list-style-type", "decimal")],
+        _ => &[],
+    }
+}
+
+/// Presentational HTML attributes (`width="600"`, `bgcolor`, `align`) as CSS declarations
+fn attribute_declarations(element: &HtmlElement) -> Vec<(String, String)> {
+    let mut decls = Vec::new();
+    for (attr, prop) in [
+        ("width", "width"),
+        ("height", "height"),
+        ("bgcolor", "background-color"),
+        ("align", "text-align"),
+    ] {
+        if let Some(value) = element.attr(attr) {
+            decls.push((prop.to_string(), value.to_string()));
+        }
+    }
+    decls
+}
+
+// ============================================================================
+// CSS VALUES
+// ============================================================================
+
+/// Root font size in points (16px), used for `rem`
+const ROOT_FONT_SIZE: f32 = 12.0;
+
+/// Parse a CSS length into points. Unitless numbers are treated as pixels,
+/// which is what HTML attributes like `width="600"` mean.
+fn parse_length(value: &str, font_size: f32) -> Option<f32> {
+    let value = value.trim().to_ascii_lowercase();
+    let num_end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let num: f32 = value[..num_end].parse().ok()?;
+
+    let factor = match &value[num_end..] {
+        "" | "px" => 0.75,
+        "pt" => 1.0,
+        "em" => font_size,
+        "rem" => ROOT_FONT_SIZE,
+        "mm" => 72.0 / 25.4,
+        "cm" => 72.0 / 2.54,
+        "in" => 72.0,
+        "pc" => 12.0,
+        _ => return None,
+    };
+    Some(num * factor)
+}
+
+/// Parse a CSS track list; lengths convert to points, unsupported tracks become auto
+fn parse_grid_tracks(value: &str, font_size: f32) -> Vec<GridTrack> {
+    split_grid_tracks(&value.to_ascii_lowercase())
+        .iter()
+        .map(|t| match parse_length(t, font_size) {
+            Some(pt) => GridTrack::Pt(pt),
+            None => GridTrack::parse(t).unwrap_or(GridTrack::Auto),
+        })
+        .collect()
+}
+
+fn parse_dimension(value: &str, font_size: f32) -> Option<Dimension> {
+    let value = value.trim();
+    if let Some(pct) = value.strip_suffix('%') {
+        return pct.trim().parse().ok().map(Dimension::Percent);
+    }
+    parse_length(value, font_size).map(Dimension::Pt)
+}
+
+/// Parse a 1-4 value box shorthand (`padding: 4px 8px`) as (top, right, bottom, left)
+fn parse_box(value: &str, font_size: f32) -> Option<(f32, f32, f32, f32)> {
+    let parts: Vec<f32> = value
+        .split_whitespace()
+        .map(|v| if v == "auto" { Some(0.0) } else { parse_length(v, font_size) })
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts.as_slice() {
+        [a] => Some((*a, *a, *a, *a)),
+        [v, h] => Some((*v, *h, *v, *h)),
+        [t, h, b] => Some((*t, *h, *b, *h)),
+        [t, r, b, l] => Some((*t, *r, *b, *l)),
+        _ => None,
+    }
+}
+
+fn parse_css_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("transparent") {
+        return Some(Color::transparent());
+    }
+    parse_color(value)
+}
+
+/// Split on separators outside parentheses (colors like rgb() contain commas and spaces)
+fn split_top_level(value: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth == 0 && is_separator(c) => {
+                parts.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+/// Parse a `box-shadow` (`0 2px 4px rgba(0, 0, 0, 0.2)`, `inset 0 0 0 1px #ccc`);
+/// only the first of several shadows is kept
+fn parse_box_shadow(value: &str, font_size: f32) -> Option<BoxShadow> {
+    let value = value.trim().to_ascii_lowercase();
+    let first = split_top_level(&value, |c| c == ',').into_iter().next()?;
+    let mut shadow = BoxShadow::default();
+    let mut lengths = Vec::new();
+    for part in split_top_level(first, char::is_whitespace) {
+        if part == "inset" {
+            shadow.inset = true;
+        } else if let Some(length) = parse_length(part, font_size) {
+            lengths.push(length);
+        } else {
+            shadow.color = Some(parse_css_color(part)?);
+        }
+    }
+    match lengths.as_slice() {
+        [x, y, rest @ ..] if rest.len() <= 2 => {
+            shadow.offset_x = *x;
+            shadow.offset_y = *y;
+            shadow.blur = rest.first().copied().unwrap_or(0.0);
+            shadow.spread = rest.get(1).copied().unwrap_or(0.0);
+            Some(shadow)
+        }
+        _ => None,
+    }
+}
+
+/// Parse a `linear-gradient(...)` or `radial-gradient(...)` image; radial shape and
+/// position arguments are ignored (gradients are circles centered in the box)
+fn parse_css_gradient(value: &str) -> Option<Gradient> {
+    let value = value.trim().to_ascii_lowercase();
+    let (kind, start) = if let Some(i) = value.find("linear-gradient(") {
+        (GradientType::LinearGradient, i + "linear-gradient(".len())
+    } else if let Some(i) = value.find("radial-gradient(") {
+        (GradientType::RadialGradient, i + "radial-gradient(".len())
+    } else {
+        return None;
+    };
+
+    let end = value.rfind(')').filter(|&end| end >= start).unwrap_or(value.len());
+    let args = split_top_level(&value[start..end], |c| c == ',');
+
+    let mut angle = None;
+    let mut stops = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        if let Some(stop) = parse_gradient_stop(arg) {
+            stops.push(stop);
+        } else if i == 0 && kind == GradientType::LinearGradient {
+            angle = parse_gradient_angle(arg);
+        }
+    }
+    if stops.is_empty() {
+        return None;
+    }
+    Some(Gradient { kind, angle, stops })
+}
+
+/// `red`, `rgb(0, 0, 0) 50%`
+fn parse_gradient_stop(arg: &str) -> Option<GradientStop> {
+    if let Some((color, offset)) = arg.rsplit_once(char::is_whitespace) {
+        if let Some(pct) = offset.strip_suffix('%') {
+            return Some(GradientStop {
+                offset: Some(pct.parse::<f32>().ok()? / 100.0),
+                color: parse_css_color(color)?,
+            });
+        }
+    }
+    Some(GradientStop { offset: None, color: parse_css_color(arg)? })
+}
+
+/// `45deg`, `0.25turn`, `to right`, `to top left`
+fn parse_gradient_angle(arg: &str) -> Option<f32> {
+    if let Some(direction) = arg.strip_prefix("to ") {
+        let has = |side: &str| direction.split_whitespace().any(|w| w == side);
+        let x = if has("right") { 1.0 } else if has("left") { -1.0 } else { 0.0 };
+        let y = if has("top") { 1.0 } else if has("bottom") { -1.0 } else { 0.0 };
+        if x == 0.0 && y == 0.0 {
+            return None;
+        }
+        let degrees = f32::atan2(x, y).to_degrees();
+        return Some(if degrees < 0.0 { degrees + 360.0 } else { degrees });
+    }
+    parse_angle(arg)
+}
+
+/// Parse a border shorthand (`1px solid #ccc`) into width and color.
+/// As in CSS, a border without a style keyword is not drawn.
+fn parse_border(value: &str, font_size: f32) -> (Option<f32>, Option<Color>, Option<BorderStyle>) {
+    let mut width = None;
+    let mut color = None;
+    let mut style = None;
+
+    for token in split_top_level(value, char::is_whitespace) {
+        match token.to_ascii_lowercase().as_str() {
+            "none" | "hidden" => return (Some(0.0), None, None),
+            t if parse_border_style(t).is_some() => style = parse_border_style(t),
+            t if border_width_keyword(t).is_some() => width = border_width_keyword(t),
+            t => {
+                if let Some(w) = parse_length(t, font_size) {
+                    width = Some(w);
+                } else if let Some(c) = parse_css_color(token) {
+                    color = Some(c);
+                }
+            }
+        }
+    }
+
+    if style.is_none() {
+        return (Some(0.0), color, None);
+    }
+    (Some(width.unwrap_or(2.25)), color, style)
+}
+
+/// Width of `thin`, `medium` and `thick` (1px, 3px and 5px)
+fn border_width_keyword(value: &str) -> Option<f32> {
+    match value {
+        "thin" => Some(0.75),
+        "medium" => Some(2.25),
+        "thick" => Some(3.75),
+        _ => None,
+    }
+}
+
+/// Border style keyword; the 3D styles are drawn solid
+fn parse_border_style(value: &str) -> Option<BorderStyle> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "solid" | "groove" | "ridge" | "inset" | "outset" => Some(BorderStyle::Solid),
+        "dashed" => Some(BorderStyle::Dashed),
+        "dotted" => Some(BorderStyle::Dotted),
+        "double" => Some(BorderStyle::Double),
+        "none" | "hidden" => Some(BorderStyle::None),
+        _ => None,
+    }
+}
+
+fn parse_font_weight(value: &str) -> Option<FontWeight> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "bold" | "bolder" => Some(FontWeight::Bold),
+        "normal" | "lighter" => Some(FontWeight::Normal),
+        v => v.parse::<u32>().ok().map(|w| {
+            if w >= 600 {
+                FontWeight::Bold
+            } else {
+                FontWeight::Normal
+            }
+        }),
+    }
+}
+
+// ============================================================================
+// CONVERSION
+// ============================================================================
+
+/// Inherited text properties. CSS inheritance is resolved here because the
+/// layout engine reads text styles from each node individually.
+#[derive(Debug, Clone, PartialEq)]
+struct TextStyle {
+    font_size: f32,
+    font_weight: FontWeight,
+    font_style: FontStyle,
+    color: Color,
+    text_align: TextAlign,
+    line_height: Option<f32>,
+    /// Target of the enclosing `<a href>`
+    href: Option<String>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            font_size: 12.0,
+            font_weight: FontWeight::Normal,
+            font_style: FontStyle::Normal,
+            color: Color::black(),
+            text_align: TextAlign::Left,
+            line_height: None,
+            href: None,
+        }
+    }
+}
+
+/// Cascaded style of one element
+struct Computed {
+    style: Style,
+    text: TextStyle,
+    display: Option<String>,
+}
+
+/// A piece of inline content waiting to be turned into nodes
+enum InlineItem {
+    Run(String, TextStyle),
+    Node(Box<JsonNode>),
+    Break,
+}
+
+struct Converter {
+    rules: Vec<CssRule>,
+}
+
+impl Converter {
+    /// Cascade user-agent defaults, HTML attributes, stylesheet rules and the
+    /// inline `style` attribute for one element
+    fn compute(&self, element: &HtmlElement, parent: &TextStyle) -> Computed {
+        let mut decls: Vec<(String, String)> = user_agent_declarations(&element.tag)
+            .iter()
+            .map(|(p, v)| (p.to_string(), v.to_string()))
+            .collect();
+        decls.extend(attribute_declarations(element));
+        for rule in &self.rules {
+            if rule.selector.matches(element) {
+                decls.extend(rule.declarations.iter().cloned());
+            }
+        }
+        if let Some(inline) = element.attr("style") {
+            decls.extend(parse_declarations(inline));
+        }
+
+        let mut computed = Computed {
+            style: Style::default(),
+            text: parent.clone(),
+            display: None,
+        };
+
+        // font-size first: em lengths in every other property resolve against it
+        for (prop, value) in &decls {
+            if prop == "font-size" {
+                if let Some(size) = parse_dimension(value, parent.font_size) {
+                    computed.text.font_size = size.resolve(parent.font_size);
+                }
+            }
+        }
+        for (prop, value) in &decls {
+            apply_declaration(&mut computed, prop, value);
+        }
+
+        computed
+    }
+
+    fn convert_page(&self, body: &HtmlElement) -> JsonNode {
+        let computed = self.compute(body, &TextStyle::default());
+
+        let mut page = JsonNode::new(NodeType::Page);
+        page.style = computed.style;
+
+        // The page box is the body, so body margins become page padding
+        let (mt, mr, mb, ml) = page.style.margin_trbl();
+        let (pt, pr, pb, pl) = page.style.padding_trbl();
+        page.style.padding_top = Some(pt + mt);
+        page.style.padding_right = Some(pr + mr);
+        page.style.padding_bottom = Some(pb + mb);
+        page.style.padding_left = Some(pl + ml);
+        page.style.margin = None;
+        page.style.margin_top = None;
+        page.style.margin_right = None;
+        page.style.margin_bottom = None;
+        page.style.margin_left = None;
+
+        if page.style.width.is_none() {
+            page.style.width = Some(Dimension::Pt(595.0));
+        }
+        if page.style.height.is_none() {
+            page.style.height = Some(Dimension::Pt(842.0));
+        }
+        if page.style.cross_align.is_none() {
+            page.style.cross_align = Some(CrossAlign::Stretch);
+        }
+
+        page.children = self.convert_children(body, &computed.text, false);
+        page
+    }
+
+    /// Convert the children of a block, grouping inline content into text nodes
+    fn convert_children(&self, element: &HtmlElement, text: &TextStyle, in_row: bool) -> Vec<JsonNode> {
+        let mut nodes = Vec::new();
+        let mut inline: Vec<InlineItem> = Vec::new();
+
+        for child in &element.children {
+            match child {
+                HtmlNode::Text(t) => inline.push(InlineItem::Run(t.clone(), text.clone())),
+                HtmlNode::Element(e) if e.tag == "br" => inline.push(InlineItem::Break),
+                HtmlNode::Element(e) if INLINE_TAGS.contains(&e.tag.as_str()) => {
+                    self.collect_inline(e, text, &mut inline);
+                }
+                HtmlNode::Element(e) => {
+                    flush_inline(&mut inline, in_row, &mut nodes);
+                    if let Some(node) = self.convert_element(e, text, in_row) {
+                        nodes.push(node);
+                    }
+                }
+            }
+        }
+        flush_inline(&mut inline, in_row, &mut nodes);
+
+        nodes
+    }
+
+    fn collect_inline(&self, element: &HtmlElement, parent: &TextStyle, inline: &mut Vec<InlineItem>) {
+        let mut computed = self.compute(element, parent);
+        if computed.display.as_deref() == Some("none") {
+            return;
+        }
+        if element.tag == "a" {
+            if let Some(href) = element.attr("href") {
+                computed.text.href = Some(href.to_string());
+            }
+        }
+
+        for child in &element.children {
+            match child {
+                HtmlNode::Text(t) => inline.push(InlineItem::Run(t.clone(), computed.text.clone())),
+                HtmlNode::Element(e) if e.tag == "br" => inline.push(InlineItem::Break),
+                HtmlNode::Element(e) if e.tag == "img" => {
+                    if let Some(mut node) = self.convert_element(e, &computed.text, true) {
+                        node.href = computed.text.href.clone();
+                        inline.push(InlineItem::Node(Box::new(node)));
+                    }
+                }
+                HtmlNode::Element(e) => self.collect_inline(e, &computed.text, inline),
+            }
+        }
+    }
+
+    fn convert_element(&self, element: &HtmlElement, parent: &TextStyle, in_row: bool) -> Option<JsonNode> {
+        let computed = self.compute(element, parent);
+        if computed.display.as_deref() == Some("none") {
+            return None;
+        }
+
+        let mut node = match element.tag.as_str() {
+            "img" => {
+                let mut node = JsonNode::new(NodeType::Image);
+                node.src = element.attr("src").map(str::to_string);
+                node.alt = element.attr("alt").map(str::to_string);
+                // An empty alt marks a decorative image, as in HTML
+                if node.alt.as_deref() == Some("") {
+                    node.role = Some(Role::Artifact);
+                }
+                node.style = computed.style;
+                node
+            }
+            "hr" => {
+                let mut node = JsonNode::new(NodeType::View);
+                node.style = computed.style;
+                if node.style.border_top_width.is_none() {
+                    node.style.border_top_width = Some(0.75);
+                    node.style.border_top_color = Some(Color { r: 200, g: 200, b: 200, a: 1.0 });
+                }
+                if node.style.margin_top.is_none() && node.style.margin.is_none() {
+                    node.style.margin_top = Some(6.0);
+                    node.style.margin_bottom = Some(6.0);
+                }
+                node
+            }
+            "table" => self.convert_table(element, computed),
+            "ul" | "ol" => self.convert_list(element, computed),
+            _ => self.convert_block(element, computed, in_row),
+        };
+        node.id = element.attr("id").map(str::to_string);
+        node.lang = element.attr("lang").map(str::to_string);
+        if element.tag == "figcaption" {
+            node.role = Some(Role::Caption);
+        }
+        // Headings make up the document outline, titled by their text
+        if let Some(level) = element.tag.strip_prefix('h').and_then(|n| n.parse::<u32>().ok()) {
+            node.bookmark = Some(Bookmark { title: None, level: Some(level) });
+        }
+
+        Some(node)
+    }
+
+    fn convert_block(&self, element: &HtmlElement, computed: Computed, in_row: bool) -> JsonNode {
+        let is_flex = matches!(computed.display.as_deref(), Some("flex") | Some("inline-flex"));
+        let is_grid = matches!(computed.display.as_deref(), Some("grid") | Some("inline-grid"));
+        let mut style = computed.style;
+        if is_flex && style.direction.is_none() {
+            style.direction = Some(Direction::Row);
+        }
+        let row = style.direction.as_ref().is_some_and(|d| d.is_row());
+        if is_grid {
+            style.display = Some(Display::Grid);
+        } else {
+            // Flex layouts only know one gap: the one along their main axis
+            let main_gap = if row { style.column_gap } else { style.row_gap };
+            style.gap = main_gap.or(style.gap);
+        }
+
+        let mut children = self.convert_children(element, &computed.text, row);
+
+        // A block holding a single line of text collapses into the text node itself
+        // (text nodes don't support padding, so padded blocks keep their box)
+        if !is_flex
+            && !is_grid
+            && children.len() == 1
+            && children[0].node_type == NodeType::Text
+            && style.padding_trbl() == (0.0, 0.0, 0.0, 0.0)
+        {
+            let text = children.pop().unwrap();
+            let mut merged = style;
+            merged.color = text.style.color;
+            merged.font_size = text.style.font_size;
+            merged.font_weight = text.style.font_weight;
+            merged.font_style = text.style.font_style;
+            merged.text_align = text.style.text_align;
+            merged.line_height = text.style.line_height;
+            if merged.width.is_none() && !in_row {
+                merged.width = text.style.width;
+            }
+            return JsonNode { style: merged, ..text };
+        }
+
+        let mut node = JsonNode::new(NodeType::View);
+        node.style = style;
+        // Like CSS blocks (and flex items), children fill the cross axis by default
+        if node.style.cross_align.is_none() {
+            node.style.cross_align = Some(CrossAlign::Stretch);
+        }
+        node.children = children;
+        node
+    }
+
+    fn convert_table(&self, element: &HtmlElement, computed: Computed) -> JsonNode {
+        let mut table = JsonNode::new(NodeType::Table);
+        table.style = computed.style;
+        if let Some(spacing) = element.attr("cellspacing").and_then(|v| parse_length(v, 12.0)) {
+            table.style.gap = Some(spacing);
+        }
+        let cell_padding = element.attr("cellpadding").and_then(|v| parse_length(v, 12.0));
+
+        // Rows may sit directly in the table or inside thead/tbody/tfoot
+        let rows: Vec<&HtmlElement> = element
+            .child_elements()
+            .flat_map(|e| match e.tag.as_str() {
+                "tr" => vec![e],
+                "thead" | "tbody" | "tfoot" => e.child_elements().filter(|r| r.tag == "tr").collect(),
+                _ => vec![],
+            })
+            .collect();
+
+        for row_el in rows {
+            let row_computed = self.compute(row_el, &computed.text);
+            let mut row = JsonNode::new(NodeType::Row);
+            row.style = row_computed.style;
+
+            for cell_el in row_el.child_elements().filter(|c| c.tag == "td" || c.tag == "th") {
+                let cell_computed = self.compute(cell_el, &row_computed.text);
+                let mut cell = JsonNode::new(NodeType::Cell);
+                cell.style = cell_computed.style;
+                if let Some(p) = cell_padding {
+                    if cell.style.padding.is_none() {
+                        cell.style.padding = Some(p);
+                    }
+                }
+                cell.col_span = cell_el.attr("colspan").and_then(|v| v.trim().parse().ok());
+                cell.row_span = cell_el.attr("rowspan").and_then(|v| v.trim().parse().ok());
+                if cell_el.tag == "th" {
+                    cell.header = Some(true);
+                }
+                cell.children = self.convert_children(cell_el, &cell_computed.text, false);
+                row.children.push(cell);
+            }
+
+            table.children.push(row);
+        }
+
+        // Column widths come from the first row's cell widths, as in HTML tables
+        if let Some(first) = table.children.first() {
+            let widths: Vec<Dimension> = first
+                .children
+                .iter()
+                .map(|c| c.style.width.clone().unwrap_or(Dimension::Pt(0.0)))
+                .collect();
+            let no_spans = first.children.iter().all(|c| c.col_span.unwrap_or(1) == 1);
+            if no_spans && first.children.iter().any(|c| c.style.width.is_some()) {
+                table.column_widths = Some(widths);
+            }
+        }
+
+        table
+    }
+
+    fn convert_list(&self, element: &HtmlElement, computed: Computed) -> JsonNode {
+        let mut list = JsonNode::new(NodeType::List);
+        list.style = computed.style;
+        list.start = element.attr("start").and_then(|v| v.trim().parse().ok());
+        if let Some(kind) = element.attr("type") {
+            list.style.list_style = match kind {
+                "a" => Some(ListStyle::LowerAlpha),
+                "A" => Some(ListStyle::UpperAlpha),
+                "i" => Some(ListStyle::LowerRoman),
+                "I" => Some(ListStyle::UpperRoman),
+                _ => Some(ListStyle::Decimal),
+            };
+        }
+        // A list's left padding is where browsers put the markers
+        if list.style.list_indent.is_none() {
+            list.style.list_indent = list.style.padding_left.take();
+        }
+        if list.style.cross_align.is_none() {
+            list.style.cross_align = Some(CrossAlign::Stretch);
+        }
+
+        for item_el in element.child_elements().filter(|e| e.tag == "li") {
+            let item_computed = self.compute(item_el, &computed.text);
+            if item_computed.display.as_deref() == Some("none") {
+                continue;
+            }
+
+            let mut item = JsonNode::new(NodeType::ListItem);
+            item.style = item_computed.style;
+            if item.style.cross_align.is_none() {
+                item.style.cross_align = Some(CrossAlign::Stretch);
+            }
+            // Markers take the item's text color, as in CSS
+            item.style.color = Some(item_computed.text.color.clone());
+            item.children = self.convert_children(item_el, &item_computed.text, false);
+            list.children.push(item);
+        }
+
+        list
+    }
+}
+
+fn text_node(text: String, style: &TextStyle) -> JsonNode {
+    let mut node = JsonNode::new(NodeType::Text);
+    node.text = Some(text);
+    node.style.font_size = Some(style.font_size);
+    node.style.font_weight = Some(style.font_weight.clone());
+    node.style.font_style = Some(style.font_style.clone());
+    node.style.color = Some(style.color.clone());
+    node.style.text_align = Some(style.text_align.clone());
+    node.style.line_height = style.line_height;
+    node.href = style.href.clone();
+    node
+}
+
+/// Turn pending inline content into nodes: one text node per line, with span
+/// children when the line mixes styles or contains images
+fn flush_inline(inline: &mut Vec<InlineItem>, in_row: bool, nodes: &mut Vec<JsonNode>) {
+    let items = std::mem::take(inline);
+    let mut lines: Vec<Vec<InlineItem>> = vec![Vec::new()];
+    for item in items {
+        match item {
+            InlineItem::Break => lines.push(Vec::new()),
+            other => lines.last_mut().unwrap().push(other),
+        }
+    }
+
+    for line in lines {
+        // Collapse whitespace and merge neighbouring runs with the same style
+        let mut merged: Vec<InlineItem> = Vec::new();
+        for item in line {
+            match item {
+                InlineItem::Run(text, style) => {
+                    let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if text.starts_with(char::is_whitespace) && !collapsed.is_empty() {
+                        collapsed.insert(0, ' ');
+                    }
+                    if text.ends_with(char::is_whitespace) {
+                        collapsed.push(' ');
+                    }
+                    if let Some(InlineItem::Run(prev, prev_style)) = merged.last_mut() {
+                        if prev.ends_with(' ') {
+                            collapsed = collapsed.trim_start().to_string();
+                        }
+                        if *prev_style == style {
+                            prev.push_str(&collapsed);
+                            continue;
+                        }
+                    }
+                    merged.push(InlineItem::Run(collapsed, style));
+                }
+                other => merged.push(other),
+            }
+        }
+
+        if let Some(InlineItem::Run(text, _)) = merged.first_mut() {
+            *text = text.trim_start().to_string();
+        }
+        if let Some(InlineItem::Run(text, _)) = merged.last_mut() {
+            *text = text.trim_end().to_string();
+        }
+        merged.retain(|item| !matches!(item, InlineItem::Run(t, _) if t.is_empty()));
+
+        match merged.len() {
+            0 => {}
+            1 => {
+                let node = match merged.pop().unwrap() {
+                    InlineItem::Run(text, style) => {
+                        let mut node = text_node(text, &style);
+                        if !in_row {
+                            node.style.width = Some(Dimension::Percent(100.0));
+                        }
+                        node
+                    }
+                    InlineItem::Node(node) => *node,
+                    InlineItem::Break => unreachable!(),
+                };
+                nodes.push(node);
+            }
+            _ => {
+                // Mixed styles become an inline formatting context of spans
+                let first_style = merged.iter().find_map(|item| match item {
+                    InlineItem::Run(_, style) => Some(style.clone()),
+                    _ => None,
+                });
+                let mut paragraph = text_node(String::new(), &first_style.unwrap_or_default());
+                paragraph.text = None;
+                paragraph.href = None;
+                if !in_row {
+                    paragraph.style.width = Some(Dimension::Percent(100.0));
+                }
+                for item in merged {
+                    match item {
+                        InlineItem::Run(text, style) => paragraph.children.push(text_node(text, &style)),
+                        InlineItem::Node(node) => paragraph.children.push(*node),
+                        InlineItem::Break => {}
+                    }
+                }
+                nodes.push(paragraph);
+            }
+        }
+    }
+}
+
+fn parse_cross_align(value: &str) -> CrossAlign {
+    match value {
+        "center" => CrossAlign::Center,
+        "flex-end" | "end" => CrossAlign::End,
+        "stretch" | "normal" => CrossAlign::Stretch,
+        "baseline" | "first baseline" => CrossAlign::Baseline,
+        _ => CrossAlign::Start,
+    }
+}
+
+/// Map one CSS declaration onto the node style or the inherited text style
+fn apply_declaration(computed: &mut Computed, prop: &str, value: &str) {
+    let fs = computed.text.font_size;
+    let style = &mut computed.style;
+    let lower = value.to_ascii_lowercase();
+
+    match prop {
+        "display" => computed.display = Some(lower),
+
+        // --- Dimensions ---
+        "width" => style.width = parse_dimension(value, fs),
+        "height" => style.height = parse_dimension(value, fs),
+        "min-width" => style.min_width = parse_dimension(value, fs),
+        "min-height" => style.min_height = parse_dimension(value, fs),
+        "max-width" => style.max_width = parse_dimension(value, fs),
+        "max-height" => style.max_height = parse_dimension(value, fs),
+
+        // --- Positioning ---
+        "position" => {
+            style.position = match lower.as_str() {
+                "relative" => Some(Position::Relative),
+                "absolute" => Some(Position::Absolute),
+                "fixed" => Some(Position::Fixed),
+                _ => Some(Position::Static),
+            }
+        }
+        "top" => style.top = parse_length(value, fs),
+        "right" => style.right = parse_length(value, fs),
+        "bottom" => style.bottom = parse_length(value, fs),
+        "left" => style.left = parse_length(value, fs),
+        "z-index" => style.z_index = lower.parse().ok(),
+
+        // --- Flex ---
+        "flex-direction" => {
+            style.direction = match lower.as_str() {
+                "column" => Some(Direction::Column),
+                "column-reverse" => Some(Direction::ColumnReverse),
+                "row-reverse" => Some(Direction::RowReverse),
+                _ => Some(Direction::Row),
+            }
+        }
+        "flex-wrap" => style.wrap = Some(lower != "nowrap"),
+        "justify-content" => {
+            style.main_align = match lower.as_str() {
+                "center" => Some(MainAlign::Center),
+                "flex-end" | "end" | "right" => Some(MainAlign::End),
+                "space-between" => Some(MainAlign::SpaceBetween),
+                "space-around" => Some(MainAlign::SpaceAround),
+                "space-evenly" => Some(MainAlign::SpaceEvenly),
+                _ => Some(MainAlign::Start),
+            }
+        }
+        "align-items" => style.cross_align = Some(parse_cross_align(&lower)),
+        "align-self" => {
+            style.align_self = if lower == "auto" { None } else { Some(parse_cross_align(&lower)) }
+        }
+        "gap" | "grid-gap" => {
+            // `gap: <row> [<column>]`
+            let mut parts = value.split_whitespace().map(|v| parse_length(v, fs));
+            if let Some(Some(g)) = parts.next() {
+                style.gap = Some(g);
+            }
+            if let Some(Some(g)) = parts.next() {
+                style.column_gap = Some(g);
+            }
+        }
+        "row-gap" | "grid-row-gap" => style.row_gap = parse_length(value, fs),
+        "column-gap" | "grid-column-gap" => style.column_gap = parse_length(value, fs),
+
+        // --- Grid ---
+        "grid-template-columns" => style.grid_template_columns = Some(parse_grid_tracks(value, fs)),
+        "grid-template-rows" => style.grid_template_rows = Some(parse_grid_tracks(value, fs)),
+        "grid-column" => style.grid_column = GridPlacement::parse(&lower),
+        "grid-row" => style.grid_row = GridPlacement::parse(&lower),
+        "flex" => {
+            let (grow, shrink, basis) = match lower.as_str() {
+                "none" => (0.0, 0.0, None),
+                "auto" => (1.0, 1.0, None),
+                v => {
+                    // `flex: <grow> [<shrink>] [<basis>]`; without a basis items keep their content size
+                    let mut numbers = Vec::new();
+                    let mut basis = None;
+                    for part in v.split_whitespace() {
+                        match part.parse::<f32>() {
+                            Ok(n) if numbers.len() < 2 => numbers.push(n),
+                            _ if part == "auto" || part == "content" => basis = None,
+                            _ => basis = parse_dimension(part, fs),
+                        }
+                    }
+                    if numbers.is_empty() {
+                        (1.0, 1.0, basis)
+                    } else {
+                        (numbers[0], numbers.get(1).copied().unwrap_or(1.0), basis)
+                    }
+                }
+            };
+            style.flex = Some(grow);
+            style.flex_shrink = Some(shrink);
+            style.flex_basis = basis;
+        }
+        "flex-grow" => style.flex = value.trim().parse().ok(),
+        "flex-shrink" => style.flex_shrink = value.trim().parse().ok(),
+        "flex-basis" => style.flex_basis = if lower == "auto" { None } else { parse_dimension(value, fs) },
+        "order" => style.order = value.trim().parse().ok(),
+
+        // --- Spacing ---
+        "padding" => {
+            if let Some((t, r, b, l)) = parse_box(value, fs) {
+                style.padding_top = Some(t);
+                style.padding_right = Some(r);
+                style.padding_bottom = Some(b);
+                style.padding_left = Some(l);
+            }
+        }
+        "padding-top" => style.padding_top = parse_length(value, fs),
+        "padding-right" => style.padding_right = parse_length(value, fs),
+        "padding-bottom" => style.padding_bottom = parse_length(value, fs),
+        "padding-left" => style.padding_left = parse_length(value, fs),
+        "margin" => {
+            if let Some((t, r, b, l)) = parse_box(value, fs) {
+                style.margin_top = Some(t);
+                style.margin_right = Some(r);
+                style.margin_bottom = Some(b);
+                style.margin_left = Some(l);
+            }
+        }
+        "margin-top" => style.margin_top = parse_length(value, fs),
+        "margin-right" => style.margin_right = parse_length(value, fs),
+        "margin-bottom" => style.margin_bottom = parse_length(value, fs),
+        "margin-left" => style.margin_left = parse_length(value, fs),
+
+        // --- Background ---
+        "background-color" => style.background_color = parse_css_color(value),
+        "background-image" => style.background_image = parse_css_gradient(value),
+        "background" => {
+            if let Some(gradient) = parse_css_gradient(value) {
+                style.background_image = Some(gradient);
+            } else {
+                let color = parse_css_color(value)
+                    .or_else(|| value.split_whitespace().find_map(parse_css_color));
+                if color.is_some() {
+                    style.background_color = color;
+                }
+            }
+        }
+        "box-shadow" => style.box_shadow = parse_box_shadow(value, fs),
+        "transform" if lower == "none" => style.transform = None,
+        "transform" => style.transform = TransformFunction::parse_list(value, |v| parse_dimension(v, fs)),
+        "transform-origin" => style.transform_origin = TransformOrigin::parse(value, |v| parse_dimension(v, fs)),
+        "opacity" => style.opacity = value.trim().parse().ok(),
+        "overflow" => {
+            style.overflow = match lower.as_str() {
+                "hidden" | "clip" | "scroll" | "auto" => Some(Overflow::Hidden),
+                _ => Some(Overflow::Visible),
+            }
+        }
+
+        // --- Borders ---
+        "border" => {
+            let (width, color, border_style) = parse_border(value, fs);
+            style.border_width = width;
+            style.border_color = color;
+            style.border_style = border_style;
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" => {
+            let (width, color, border_style) = parse_border(value, fs);
+            let side = match prop {
+                "border-top" => &mut style.border_top,
+                "border-right" => &mut style.border_right,
+                "border-bottom" => &mut style.border_bottom,
+                _ => &mut style.border_left,
+            };
+            *side = Some(crate::layout::BorderSide { width, color, style: border_style });
+        }
+        "border-width" => {
+            let widths: Vec<String> = lower.split_whitespace()
+                .map(|w| border_width_keyword(w).map_or_else(|| w.to_string(), |pt| format!("{}pt", pt)))
+                .collect();
+            if let Some((t, r, b, l)) = parse_box(&widths.join(" "), fs) {
+                style.border_top_width = Some(t);
+                style.border_right_width = Some(r);
+                style.border_bottom_width = Some(b);
+                style.border_left_width = Some(l);
+            }
+        }
+        "border-color" => style.border_color = parse_css_color(value),
+        "border-style" => {
+            let styles: Vec<BorderStyle> = lower.split_whitespace().filter_map(parse_border_style).collect();
+            let (t, r, b, l) = match styles.as_slice() {
+                [a] => (a, a, a, a),
+                [v, h] => (v, h, v, h),
+                [t, h, b] => (t, h, b, h),
+                [t, r, b, l] => (t, r, b, l),
+                _ => return,
+            };
+            style.border_top_style = Some(t.clone());
+            style.border_right_style = Some(r.clone());
+            style.border_bottom_style = Some(b.clone());
+            style.border_left_style = Some(l.clone());
+        }
+        "border-top-style" => style.border_top_style = parse_border_style(value),
+        "border-right-style" => style.border_right_style = parse_border_style(value),
+        "border-bottom-style" => style.border_bottom_style = parse_border_style(value),
+        "border-left-style" => style.border_left_style = parse_border_style(value),
+        "border-radius" => {
+            if let Some((tl, tr, br, bl)) = parse_box(value, fs) {
+                style.border_top_left_radius = Some(tl);
+                style.border_top_right_radius = Some(tr);
+                style.border_bottom_right_radius = Some(br);
+                style.border_bottom_left_radius = Some(bl);
+            }
+        }
+        "border-top-left-radius" => style.border_top_left_radius = parse_length(value, fs),
+        "border-top-right-radius" => style.border_top_right_radius = parse_length(value, fs),
+        "border-bottom-right-radius" => style.border_bottom_right_radius = parse_length(value, fs),
+        "border-bottom-left-radius" => style.border_bottom_left_radius = parse_length(value, fs),
+        "border-top-width" => style.border_top_width = parse_length(value, fs),
+        "border-right-width" => style.border_right_width = parse_length(value, fs),
+        "border-bottom-width" => style.border_bottom_width = parse_length(value, fs),
+        "border-left-width" => style.border_left_width = parse_length(value, fs),
+        "border-top-color" => style.border_top_color = parse_css_color(value),
+        "border-right-color" => style.border_right_color = parse_css_color(value),
+        "border-bottom-color" => style.border_bottom_color = parse_css_color(value),
+        "border-left-color" => style.border_left_color = parse_css_color(value),
+
+        // --- Image ---
+        "object-fit" => {
+            style.object_fit = match lower.as_str() {
+                "cover" => Some(ObjectFit::Cover),
+                "fill" => Some(ObjectFit::Fill),
+                "none" => Some(ObjectFit::None),
+                "scale-down" => Some(ObjectFit::ScaleDown),
+                _ => Some(ObjectFit::Contain),
+            }
+        }
+
+        "vertical-align" => {
+            style.vertical_align = match lower.as_str() {
+                "top" | "text-top" => Some(VerticalAlign::Top),
+                "middle" => Some(VerticalAlign::Middle),
+                "bottom" | "text-bottom" => Some(VerticalAlign::Bottom),
+                "super" => Some(VerticalAlign::Super),
+                "sub" => Some(VerticalAlign::Sub),
+                _ => Some(VerticalAlign::Baseline),
+            }
+        }
+
+        // --- Lists ---
+        "list-style-type" | "list-style" => {
+            for token in lower.split_whitespace() {
+                let list_style = match token {
+                    "disc" => ListStyle::Disc,
+                    "circle" => ListStyle::Circle,
+                    "square" => ListStyle::Square,
+                    "decimal" => ListStyle::Decimal,
+                    "lower-alpha" | "lower-latin" => ListStyle::LowerAlpha,
+                    "upper-alpha" | "upper-latin" => ListStyle::UpperAlpha,
+                    "lower-roman" => ListStyle::LowerRoman,
+                    "upper-roman" => ListStyle::UpperRoman,
+                    "none" => ListStyle::None,
+                    _ => continue,
+                };
+                style.list_style = Some(list_style);
+            }
+        }
+
+        // --- Text (inherited) ---
+        "color" => {
+            if let Some(c) = parse_css_color(value) {
+                computed.text.color = c;
+            }
+        }
+        "font-weight" => {
+            if let Some(w) = parse_font_weight(value) {
+                computed.text.font_weight = w;
+            }
+        }
+        "font-style" => {
+            computed.text.font_style = if lower == "italic" || lower == "oblique" {
+                FontStyle::Italic
+            } else {
+                FontStyle::Normal
+            };
+        }
+        "text-align" => {
+            computed.text.text_align = match lower.as_str() {
+                "center" | "middle" => TextAlign::Center,
+                "right" | "end" => TextAlign::Right,
+                "justify" => TextAlign::Justify,
+                _ => TextAlign::Left,
+            };
+        }
+        "line-height" => {
+            computed.text.line_height = if lower == "normal" {
+                None
+            } else if let Ok(multiplier) = lower.parse::<f32>() {
+                Some(multiplier)
+            } else if let Some(pct) = lower.strip_suffix('%') {
+                pct.trim().parse::<f32>().ok().map(|p| p / 100.0)
+            } else {
+                parse_length(value, fs).map(|h| h / fs)
+            };
+        }
+
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_elements_and_entities() {
+        let (dom, _) = parse_html("<div id=\"a\"><p>Fish &amp; chips<br/>2 &lt; 3</p></div>").unwrap();
+        let div = find_element(&dom, "div").unwrap();
+        assert_eq!(div.attr("id"), Some("a"));
+        let p = find_element(div, "p").unwrap();
+        assert_eq!(p.children.len(), 3);
+        match &p.children[0] {
+            HtmlNode::Text(t) => assert_eq!(t, "Fish & chips"),
+            _ => panic!("expected text"),
+        }
+    }
+
+    #[test]
+    fn test_implied_end_tags() {
+        let (dom, _) = parse_html("<ul><li>one<li>two</ul>").unwrap();
+        let ul = find_element(&dom, "ul").unwrap();
+        assert_eq!(ul.child_elements().count(), 2);
+    }
+
+    #[test]
+    fn test_css_specificity_order() {
+        let html = "<style>p { color: red } .note { color: blue } p { font-size: 20px }</style>\
+                    <p class=\"note\">Hello</p>";
+        let page = html_to_node(html).unwrap();
+        let text = &page.children[0];
+        assert_eq!(text.node_type, NodeType::Text);
+        assert_eq!(text.style.color, Some(Color { r: 0, g: 0, b: 255, a: 1.0 }));
+        assert_eq!(text.style.font_size, Some(15.0));
+    }
+
+    #[test]
+    fn test_lengths_convert_to_points() {
+        assert_eq!(parse_length("16px", 12.0), Some(12.0));
+        assert_eq!(parse_length("2em", 10.0), Some(20.0));
+        assert_eq!(parse_dimension("50%", 12.0), Some(Dimension::Percent(50.0)));
+        assert_eq!(parse_box("4pt 8pt", 12.0), Some((4.0, 8.0, 4.0, 8.0)));
+    }
+
+    #[test]
+    fn test_table_spans_and_mixed_inline_runs() {
+        let html = "<table><tr><td colspan=\"2\">A <b>bold</b> word</td></tr></table>";
+        let page = html_to_node(html).unwrap();
+        let table = &page.children[0];
+        assert_eq!(table.node_type, NodeType::Table);
+        let cell = &table.children[0].children[0];
+        assert_eq!(cell.col_span, Some(2));
+        // Mixed styles become a text node with spans
+        let paragraph = &cell.children[0];
+        assert_eq!(paragraph.node_type, NodeType::Text);
+        assert_eq!(paragraph.children.len(), 3);
+        assert_eq!(paragraph.children[1].text.as_deref(), Some("bold"));
+        assert_eq!(paragraph.children[1].style.font_weight, Some(FontWeight::Bold));
+    }
+
+    #[test]
+    fn test_header_cells_and_alt_text() {
+        let html = "<table><tr><th>Item</th><td>Price</td></tr></table><img src=\"logo.png\" alt=\"Company logo\">";
+        let page = html_to_node(html).unwrap();
+        let row = &page.children[0].children[0];
+        assert_eq!(row.children[0].header, Some(true));
+        assert_eq!(row.children[1].header, None);
+        assert_eq!(page.children[1].alt.as_deref(), Some("Company logo"));
+    }
+
+    #[test]
+    fn test_language_and_roles() {
+        let html = "<html lang=\"sv\"><body><p lang=\"en\">Hello</p><img src=\"rule.png\" alt=\"\"><figcaption>Bild 1</figcaption></body></html>";
+        let page = html_to_node(html).unwrap();
+        assert_eq!(page.lang.as_deref(), Some("sv"));
+        assert_eq!(page.children[0].lang.as_deref(), Some("en"));
+        assert_eq!(page.children[1].role, Some(Role::Artifact));
+        assert_eq!(page.children[2].role, Some(Role::Caption));
+    }
+
+    #[test]
+    fn test_lists_become_list_nodes() {
+        let page = html_to_node("<ol start=\"3\" type=\"i\"><li>One</li><li>Two</li></ol>").unwrap();
+        let list = &page.children[0];
+        assert_eq!(list.node_type, NodeType::List);
+        assert_eq!(list.start, Some(3));
+        assert_eq!(list.style.list_style, Some(ListStyle::LowerRoman));
+        assert_eq!(list.style.list_indent, Some(30.0));
+        assert_eq!(list.children.len(), 2);
+        assert_eq!(list.children[0].node_type, NodeType::ListItem);
+    }
+
+    #[test]
+    fn test_flex_item_properties() {
+        let html = "<div style=\"display: flex; flex-direction: row-reverse\">\
+                    <div style=\"flex: 2 0 100px; order: -1\"><p>A</p><p>B</p></div></div>";
+        let page = html_to_node(html).unwrap();
+        let row = &page.children[0];
+        assert_eq!(row.style.direction, Some(Direction::RowReverse));
+        let item = &row.children[0].style;
+        assert_eq!(item.flex, Some(2.0));
+        assert_eq!(item.flex_shrink, Some(0.0));
+        assert_eq!(item.flex_basis, Some(Dimension::Pt(75.0)));
+        assert_eq!(item.order, Some(-1));
+    }
+
+    #[test]
+    fn test_grid_container() {
+        let html = "<div style=\"display: grid; grid-template-columns: 80px repeat(2, 1fr); gap: 4px 8px\">\
+                    <div style=\"grid-column: 2 / span 2\">A</div><div>B</div></div>";
+        let page = html_to_node(html).unwrap();
+        let grid = &page.children[0];
+        assert_eq!(grid.style.display, Some(Display::Grid));
+        assert_eq!(
+            grid.style.grid_template_columns,
+            Some(vec![GridTrack::Pt(60.0), GridTrack::Fr(1.0), GridTrack::Fr(1.0)])
+        );
+        assert_eq!(grid.style.gap, Some(3.0));
+        assert_eq!(grid.style.column_gap, Some(6.0));
+        assert_eq!(grid.children.len(), 2);
+        assert_eq!(grid.children[0].style.grid_column, Some(GridPlacement { start: Some(2), span: 2 }));
+    }
+
+    #[test]
+    fn test_gradient_background() {
+        let html = "<div style=\"background: linear-gradient(to right, rgb(255, 0, 0), #0000ff 75%)\">A</div>\
+                    <div style=\"background-image: radial-gradient(circle, white, black)\">B</div>";
+        let page = html_to_node(html).unwrap();
+
+        let linear = page.children[0].style.background_image.as_ref().unwrap();
+        assert_eq!(linear.kind, GradientType::LinearGradient);
+        assert_eq!(linear.angle, Some(90.0));
+        assert_eq!(linear.stops.len(), 2);
+        assert_eq!(linear.stops[0].color, Color { r: 255, g: 0, b: 0, a: 1.0 });
+        assert_eq!(linear.stops[1].offset, Some(0.75));
+
+        let radial = page.children[1].style.background_image.as_ref().unwrap();
+        assert_eq!(radial.kind, GradientType::RadialGradient);
+        assert_eq!(radial.stops.len(), 2);
+    }
+
+    #[test]
+    fn test_box_shadow() {
+        let html = "<div style=\"box-shadow: 0 4px 8px rgba(0, 0, 0, 0.25), 0 1px 2px black\">A</div>\
+                    <div style=\"box-shadow: inset 0 0 0 2px #ccc\">B</div>\
+                    <div style=\"box-shadow: none\">C</div>";
+        let page = html_to_node(html).unwrap();
+
+        let outer = page.children[0].style.box_shadow.as_ref().unwrap();
+        assert_eq!((outer.offset_x, outer.offset_y, outer.blur, outer.spread), (0.0, 3.0, 6.0, 0.0));
+        assert_eq!(outer.color, Some(Color { r: 0, g: 0, b: 0, a: 0.25 }));
+        assert!(!outer.inset);
+
+        let inset = page.children[1].style.box_shadow.as_ref().unwrap();
+        assert!(inset.inset);
+        assert_eq!(inset.spread, 1.5);
+
+        assert!(page.children[2].style.box_shadow.is_none());
+    }
+
+    #[test]
+    fn test_border_styles() {
+        let html = "<div style=\"border: 2px dashed #999; border-bottom-style: double\">A</div>\
+                    <div style=\"border-width: 1px; border-style: solid dotted\">B</div>\
+                    <div style=\"border-width: thin medium thick 2px\">C</div>";
+        let page = html_to_node(html).unwrap();
+
+        let dashed = &page.children[0].style;
+        assert_eq!(dashed.border_width, Some(1.5));
+        assert_eq!(dashed.border_style, Some(BorderStyle::Dashed));
+        let (top, _, bottom, _) = dashed.border_styles();
+        assert_eq!((top, bottom), (BorderStyle::Dashed, BorderStyle::Double));
+
+        let (top, right, bottom, left) = page.children[1].style.border_styles();
+        assert_eq!(
+            (top, right, bottom, left),
+            (BorderStyle::Solid, BorderStyle::Dotted, BorderStyle::Solid, BorderStyle::Dotted)
+        );
+
+        assert_eq!(page.children[2].style.border_widths(), (0.75, 2.25, 3.75, 1.5));
+    }
+
+    #[test]
+    fn test_transform() {
+        let html = "<div style=\"transform: rotate(-0.25turn) translateX(8px) scale(2, 1); transform-origin: left top\">A</div>";
+        let page = html_to_node(html).unwrap();
+        let style = &page.children[0].style;
+        assert_eq!(
+            style.transform,
+            Some(vec![
+                TransformFunction::Rotate(-90.0),
+                TransformFunction::Translate(Dimension::Pt(6.0), Dimension::Pt(0.0)),
+                TransformFunction::Scale(2.0, 1.0),
+            ])
+        );
+        assert_eq!(
+            style.transform_origin,
+            Some(TransformOrigin { x: Dimension::Percent(0.0), y: Dimension::Percent(0.0) })
+        );
+    }
+
+    #[test]
+    fn test_links_ids_and_bookmarks() {
+        let html = "<h2 id=\"terms\">Terms</h2><p>See <a href=\"#terms\">the terms</a> or <a href=\"mailto:x@y.z\"><b>mail</b> us</a>.</p>";
+        let page = html_to_node(html).unwrap();
+        assert_eq!(page.children[0].id.as_deref(), Some("terms"));
+        assert_eq!(page.children[0].bookmark, Some(Bookmark { title: None, level: Some(2) }));
+
+        let spans = &page.children[1].children;
+        assert_eq!(page.children[1].href, None);
+        assert_eq!(spans[0].href, None);
+        assert_eq!(spans[1].href.as_deref(), Some("#terms"));
+        assert_eq!(spans[3].href.as_deref(), Some("mailto:x@y.z"));
+        assert_eq!(spans[4].href.as_deref(), Some("mailto:x@y.z"));
+        assert_eq!(spans[5].href, None);
+    }
+
+    #[test]
+    fn test_payload_document_options() {
+        let json = r#"{ "html": "<p>Quote</p>", "watermark": { "text": "DRAFT", "layer": "under" } }"#;
+        let payload: HtmlPayload = serde_json::from_str(json).unwrap();
+        let watermark = payload.document.watermark.unwrap();
+        assert_eq!(watermark.text.as_deref(), Some("DRAFT"));
+        assert_eq!(watermark.layer, Some(crate::layout::WatermarkLayer::Under));
+    }
+}
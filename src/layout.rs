@@ -15,6 +15,33 @@ pub enum NodeType {
     Table,
     Row,
     Cell,
+    List,
+    #[serde(alias = "listItem", alias = "list_item")]
+    ListItem,
+    /// Table of contents: the engine fills it with an entry per bookmarked node and heading
+    Toc,
+    /// Paperclip icon opening an embedded file
+    #[serde(alias = "fileAttachment", alias = "file_attachment")]
+    FileAttachment,
+    // Form fields, filled in by the reader
+    #[serde(alias = "textField", alias = "text_field")]
+    TextField,
+    Checkbox,
+    /// One field of mutually exclusive options, listed under each other
+    #[serde(alias = "radioGroup", alias = "radio_group")]
+    RadioGroup,
+    Dropdown,
+    #[serde(alias = "signatureField", alias = "signature_field")]
+    SignatureField,
+}
+
+impl NodeType {
+    pub fn is_form_field(&self) -> bool {
+        matches!(
+            self,
+            NodeType::TextField | NodeType::Checkbox | NodeType::RadioGroup | NodeType::Dropdown | NodeType::SignatureField
+        )
+    }
 }
 
 // ============================================================================
@@ -42,10 +69,35 @@ pub enum ObjectFit {
 // ============================================================================
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "kebab-case")]
 pub enum Direction {
     Row,
     Column,
+    /// Row laid out from the right edge, last child first
+    RowReverse,
+    /// Column laid out from the bottom edge, last child first
+    ColumnReverse,
+}
+
+impl Direction {
+    /// Main axis is horizontal
+    pub fn is_row(&self) -> bool {
+        matches!(self, Direction::Row | Direction::RowReverse)
+    }
+
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, Direction::RowReverse | Direction::ColumnReverse)
+    }
+}
+
+/// How a container lays out its children (like CSS display)
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Display {
+    /// Children flow along `direction` (default)
+    Flex,
+    /// Children fill the cells of `gridTemplateColumns` x `gridTemplateRows`
+    Grid,
 }
 
 /// Main-axis alignment (justify-content)
@@ -68,6 +120,51 @@ pub enum CrossAlign {
     Center,
     End,
     Stretch,
+    /// Line up the first text baselines of the children (rows only; start in columns)
+    Baseline,
+}
+
+/// List marker style (like CSS list-style-type)
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ListStyle {
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    None,
+}
+
+/// Vertical alignment of a box inside a line of inline content (like CSS vertical-align)
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalAlign {
+    /// Sit on the baseline (text baseline, or bottom edge for images/icons)
+    Baseline,
+    /// Align the top with the top of the line
+    Top,
+    /// Center on the middle of the parent's lowercase letters
+    Middle,
+    /// Align the bottom with the bottom of the line
+    Bottom,
+    /// Raise the baseline (superscript)
+    Super,
+    /// Lower the baseline (subscript)
+    Sub,
+}
+
+/// What happens to content that extends past a box (like CSS overflow)
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Content is drawn outside the box (default)
+    Visible,
+    /// Content is clipped to the inside of the box's border, following its radii
+    Hidden,
 }
 
 /// Position type (like CSS position)
@@ -80,6 +177,8 @@ pub enum Position {
     Relative,
     /// Positioned relative to nearest positioned ancestor (or page)
     Absolute,
+    /// Positioned relative to the page box and repeated on every page
+    Fixed,
 }
 
 impl Default for Position {
@@ -110,6 +209,35 @@ impl Dimension {
     pub fn is_percent(&self) -> bool {
         matches!(self, Dimension::Percent(_))
     }
+
+    /// Parse `12`, `"12pt"` or `"50%"`
+    pub fn parse(value: &str) -> Option<Dimension> {
+        let v = value.trim();
+        match v.strip_suffix('%') {
+            Some(p) => p.trim().parse().ok().map(Dimension::Percent),
+            None => v.strip_suffix("pt").unwrap_or(v).trim().parse().ok().map(Dimension::Pt),
+        }
+    }
+}
+
+/// flex-basis accepts a dimension or "auto" (same as not setting it)
+fn deserialize_flex_basis<'de, D>(deserializer: D) -> Result<Option<Dimension>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FlexBasis {
+        Auto(String),
+        Dimension(Dimension),
+    }
+
+    match Option::<FlexBasis>::deserialize(deserializer)? {
+        Some(FlexBasis::Auto(s)) if s.trim() == "auto" => Ok(None),
+        Some(FlexBasis::Auto(s)) => Dimension::deserialize(serde::de::value::StrDeserializer::<D::Error>::new(&s)).map(Some),
+        Some(FlexBasis::Dimension(d)) => Ok(Some(d)),
+        None => Ok(None),
+    }
 }
 
 // Custom deserializer for Dimension to handle both numbers and strings like "50%"
@@ -174,6 +302,316 @@ impl<'de> Deserialize<'de> for Dimension {
     }
 }
 
+// ============================================================================
+// GRID
+// ============================================================================
+
+/// Size of one grid column or row
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridTrack {
+    /// Fixed size in points
+    Pt(f32),
+    /// Percentage of the grid's inner size
+    Percent(f32),
+    /// Share of the space left over by the other tracks ("1fr")
+    Fr(f32),
+    /// As large as the largest item in the track
+    Auto,
+}
+
+impl GridTrack {
+    /// Parse one track: `120`, `"25%"`, `"1fr"` or `"auto"`
+    pub fn parse(value: &str) -> Option<GridTrack> {
+        let v = value.trim();
+        if v == "auto" {
+            Some(GridTrack::Auto)
+        } else if let Some(fr) = v.strip_suffix("fr") {
+            fr.trim().parse().ok().map(GridTrack::Fr)
+        } else if let Some(p) = v.strip_suffix('%') {
+            p.trim().parse().ok().map(GridTrack::Percent)
+        } else {
+            v.strip_suffix("pt").unwrap_or(v).parse().ok().map(GridTrack::Pt)
+        }
+    }
+}
+
+/// Split a track list like `"100 repeat(3, 1fr) auto"` into single tracks
+pub fn split_grid_tracks(value: &str) -> Vec<String> {
+    let mut tracks = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix("repeat(") {
+            let close = inner.find(')').unwrap_or(inner.len());
+            let (count, pattern) = inner[..close].split_once(',').unwrap_or(("1", &inner[..close]));
+            for _ in 0..count.trim().parse::<usize>().unwrap_or(1) {
+                tracks.extend(pattern.split_whitespace().map(str::to_string));
+            }
+            rest = inner.get(close + 1..).unwrap_or("").trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tracks.push(rest[..end].to_string());
+            rest = rest[end..].trim_start();
+        }
+    }
+    tracks
+}
+
+/// Track lists accept an array (`[100, "1fr", "auto"]`) or a CSS-like string (`"100 repeat(2, 1fr)"`)
+fn deserialize_grid_tracks<'de, D>(deserializer: D) -> Result<Option<Vec<GridTrack>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Track {
+        Number(f32),
+        Text(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tracks {
+        List(Vec<Track>),
+        Text(String),
+    }
+
+    let parse = |v: &str| GridTrack::parse(v).ok_or_else(|| D::Error::custom(format!("invalid grid track: {}", v)));
+    match Option::<Tracks>::deserialize(deserializer)? {
+        Some(Tracks::List(list)) => list.into_iter()
+            .map(|t| match t {
+                Track::Number(n) => Ok(GridTrack::Pt(n)),
+                Track::Text(s) => parse(&s),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        Some(Tracks::Text(s)) => split_grid_tracks(&s).iter().map(|t| parse(t)).collect::<Result<Vec<_>, _>>().map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Where a grid item sits along one axis (like CSS grid-column / grid-row)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridPlacement {
+    /// 1-based start line (None = auto-placed)
+    pub start: Option<usize>,
+    /// Number of tracks covered
+    pub span: usize,
+}
+
+impl GridPlacement {
+    /// Parse `2`, `"span 2"`, `"1 / 3"` or `"2 / span 2"`
+    pub fn parse(value: &str) -> Option<GridPlacement> {
+        let line = |v: &str| v.trim().parse::<usize>().ok().filter(|&n| n > 0);
+        let span = |v: &str| v.trim().strip_prefix("span").and_then(|n| n.trim().parse::<usize>().ok()).filter(|&n| n > 0);
+
+        let (first, second) = match value.split_once('/') {
+            Some((a, b)) => (a.trim(), Some(b.trim())),
+            None => (value.trim(), None),
+        };
+        let start = if first == "auto" || first.starts_with("span") { None } else { Some(line(first)?) };
+        let first_span = if first.starts_with("span") { Some(span(first)?) } else { None };
+
+        let span = match second {
+            None | Some("auto") => first_span.unwrap_or(1),
+            Some(end) if end.starts_with("span") => span(end)?,
+            Some(end) => line(end)?.saturating_sub(start?).max(1),
+        };
+        Some(GridPlacement { start, span })
+    }
+}
+
+impl<'de> Deserialize<'de> for GridPlacement {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Line(usize),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Line(n) if n > 0 => Ok(GridPlacement { start: Some(n), span: 1 }),
+            Raw::Line(n) => Err(D::Error::custom(format!("invalid grid line: {}", n))),
+            Raw::Text(s) => GridPlacement::parse(&s)
+                .ok_or_else(|| D::Error::custom(format!("invalid grid placement: {}", s))),
+        }
+    }
+}
+
+// ============================================================================
+// TRANSFORM
+// ============================================================================
+
+/// One CSS transform function; angles are in degrees and y points down, like CSS
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformFunction {
+    /// Clockwise
+    Rotate(f32),
+    Scale(f32, f32),
+    /// Percentages are of the node's own size
+    Translate(Dimension, Dimension),
+    Skew(f32, f32),
+    /// `matrix(a, b, c, d, e, f)`
+    Matrix([f32; 6]),
+}
+
+impl TransformFunction {
+    /// Parse a transform list like `"rotate(-30deg) scale(1.5)"`, with `length`
+    /// parsing translate arguments; `None` if any function is invalid
+    pub fn parse_list(value: &str, length: impl Fn(&str) -> Option<Dimension>) -> Option<Vec<TransformFunction>> {
+        use TransformFunction::*;
+
+        let mut functions = Vec::new();
+        let mut rest = value.trim();
+        while !rest.is_empty() {
+            let open = rest.find('(')?;
+            let close = open + rest[open..].find(')')?;
+            let name = rest[..open].trim().to_ascii_lowercase();
+            let args: Vec<&str> = rest[open + 1..close]
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|a| !a.is_empty())
+                .collect();
+            let numbers = || args.iter().map(|a| a.parse::<f32>().ok()).collect::<Option<Vec<_>>>();
+            let angles = || args.iter().map(|a| parse_angle(a)).collect::<Option<Vec<_>>>();
+            let lengths = || args.iter().map(|a| length(a)).collect::<Option<Vec<_>>>();
+            let zero = || Dimension::Pt(0.0);
+
+            functions.push(match (name.as_str(), args.len()) {
+                ("rotate", 1) => Rotate(angles()?[0]),
+                ("scale", 1) => {
+                    let s = numbers()?[0];
+                    Scale(s, s)
+                }
+                ("scale", 2) => {
+                    let s = numbers()?;
+                    Scale(s[0], s[1])
+                }
+                ("scalex", 1) => Scale(numbers()?[0], 1.0),
+                ("scaley", 1) => Scale(1.0, numbers()?[0]),
+                ("translate", 1 | 2) => {
+                    let mut l = lengths()?.into_iter();
+                    Translate(l.next()?, l.next().unwrap_or_else(zero))
+                }
+                ("translatex", 1) => Translate(lengths()?.remove(0), zero()),
+                ("translatey", 1) => Translate(zero(), lengths()?.remove(0)),
+                ("skew", 1 | 2) => {
+                    let a = angles()?;
+                    Skew(a[0], a.get(1).copied().unwrap_or(0.0))
+                }
+                ("skewx", 1) => Skew(angles()?[0], 0.0),
+                ("skewy", 1) => Skew(0.0, angles()?[0]),
+                ("matrix", 6) => {
+                    let m = numbers()?;
+                    Matrix([m[0], m[1], m[2], m[3], m[4], m[5]])
+                }
+                _ => return None,
+            });
+            rest = rest[close + 1..].trim_start();
+        }
+        Some(functions)
+    }
+}
+
+/// Angle in degrees from `30`, `"30deg"`, `"0.5rad"`, `"100grad"` or `"0.25turn"`
+pub fn parse_angle(value: &str) -> Option<f32> {
+    let v = value.trim();
+    let units = [("deg", 1.0), ("grad", 0.9), ("rad", 180.0 / std::f32::consts::PI), ("turn", 360.0)];
+    units
+        .iter()
+        .find_map(|(unit, factor)| v.strip_suffix(unit).and_then(|n| n.trim().parse::<f32>().ok()).map(|n| n * factor))
+        .or_else(|| v.parse().ok())
+}
+
+/// Point a transform rotates and scales around, from the node's top-left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformOrigin {
+    pub x: Dimension,
+    pub y: Dimension,
+}
+
+impl Default for TransformOrigin {
+    fn default() -> Self {
+        TransformOrigin { x: Dimension::Percent(50.0), y: Dimension::Percent(50.0) }
+    }
+}
+
+impl TransformOrigin {
+    /// Parse `"center"`, `"top left"`, `"0 100%"`, ...; missing values are centered
+    pub fn parse(value: &str, length: impl Fn(&str) -> Option<Dimension>) -> Option<TransformOrigin> {
+        let mut x = None;
+        let mut y = None;
+        let mut unplaced = Vec::new();
+        for part in value.split_whitespace() {
+            match part.to_ascii_lowercase().as_str() {
+                "left" => x = Some(Dimension::Percent(0.0)),
+                "right" => x = Some(Dimension::Percent(100.0)),
+                "top" => y = Some(Dimension::Percent(0.0)),
+                "bottom" => y = Some(Dimension::Percent(100.0)),
+                "center" => unplaced.push(Dimension::Percent(50.0)),
+                other => unplaced.push(length(other)?),
+            }
+        }
+        // Centers and lengths fill x, then y
+        for value in unplaced {
+            if x.is_none() {
+                x = Some(value);
+            } else if y.is_none() {
+                y = Some(value);
+            } else {
+                return None;
+            }
+        }
+        let center = TransformOrigin::default();
+        Some(TransformOrigin { x: x.unwrap_or(center.x), y: y.unwrap_or(center.y) })
+    }
+}
+
+/// Transforms are CSS-like strings: `"rotate(-30) translate(10, 50%)"`
+fn deserialize_transform<'de, D>(deserializer: D) -> Result<Option<Vec<TransformFunction>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) if s.trim() == "none" => Ok(None),
+        Some(s) => TransformFunction::parse_list(&s, Dimension::parse)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("invalid transform: {}", s))),
+        None => Ok(None),
+    }
+}
+
+/// Transform origins accept keywords and lengths (`"top left"`, `"0 100%"`) or `[x, y]`
+fn deserialize_transform_origin<'de, D>(deserializer: D) -> Result<Option<TransformOrigin>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Origin {
+        Pair(Dimension, Dimension),
+        Text(String),
+    }
+
+    match Option::<Origin>::deserialize(deserializer)? {
+        Some(Origin::Pair(x, y)) => Ok(Some(TransformOrigin { x, y })),
+        Some(Origin::Text(s)) => TransformOrigin::parse(&s, Dimension::parse)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("invalid transform origin: {}", s))),
+        None => Ok(None),
+    }
+}
+
 /// Text alignment within a text box
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -231,15 +669,98 @@ impl Color {
     }
 }
 
+// ============================================================================
+// GRADIENT
+// ============================================================================
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GradientType {
+    LinearGradient,
+    /// Circle centered in the box, reaching its farthest corner
+    RadialGradient,
+}
+
+/// Gradient background, drawn over backgroundColor inside the border box
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Gradient {
+    #[serde(rename = "type")]
+    pub kind: GradientType,
+    /// Direction of a linear gradient in degrees, like CSS: 0 = to top, 90 = to right (default 180 = to bottom)
+    pub angle: Option<f32>,
+    pub stops: Vec<GradientStop>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct GradientStop {
+    /// Position along the gradient (0.0 - 1.0); missing offsets are spread evenly
+    pub offset: Option<f32>,
+    pub color: Color,
+}
+
+impl Gradient {
+    /// Stops with every offset resolved, clamped to 0-1 and never decreasing
+    pub fn resolved_stops(&self) -> Vec<(f32, Color)> {
+        let n = self.stops.len();
+        let mut stops: Vec<(f32, Color)> = Vec::with_capacity(n);
+        for (i, stop) in self.stops.iter().enumerate() {
+            let even = if n > 1 { i as f32 / (n - 1) as f32 } else { 0.0 };
+            let previous = stops.last().map_or(0.0, |s| s.0);
+            let offset = stop.offset.unwrap_or(even).clamp(0.0, 1.0).max(previous);
+            stops.push((offset, stop.color.clone()));
+        }
+        stops
+    }
+}
+
+// ============================================================================
+// SHADOW
+// ============================================================================
+
+/// Box shadow, following the box's border radii
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct BoxShadow {
+    #[serde(default, alias = "offsetX")]
+    pub offset_x: f32,
+    /// Positive values move the shadow down, like CSS
+    #[serde(default, alias = "offsetY")]
+    pub offset_y: f32,
+    /// Blur radius: the shadow fades out over this distance
+    #[serde(default)]
+    pub blur: f32,
+    /// Grows (or, when negative, shrinks) the shadow before blurring
+    #[serde(default)]
+    pub spread: f32,
+    /// Defaults to black
+    pub color: Option<Color>,
+    /// Draw the shadow inside the box, over its background
+    #[serde(default)]
+    pub inset: bool,
+}
+
 // ============================================================================
 // BORDER
 // ============================================================================
 
+/// Line style of a border; dash lengths scale with the border width
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderStyle {
+    Solid,
+    Dashed,
+    /// Round dots, one border width across
+    Dotted,
+    /// Two lines, each a third of the border width
+    Double,
+    None,
+}
+
 /// Individual border side
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct BorderSide {
     pub width: Option<f32>,
     pub color: Option<Color>,
+    pub style: Option<BorderStyle>,
 }
 
 /// Full border specification
@@ -248,6 +769,7 @@ pub struct Border {
     pub width: Option<f32>,
     pub color: Option<Color>,
     pub radius: Option<f32>,
+    pub style: Option<BorderStyle>,
 }
 
 // ============================================================================
@@ -275,6 +797,14 @@ pub struct Style {
     pub right: Option<f32>,
     pub bottom: Option<f32>,
     pub left: Option<f32>,
+    /// Paint order among the boxes of the same stacking context (higher is on top)
+    #[serde(alias = "zIndex")]
+    pub z_index: Option<i32>,
+    /// Drawn transformed around transformOrigin (default the center); doesn't affect layout
+    #[serde(default, deserialize_with = "deserialize_transform")]
+    pub transform: Option<Vec<TransformFunction>>,
+    #[serde(alias = "transformOrigin", default, deserialize_with = "deserialize_transform_origin")]
+    pub transform_origin: Option<TransformOrigin>,
 
     // --- Flex / Layout ---
     pub direction: Option<Direction>,
@@ -283,9 +813,40 @@ pub struct Style {
     pub main_align: Option<MainAlign>,
     #[serde(alias = "crossAlign")]
     pub cross_align: Option<CrossAlign>,
+    /// Per-child override of the parent's cross_align
+    #[serde(alias = "alignSelf")]
+    pub align_self: Option<CrossAlign>,
     pub gap: Option<f32>,
     /// flex-grow: how much of remaining space to take (0 = none, 1 = equal share)
     pub flex: Option<f32>,
+    /// flex-shrink: share of the overflow to give up when children don't fit (default 1)
+    #[serde(alias = "flexShrink")]
+    pub flex_shrink: Option<f32>,
+    /// flex-basis: main size before growing or shrinking (points, percent, or "auto")
+    #[serde(alias = "flexBasis", default, deserialize_with = "deserialize_flex_basis")]
+    pub flex_basis: Option<Dimension>,
+    /// Placement order among siblings (lower first, default 0)
+    pub order: Option<i32>,
+
+    // --- Grid ---
+    /// "grid" lays children out in the cells of the template tracks (default "flex")
+    pub display: Option<Display>,
+    #[serde(alias = "gridTemplateColumns", default, deserialize_with = "deserialize_grid_tracks")]
+    pub grid_template_columns: Option<Vec<GridTrack>>,
+    #[serde(alias = "gridTemplateRows", default, deserialize_with = "deserialize_grid_tracks")]
+    pub grid_template_rows: Option<Vec<GridTrack>>,
+    /// Space between grid rows (defaults to gap)
+    #[serde(alias = "rowGap")]
+    pub row_gap: Option<f32>,
+    /// Space between grid columns (defaults to gap)
+    #[serde(alias = "columnGap")]
+    pub column_gap: Option<f32>,
+    /// Column placement of a grid item: a line number, "span 2", "1 / 3", ...
+    #[serde(alias = "gridColumn")]
+    pub grid_column: Option<GridPlacement>,
+    /// Row placement of a grid item
+    #[serde(alias = "gridRow")]
+    pub grid_row: Option<GridPlacement>,
 
     // --- Padding (inside) ---
     pub padding: Option<f32>,
@@ -314,6 +875,13 @@ pub struct Style {
     pub background_color: Option<Color>,
     /// Opacity (0.0 = transparent, 1.0 = opaque)
     pub opacity: Option<f32>,
+    /// Linear or radial gradient drawn over backgroundColor
+    #[serde(alias = "backgroundImage")]
+    pub background_image: Option<Gradient>,
+    /// "hidden" clips children (and image content) to this box
+    pub overflow: Option<Overflow>,
+    #[serde(alias = "boxShadow")]
+    pub box_shadow: Option<BoxShadow>,
 
     // --- Border (shorthand) ---
     pub border: Option<Border>,
@@ -323,6 +891,8 @@ pub struct Style {
     pub border_color: Option<Color>,
     #[serde(alias = "borderRadius")]
     pub border_radius: Option<f32>,
+    #[serde(alias = "borderStyle")]
+    pub border_style: Option<BorderStyle>,
 
     // --- Per-side borders ---
     #[serde(alias = "borderTop")]
@@ -354,6 +924,16 @@ pub struct Style {
     #[serde(alias = "borderLeftColor")]
     pub border_left_color: Option<Color>,
 
+    // --- Per-side border styles ---
+    #[serde(alias = "borderTopStyle")]
+    pub border_top_style: Option<BorderStyle>,
+    #[serde(alias = "borderRightStyle")]
+    pub border_right_style: Option<BorderStyle>,
+    #[serde(alias = "borderBottomStyle")]
+    pub border_bottom_style: Option<BorderStyle>,
+    #[serde(alias = "borderLeftStyle")]
+    pub border_left_style: Option<BorderStyle>,
+
     // --- Per-corner border radius ---
     #[serde(alias = "borderTopLeftRadius")]
     pub border_top_left_radius: Option<f32>,
@@ -376,10 +956,21 @@ pub struct Style {
     pub font_style: Option<FontStyle>,
     #[serde(alias = "lineHeight")]
     pub line_height: Option<f32>,
+    /// Alignment of inline children (spans, images, icons) within their line
+    #[serde(alias = "verticalAlign")]
+    pub vertical_align: Option<VerticalAlign>,
 
     // --- Image ---
     #[serde(alias = "objectFit")]
     pub object_fit: Option<ObjectFit>,
+
+    // --- List ---
+    /// Marker style for list items (set on a list, or on an item to override)
+    #[serde(alias = "listStyle")]
+    pub list_style: Option<ListStyle>,
+    /// Width of the marker column that list item content is indented by
+    #[serde(alias = "listIndent")]
+    pub list_indent: Option<f32>,
 }
 
 // ============================================================================
@@ -427,6 +1018,92 @@ pub struct JsonNode {
     pub col_span: Option<usize>,
     #[serde(alias = "rowSpan")]
     pub row_span: Option<usize>,
+    /// Header cell, tagged TH rather than TD
+    pub header: Option<bool>,
+
+    // List-specific
+    /// First counter value of an ordered list (default 1)
+    pub start: Option<u32>,
+    /// Custom marker text, on a list (all items) or a single item
+    pub marker: Option<String>,
+
+    // Links
+    /// Link target: an external URL, `mailto:` address or `#id` of another node
+    pub href: Option<String>,
+    /// Name this node can be linked to as `#id`
+    pub id: Option<String>,
+    /// Outline entry pointing at this node
+    #[serde(default, deserialize_with = "deserialize_bookmark")]
+    pub bookmark: Option<Bookmark>,
+
+    /// File embedded by a fileAttachment node
+    pub file: Option<Attachment>,
+
+    // Form fields
+    /// Field name, unique in the document (widgets sharing a name show one value)
+    pub name: Option<String>,
+    /// Initial value: the text, or the chosen option of a radio group or dropdown
+    pub value: Option<String>,
+    /// Initial state of a checkbox
+    pub checked: Option<bool>,
+    /// Choices of a radio group or dropdown
+    pub options: Option<Vec<String>>,
+    pub required: Option<bool>,
+    #[serde(alias = "readOnly")]
+    pub read_only: Option<bool>,
+    /// Text field accepting several lines
+    pub multiline: Option<bool>,
+
+    // Semantics
+    /// Text alternative for an image or SVG
+    pub alt: Option<String>,
+    /// What the node is, when its type doesn't say (text is a paragraph by default)
+    pub role: Option<Role>,
+    /// 1 to 6, for headings (implies the heading role on text)
+    #[serde(alias = "headingLevel")]
+    pub heading_level: Option<u32>,
+    /// Language of the node's content, like `en-US` (inherited by its children)
+    pub lang: Option<String>,
+}
+
+/// Semantic role of a node, for assistive technology
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Heading,
+    Paragraph,
+    Caption,
+    List,
+    /// Decoration that isn't part of the content
+    Artifact,
+}
+
+/// An entry in the document outline (the viewer's bookmark panel)
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct Bookmark {
+    /// Defaults to the node's text
+    pub title: Option<String>,
+    /// 1 for top-level entries; deeper levels nest under the entry before them
+    pub level: Option<u32>,
+}
+
+/// Bookmarks are a title (`"Introduction"`) or `{ "title", "level" }`
+fn deserialize_bookmark<'de, D>(deserializer: D) -> Result<Option<Bookmark>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Title(String),
+        Full(Bookmark),
+    }
+
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Title(title)) => Some(Bookmark { title: Some(title), level: None }),
+        Some(Value::Full(bookmark)) => Some(bookmark),
+        None => None,
+    })
 }
 
 // ============================================================================
@@ -436,12 +1113,294 @@ pub struct JsonNode {
 #[derive(Debug, Deserialize)]
 pub struct LayoutPayload {
     pub root: JsonNode,
+    #[serde(flatten)]
+    pub document: DocumentOptions,
+}
+
+/// Settings for the whole document, given next to the root (or html) in a payload
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DocumentOptions {
+    pub watermark: Option<Watermark>,
+    pub metadata: Option<Metadata>,
+    #[serde(alias = "viewerPreferences")]
+    pub viewer_preferences: Option<ViewerPreferences>,
+    /// Archival standard the PDF must meet; layouts that can't are refused
+    pub conformance: Option<Conformance>,
+    /// E-invoice XML to embed (needs PDF/A-3)
+    pub invoice: Option<Invoice>,
+    /// Files to embed, listed in the viewer's attachments panel
+    pub attachments: Option<Vec<Attachment>>,
+    /// Emit a structure tree so screen readers can follow the document
+    pub tagged: Option<bool>,
+}
+
+/// A file embedded in the PDF
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Attachment {
+    /// File name shown by viewers
+    pub name: String,
+    /// Data URI, URL or local path, like an image node's `src`
+    #[serde(alias = "data", alias = "path")]
+    pub src: String,
+    /// Defaults to the data URI's type, or a guess from the name
+    #[serde(alias = "mimeType")]
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+}
+
+/// PDF/A conformance levels
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum Conformance {
+    #[serde(rename = "pdf-a-2b")]
+    PdfA2b,
+    #[serde(rename = "pdf-a-3b")]
+    PdfA3b,
+}
+
+impl Conformance {
+    /// Part of ISO 19005 the level belongs to
+    pub fn part(self) -> u32 {
+        match self {
+            Conformance::PdfA2b => 2,
+            Conformance::PdfA3b => 3,
+        }
+    }
+}
+
+/// Document information, written to the Info dictionary and XMP metadata
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// Application that made the content
+    pub creator: Option<String>,
+    /// Application that made the PDF
+    pub producer: Option<String>,
+    /// Language of the content, like "en" or "sv-SE"
+    #[serde(alias = "lang")]
+    pub language: Option<String>,
+    /// ISO 8601 dates, like "2024-05-01" or "2024-05-01T10:30:00+02:00" (default now)
+    #[serde(alias = "creationDate")]
+    pub creation_date: Option<String>,
+    #[serde(alias = "modificationDate", alias = "modDate")]
+    pub modification_date: Option<String>,
+}
+
+/// A Factur-X / ZUGFeRD e-invoice: the visual invoice is the rendered document,
+/// the machine-readable one is embedded next to it
+#[derive(Debug, Deserialize, Clone)]
+pub struct Invoice {
+    /// Cross Industry Invoice (CII) XML
+    pub xml: String,
+    /// Profile the XML follows (default "en16931")
+    pub profile: Option<InvoiceProfile>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum InvoiceProfile {
+    Minimum,
+    #[serde(alias = "basicWL", alias = "basic-wl")]
+    BasicWl,
+    Basic,
+    En16931,
+    Extended,
+    Xrechnung,
+}
+
+impl InvoiceProfile {
+    /// Value of the Factur-X ConformanceLevel XMP property
+    pub fn level_name(self) -> &'static str {
+        match self {
+            InvoiceProfile::Minimum => "MINIMUM",
+            InvoiceProfile::BasicWl => "BASIC WL",
+            InvoiceProfile::Basic => "BASIC",
+            InvoiceProfile::En16931 => "EN 16931",
+            InvoiceProfile::Extended => "EXTENDED",
+            InvoiceProfile::Xrechnung => "XRECHNUNG",
+        }
+    }
+
+    /// Name the XML must be embedded under
+    pub fn file_name(self) -> &'static str {
+        match self {
+            InvoiceProfile::Xrechnung => "xrechnung.xml",
+            _ => "factur-x.xml",
+        }
+    }
+
+    /// How the XML relates to the PDF: the profiles too small to make a valid
+    /// invoice only carry data, the others are an equivalent alternative
+    pub fn relationship(self) -> &'static str {
+        match self {
+            InvoiceProfile::Minimum | InvoiceProfile::BasicWl => "Data",
+            _ => "Alternative",
+        }
+    }
+}
+
+/// How viewers should present the document when it is opened
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ViewerPreferences {
+    #[serde(alias = "pageLayout")]
+    pub page_layout: Option<PageLayout>,
+    /// Show the metadata title in the window title bar instead of the file name
+    #[serde(alias = "displayDocTitle")]
+    pub display_doc_title: Option<bool>,
+    /// Open with the bookmark panel showing
+    #[serde(alias = "openOutline")]
+    pub open_outline: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PageLayout {
+    /// One page at a time
+    SinglePage,
+    /// Pages in one continuous column
+    OneColumn,
+    /// Two continuous columns, odd pages on the left
+    TwoColumnLeft,
+    /// Two continuous columns, odd pages on the right
+    TwoColumnRight,
+    /// Two pages at a time, odd pages on the left
+    TwoPageLeft,
+    /// Two pages at a time, odd pages on the right
+    TwoPageRight,
+}
+
+impl PageLayout {
+    /// Name of the catalog's PageLayout value
+    pub fn pdf_name(&self) -> &'static str {
+        match self {
+            PageLayout::SinglePage => "SinglePage",
+            PageLayout::OneColumn => "OneColumn",
+            PageLayout::TwoColumnLeft => "TwoColumnLeft",
+            PageLayout::TwoColumnRight => "TwoColumnRight",
+            PageLayout::TwoPageLeft => "TwoPageLeft",
+            PageLayout::TwoPageRight => "TwoPageRight",
+        }
+    }
+}
+
+/// Text, image or SVG drawn centered on every page
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Watermark {
+    /// Text to stamp, e.g. "DRAFT"
+    pub text: Option<String>,
+    /// Image source, like an image node's `src`
+    pub image: Option<String>,
+    /// SVG markup or source, like an svg node's `src`
+    pub svg: Option<String>,
+    /// Image or SVG size in points (text is sized by fontSize)
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// Text size (default 96)
+    #[serde(alias = "fontSize")]
+    pub font_size: Option<f32>,
+    /// Text weight (default bold)
+    #[serde(alias = "fontWeight")]
+    pub font_weight: Option<FontWeight>,
+    #[serde(alias = "fontStyle")]
+    pub font_style: Option<FontStyle>,
+    /// Text color (default gray)
+    pub color: Option<Color>,
+    /// Default 0.2
+    pub opacity: Option<f32>,
+    /// Clockwise degrees, like a CSS rotate (default -45: rising to the right)
+    pub rotation: Option<f32>,
+    /// Drawn "over" (default) or "under" the page content
+    pub layer: Option<WatermarkLayer>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatermarkLayer {
+    Over,
+    /// Between the page background and the content (opaque backgrounds hide it)
+    Under,
 }
 
 // ============================================================================
 // HELPER FUNCTIONS
 // ============================================================================
 
+impl JsonNode {
+    /// Create an empty node of the given type (for front ends that build trees in code)
+    pub fn new(node_type: NodeType) -> Self {
+        JsonNode {
+            node_type,
+            style: Style::default(),
+            children: Vec::new(),
+            text: None,
+            font_size: None,
+            font_weight: None,
+            font_style: None,
+            text_align: None,
+            src: None,
+            content: None,
+            column_widths: None,
+            col_span: None,
+            row_span: None,
+            header: None,
+            start: None,
+            marker: None,
+            href: None,
+            id: None,
+            bookmark: None,
+            file: None,
+            name: None,
+            value: None,
+            checked: None,
+            options: None,
+            required: None,
+            read_only: None,
+            multiline: None,
+            alt: None,
+            role: None,
+            heading_level: None,
+            lang: None,
+        }
+    }
+
+    /// All text in the node and its descendants, with whitespace collapsed
+    pub fn text_content(&self) -> String {
+        fn collect(node: &JsonNode, out: &mut String) {
+            if let Some(ref text) = node.text {
+                out.push_str(text);
+            }
+            for child in &node.children {
+                collect(child, out);
+                // Spans run on in a paragraph; anything else is its own block
+                if node.node_type != NodeType::Text {
+                    out.push(' ');
+                }
+            }
+        }
+
+        let mut text = String::new();
+        collect(self, &mut text);
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Title and level of the node's outline and table of contents entry: its
+    /// bookmark, or for a heading, its text at the heading's level
+    pub fn outline_entry(&self) -> Option<(String, u32)> {
+        let (title, level) = if let Some(ref bookmark) = self.bookmark {
+            (bookmark.title.clone().unwrap_or_else(|| self.text_content()), bookmark.level.unwrap_or(1))
+        } else if self.role == Some(Role::Heading)
+            || (self.role.is_none() && self.node_type == NodeType::Text && self.heading_level.is_some())
+        {
+            (self.text_content(), self.heading_level.unwrap_or(1).clamp(1, 6))
+        } else {
+            return None;
+        };
+        Some((title, level)).filter(|(title, _)| !title.is_empty())
+    }
+}
+
 impl Style {
     /// Get padding as (top, right, bottom, left)
     pub fn padding_trbl(&self) -> (f32, f32, f32, f32) {
@@ -521,6 +1480,26 @@ impl Style {
         )
     }
 
+    /// Get border styles as (top, right, bottom, left), solid by default
+    pub fn border_styles(&self) -> (BorderStyle, BorderStyle, BorderStyle, BorderStyle) {
+        let base = self.border_style
+            .clone()
+            .or_else(|| self.border.as_ref().and_then(|b| b.style.clone()))
+            .unwrap_or(BorderStyle::Solid);
+        let side = |style: &Option<BorderStyle>, side: &Option<BorderSide>| {
+            style
+                .clone()
+                .or_else(|| side.as_ref().and_then(|b| b.style.clone()))
+                .unwrap_or_else(|| base.clone())
+        };
+        (
+            side(&self.border_top_style, &self.border_top),
+            side(&self.border_right_style, &self.border_right),
+            side(&self.border_bottom_style, &self.border_bottom),
+            side(&self.border_left_style, &self.border_left),
+        )
+    }
+
     /// Get effective border radius (uniform)
     pub fn border_radius(&self) -> f32 {
         self.border_radius
@@ -1,5 +1,6 @@
 use crate::layout::{
     JsonNode, NodeType, Direction, MainAlign, CrossAlign, TextAlign, FontWeight, FontStyle, Position, Dimension,
+    Color, ListStyle, Style, VerticalAlign, Display, GridTrack, Overflow, Role,
 };
 use crate::font_metrics;
 
@@ -13,6 +14,67 @@ pub struct TableLayout {
     pub row_heights: Vec<f32>,
 }
 
+/// Cell area of a grid item (0-based track indices)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridArea {
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GridLayout {
+    pub column_widths: Vec<f32>,
+    pub row_heights: Vec<f32>,
+    /// Area of each child, by child index (None for absolute children)
+    pub areas: Vec<Option<GridArea>>,
+}
+
+/// A piece of inline content placed on one line of an inline formatting context
+#[derive(Debug, Clone)]
+pub struct InlineFragment {
+    /// Index of the span, image or svg child this fragment belongs to
+    pub child: usize,
+    /// Text for span fragments (None for inline boxes)
+    pub text: Option<String>,
+    /// Offset from the start of the line
+    pub x: f32,
+    /// Offset from the top of the box: the baseline for text, the top edge for boxes
+    pub y: f32,
+    pub width: f32,
+}
+
+/// One line of an inline formatting context
+#[derive(Debug, Clone)]
+pub struct InlineLine {
+    /// Offset of the line's top edge from the top of the box
+    pub top: f32,
+    pub height: f32,
+    /// Distance from the line's top edge to its baseline
+    pub baseline: f32,
+    /// Width of the line's content (without trailing space)
+    pub width: f32,
+    /// Horizontal offset from text alignment (set in the place pass)
+    pub offset_x: f32,
+    pub fragments: Vec<InlineFragment>,
+}
+
+/// Marker of a list item, resolved from its list's listStyle, start and marker
+#[derive(Debug, Clone)]
+pub struct ListMarker {
+    pub style: ListStyle,
+    /// Text for counters and custom markers (empty for bullet shapes)
+    pub text: String,
+    /// Distance from the item's left edge to the marker's right edge
+    pub right: f32,
+    /// Distance from the item's top edge to the first line's baseline
+    pub baseline: f32,
+    pub font_size: f32,
+    pub bold: bool,
+    pub color: Color,
+}
+
 #[derive(Debug, Clone)]
 pub struct LayoutBox {
     // Final computed position (PDF coordinates: origin bottom-left)
@@ -40,6 +102,39 @@ pub struct LayoutBox {
 
     // For Table nodes: computed grid info
     pub table: Option<TableLayout>,
+
+    // For grid containers: computed tracks and item areas
+    pub grid: Option<GridLayout>,
+
+    // For ListItem nodes: the marker drawn in the indent
+    pub marker: Option<ListMarker>,
+
+    // For Text nodes with children: lines of inline content
+    pub inline_lines: Vec<InlineLine>,
+
+    // What the box means, for accessible output
+    pub semantics: Semantics,
+
+    // Width a flex or grid parent gives the box (flex-basis, shrinking, grid
+    // areas), used in place of the style width. Set again on every measure pass.
+    pub used_width: Option<f32>,
+}
+
+/// Space between a form field's border and its text
+pub const FIELD_PADDING: f32 = 2.0;
+/// Start of a radio option's label, in font sizes from the left of the group
+pub const RADIO_LABEL_OFFSET: f32 = 1.5;
+
+/// Role, alt text and language of a box, resolved from its node and ancestors
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Semantics {
+    /// The node's role, or the one its type implies
+    pub role: Option<Role>,
+    /// 1 to 6, set for headings
+    pub heading_level: Option<u32>,
+    pub alt: Option<String>,
+    /// Language of the content, inherited from the nearest ancestor that sets one
+    pub lang: Option<String>,
 }
 
 impl LayoutBox {
@@ -59,6 +154,11 @@ impl LayoutBox {
             node,
             lines: Vec::new(),
             table: None,
+            grid: None,
+            marker: None,
+            inline_lines: Vec::new(),
+            semantics: Semantics::default(),
+            used_width: None,
         }
     }
 
@@ -80,9 +180,10 @@ impl LayoutBox {
         }
     }
 
-    /// Resolve width given parent width (handles both fixed and percentage)
+    /// Resolve width given parent width (handles both fixed and percentage);
+    /// a width from the parent's layout wins over the style
     pub fn resolve_width(&self, parent_width: f32) -> Option<f32> {
-        self.node.style.width.as_ref().map(|d| d.resolve(parent_width))
+        self.used_width.or_else(|| self.node.style.width.as_ref().map(|d| d.resolve(parent_width)))
     }
 
     /// Resolve height given parent height (handles both fixed and percentage)
@@ -104,6 +205,26 @@ impl LayoutBox {
         self.node.style.flex.unwrap_or(0.0)
     }
 
+    pub fn flex_shrink(&self) -> f32 {
+        self.node.style.flex_shrink.unwrap_or(1.0).max(0.0)
+    }
+
+    pub fn order(&self) -> i32 {
+        self.node.style.order.unwrap_or(0)
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.node.style.direction.clone().unwrap_or(Direction::Column)
+    }
+
+    pub fn clips_overflow(&self) -> bool {
+        self.node.style.overflow == Some(Overflow::Hidden)
+    }
+
+    pub fn is_grid(&self) -> bool {
+        self.node.style.display == Some(Display::Grid)
+    }
+
     pub fn main_align(&self) -> MainAlign {
         self.node.style.main_align.clone().unwrap_or(MainAlign::Start)
     }
@@ -112,18 +233,55 @@ impl LayoutBox {
         self.node.style.cross_align.clone().unwrap_or(CrossAlign::Start)
     }
 
+    /// Cross-axis alignment of this box inside a parent aligning with `parent_align`
+    pub fn align_self(&self, parent_align: &CrossAlign) -> CrossAlign {
+        self.node.style.align_self.clone().unwrap_or_else(|| parent_align.clone())
+    }
+
     pub fn position(&self) -> Position {
         self.node.style.position.clone().unwrap_or(Position::Static)
     }
 
+    /// Absolute and fixed boxes are taken out of the flow
     pub fn is_absolute(&self) -> bool {
-        matches!(self.position(), Position::Absolute)
+        matches!(self.position(), Position::Absolute | Position::Fixed)
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        matches!(self.position(), Position::Fixed)
     }
 
     pub fn is_relative(&self) -> bool {
         matches!(self.position(), Position::Relative)
     }
 
+    pub fn is_positioned(&self) -> bool {
+        !matches!(self.position(), Position::Static)
+    }
+
+    /// zIndex only applies to positioned boxes, as in CSS
+    pub fn z_index(&self) -> Option<i32> {
+        self.node.style.z_index.filter(|_| self.is_positioned())
+    }
+
+    /// Boxes with a zIndex, opacity, transform or clipping paint their subtree as one
+    /// unit; zIndex descendants of other boxes are stacked by the nearest such ancestor
+    pub fn forms_stacking_context(&self) -> bool {
+        self.z_index().is_some()
+            || self.node.style.opacity() < 1.0
+            || self.node.style.transform.is_some()
+            || self.clips_overflow()
+    }
+
+    /// A text node with children lays out its spans, images and icons inline
+    pub fn is_inline_context(&self) -> bool {
+        self.node.node_type == NodeType::Text && !self.children.is_empty()
+    }
+
+    pub fn vertical_align(&self) -> VerticalAlign {
+        self.node.style.vertical_align.clone().unwrap_or(VerticalAlign::Baseline)
+    }
+
     pub fn text_align(&self) -> TextAlign {
         self.node.style.text_align.clone()
             .or_else(|| self.node.text_align.clone())
@@ -163,6 +321,11 @@ impl LayoutBox {
         font_metrics::get_metrics(self.is_bold(), self.is_italic())
     }
 
+    /// Distance from the top of a line of text to its baseline (the font's ascender)
+    pub fn ascent(&self) -> f32 {
+        self.font_metrics().ascent(self.font_size())
+    }
+
     /// Total outer width including margins
     pub fn outer_width(&self) -> f32 {
         self.margin_left + self.width + self.margin_right
@@ -180,6 +343,22 @@ impl LayoutBox {
     pub fn row_span(&self) -> usize {
         self.node.row_span.unwrap_or(1).max(1)
     }
+
+    /// Distance from the top edge to the baseline of the first line of text inside
+    /// this box (valid after the place pass). None if the box holds no text.
+    pub fn first_baseline(&self) -> Option<f32> {
+        match self.node.node_type {
+            NodeType::Text => match self.inline_lines.first() {
+                Some(line) => Some(line.top + line.baseline),
+                None => Some(self.ascent()),
+            },
+            NodeType::Image | NodeType::Svg | NodeType::FileAttachment => None,
+            ref t if t.is_form_field() => None,
+            _ => self.children.iter()
+                .filter(|c| !c.is_absolute())
+                .find_map(|c| c.first_baseline().map(|b| self.y - c.y + b)),
+        }
+    }
 }
 
 // ============================================================================
@@ -199,13 +378,274 @@ fn measure_line_height(font_size: f32, line_height_mult: f32) -> f32 {
 // ============================================================================
 
 pub fn build_layout(node: &JsonNode) -> LayoutBox {
+    let mut layout = build_layout_at_depth(node, 0);
+    resolve_semantics(&mut layout, None);
+    layout
+}
+
+/// `list_depth` counts the lists enclosing this node (nested lists cycle bullet shapes)
+fn build_layout_at_depth(node: &JsonNode, list_depth: usize) -> LayoutBox {
     let mut lb = LayoutBox::new(node.clone());
+    if node.node_type == NodeType::Text && !node.children.is_empty() {
+        lb.children = build_inline_children(node, list_depth);
+        return lb;
+    }
+
+    let child_depth = if node.node_type == NodeType::List { list_depth + 1 } else { list_depth };
     for child in &node.children {
-        lb.children.push(build_layout(child));
+        lb.children.push(build_layout_at_depth(child, child_depth));
+    }
+    if node.node_type == NodeType::List {
+        assign_list_markers(&mut lb, list_depth);
     }
     lb
 }
 
+/// Flatten the content of an inline formatting context into leaf spans and
+/// inline boxes. Spans inherit text styles from the text nodes around them,
+/// and the context's own `text` becomes the first span.
+fn build_inline_children(node: &JsonNode, list_depth: usize) -> Vec<LayoutBox> {
+    let mut out = Vec::new();
+    let inherited = inherited_text_style(node, &Style::default());
+    if let Some(ref text) = node.text {
+        let mut span = JsonNode::new(NodeType::Text);
+        span.text = Some(text.clone());
+        span.style = inherited.clone();
+        span.href = node.href.clone();
+        out.push(LayoutBox::new(span));
+    }
+    for child in &node.children {
+        flatten_inline(child, &inherited, node.href.as_deref(), list_depth, &mut out);
+    }
+    out
+}
+
+/// Spans inside a link inherit its `href`
+fn flatten_inline(node: &JsonNode, inherited: &Style, href: Option<&str>, list_depth: usize, out: &mut Vec<LayoutBox>) {
+    if node.node_type != NodeType::Text {
+        out.push(build_layout_at_depth(node, list_depth));
+        return;
+    }
+
+    let style = inherited_text_style(node, inherited);
+    let href = node.href.as_deref().or(href);
+    if node.text.is_some() {
+        let mut span = node.clone();
+        span.children.clear();
+        span.href = href.map(str::to_string);
+        span.style = Style {
+            vertical_align: node.style.vertical_align.clone(),
+            background_color: node.style.background_color.clone(),
+            ..style.clone()
+        };
+        out.push(LayoutBox::new(span));
+    }
+    for child in &node.children {
+        flatten_inline(child, &style, href, list_depth, out);
+    }
+}
+
+/// The inheritable text properties of a node, falling back to its parent's
+fn inherited_text_style(node: &JsonNode, parent: &Style) -> Style {
+    Style {
+        font_size: node.style.font_size.or(node.font_size).or(parent.font_size),
+        font_weight: node.style.font_weight.clone()
+            .or_else(|| node.font_weight.clone())
+            .or_else(|| parent.font_weight.clone()),
+        font_style: node.style.font_style.clone()
+            .or_else(|| node.font_style.clone())
+            .or_else(|| parent.font_style.clone()),
+        color: node.style.color.clone().or_else(|| parent.color.clone()),
+        line_height: node.style.line_height.or(parent.line_height),
+        ..Style::default()
+    }
+}
+
+// ============================================================================
+// LIST MARKERS
+// ============================================================================
+
+/// Resolve each item's marker and indent item content by the marker column
+fn assign_list_markers(list: &mut LayoutBox, list_depth: usize) {
+    let default_style = list.node.style.list_style.clone().unwrap_or(match list_depth {
+        0 => ListStyle::Disc,
+        1 => ListStyle::Circle,
+        _ => ListStyle::Square,
+    });
+    let list_marker = list.node.marker.clone();
+    let mut number = list.node.start.unwrap_or(1);
+
+    let mut widest: f32 = 0.0;
+    let mut max_font_size: f32 = 0.0;
+
+    for item in list.children.iter_mut() {
+        if item.node.node_type != NodeType::ListItem {
+            continue;
+        }
+
+        let style = item.node.style.list_style.clone().unwrap_or_else(|| default_style.clone());
+        let text = match item.node.marker.clone().or_else(|| list_marker.clone()) {
+            Some(custom) => custom,
+            None => format_list_counter(&style, number),
+        };
+        number += 1;
+
+        // The marker uses the font of the item's first line of text
+        let first_text = first_text_node(&item.node);
+        let font_size = item.node.style.font_size
+            .or_else(|| first_text.and_then(|t| t.style.font_size.or(t.font_size)))
+            .unwrap_or(12.0);
+        let color = item.node.style.color.clone()
+            .or_else(|| first_text.and_then(|t| t.style.color.clone()))
+            .unwrap_or_else(Color::black);
+        let bold = item.font_weight()
+            .or_else(|| first_text.and_then(|t| t.style.font_weight.clone().or_else(|| t.font_weight.clone())))
+            == Some(FontWeight::Bold);
+
+        let marker_width = if text.is_empty() {
+            font_size * 0.4
+        } else {
+            measure_text_width(&text, font_size, font_metrics::get_metrics(bold, false))
+        };
+        widest = widest.max(marker_width);
+        max_font_size = max_font_size.max(font_size);
+
+        item.marker = Some(ListMarker {
+            style,
+            text,
+            right: 0.0,
+            baseline: font_metrics::get_metrics(bold, false).ascent(font_size),
+            font_size,
+            bold,
+            color,
+        });
+    }
+
+    // All items share one marker column so their content lines up
+    let gap = max_font_size * 0.5;
+    let indent = list.node.style.list_indent
+        .unwrap_or_else(|| (widest + gap).max(max_font_size * 1.8));
+
+    for item in list.children.iter_mut() {
+        if let Some(ref mut marker) = item.marker {
+            let (_, _, _, pad_l) = item.node.style.padding_trbl();
+            item.node.style.padding_left = Some(pad_l + indent);
+            marker.right = pad_l + indent - gap;
+        }
+    }
+}
+
+fn first_text_node(node: &JsonNode) -> Option<&JsonNode> {
+    if node.node_type == NodeType::Text {
+        return Some(node);
+    }
+    node.children.iter().find_map(first_text_node)
+}
+
+/// Marker text for a counter value; empty for bullet shapes (drawn as vectors)
+fn format_list_counter(style: &ListStyle, n: u32) -> String {
+    match style {
+        ListStyle::Disc | ListStyle::Circle | ListStyle::Square | ListStyle::None => String::new(),
+        ListStyle::Decimal => format!("{}.", n),
+        ListStyle::LowerAlpha => format!("{}.", to_alpha(n)),
+        ListStyle::UpperAlpha => format!("{}.", to_alpha(n).to_uppercase()),
+        ListStyle::LowerRoman => format!("{}.", to_roman(n).to_lowercase()),
+        ListStyle::UpperRoman => format!("{}.", to_roman(n)),
+    }
+}
+
+/// 1 -> a, 26 -> z, 27 -> aa
+fn to_alpha(mut n: u32) -> String {
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn to_roman(mut n: u32) -> String {
+    if n == 0 || n >= 4000 {
+        return n.to_string();
+    }
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+// ============================================================================
+// SEMANTICS
+// ============================================================================
+
+/// Resolve the semantics of the box and its descendants, warning about what
+/// assistive technology can't make sense of
+fn resolve_semantics(layout: &mut LayoutBox, parent_lang: Option<&str>) {
+    let node = &layout.node;
+    let lang = match node.lang.as_deref() {
+        Some(lang) if is_language_tag(lang) => Some(lang),
+        Some(lang) => {
+            eprintln!("Invalid lang {:?}, expected a language tag like \"en-US\"", lang);
+            parent_lang
+        }
+        None => parent_lang,
+    };
+
+    // Bookmarked text is a heading of the bookmark's level, as h1-h6 are in HTML
+    let bookmark_level = node.bookmark.as_ref().map(|b| b.level.unwrap_or(1));
+    let role = node.role.or(match node.node_type {
+        NodeType::Text if node.heading_level.is_some() || bookmark_level.is_some() => Some(Role::Heading),
+        NodeType::Text => Some(Role::Paragraph),
+        NodeType::List => Some(Role::List),
+        _ => None,
+    });
+    let heading_level = match role {
+        Some(Role::Heading) => {
+            let level = node.heading_level.or(bookmark_level).unwrap_or(1);
+            if !(1..=6).contains(&level) {
+                eprintln!("Heading level {} is out of range, using {}", level, level.clamp(1, 6));
+            }
+            Some(level.clamp(1, 6))
+        }
+        _ => {
+            if node.heading_level.is_some() {
+                eprintln!("headingLevel is ignored on a {:?} node that isn't a heading", node.node_type);
+            }
+            None
+        }
+    };
+
+    let alt = node.alt.clone().filter(|alt| !alt.trim().is_empty());
+    if matches!(node.node_type, NodeType::Image | NodeType::Svg) && alt.is_none() && role != Some(Role::Artifact) {
+        let src: String = node.src.as_deref().unwrap_or("inline").chars().take(60).collect();
+        eprintln!("{:?} ({}) has no alt text; add one, or the artifact role if it's decorative", node.node_type, src);
+    }
+
+    layout.semantics = Semantics { role, heading_level, alt, lang: lang.map(str::to_string) };
+    let lang = layout.semantics.lang.clone();
+    for child in &mut layout.children {
+        resolve_semantics(child, lang.as_deref());
+    }
+}
+
+/// Loose BCP 47 check: letter subtags of 2-8 characters, then alphanumeric subtags
+fn is_language_tag(lang: &str) -> bool {
+    let mut subtags = lang.split('-');
+    let primary = subtags.next().unwrap_or("");
+    (2..=8).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
 // ============================================================================
 // MEASURE PASS
 // ============================================================================
@@ -220,11 +660,17 @@ pub fn measure_layout(layout: &mut LayoutBox) {
 /// Measure layout with explicit parent dimensions
 pub fn measure_layout_with_parent(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
     match layout.node.node_type {
+        NodeType::Text if layout.is_inline_context() => measure_inline(layout, parent_width, parent_height),
         NodeType::Text => measure_text(layout, parent_width),
-        NodeType::Page | NodeType::View => measure_container(layout, parent_width, parent_height),
+        NodeType::Page | NodeType::View | NodeType::Toc => measure_container(layout, parent_width, parent_height),
+        NodeType::List | NodeType::ListItem => measure_container(layout, parent_width, parent_height),
         NodeType::Table => measure_table(layout, parent_width, parent_height),
         NodeType::Row | NodeType::Cell => measure_container(layout, parent_width, parent_height),
         NodeType::Image | NodeType::Svg => measure_image(layout, parent_width, parent_height),
+        NodeType::FileAttachment => measure_file_attachment(layout, parent_width, parent_height),
+        NodeType::TextField | NodeType::Checkbox | NodeType::RadioGroup | NodeType::Dropdown | NodeType::SignatureField => {
+            measure_form_field(layout, parent_width, parent_height)
+        }
     }
 }
 
@@ -288,6 +734,195 @@ fn wrap_text(
     layout.height = measure_line_height(size, line_h) * (layout.lines.len() as f32);
 }
 
+// ============================================================================
+// INLINE FORMATTING CONTEXT
+// ============================================================================
+
+/// An unbreakable run of inline content: a word (possibly spanning several
+/// spans, like "<b>bold</b>ness") or a single inline box
+#[derive(Default)]
+struct InlineWord {
+    /// (child index, text, width) - text is None for inline boxes
+    atoms: Vec<(usize, Option<String>, f32)>,
+    width: f32,
+    /// Width of the collapsed whitespace after the word (0 if none)
+    space_after: f32,
+}
+
+/// Finish the current word; `space` is the whitespace that ended it
+fn finish_inline_word(current: &mut InlineWord, words: &mut Vec<InlineWord>, space: f32) {
+    if current.atoms.is_empty() {
+        // Collapse whitespace runs into the previous word's trailing space
+        if let Some(last) = words.last_mut() {
+            last.space_after = last.space_after.max(space);
+        }
+        return;
+    }
+    current.space_after = space;
+    words.push(std::mem::take(current));
+}
+
+fn measure_inline(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
+    let explicit_width = layout.resolve_width(parent_width);
+    let max_width = explicit_width.unwrap_or(parent_width);
+
+    // 1) Split content into unbreakable words
+    let mut words: Vec<InlineWord> = Vec::new();
+    let mut current = InlineWord::default();
+
+    for (i, child) in layout.children.iter_mut().enumerate() {
+        if child.node.node_type == NodeType::Text {
+            let text = child.node.text.clone().unwrap_or_default();
+            let size = child.font_size();
+            let metrics = child.font_metrics();
+            let space = metrics.char_width_pt(' ', size);
+
+            let starts_with_space = text.starts_with(char::is_whitespace);
+            for (k, word) in text.split_whitespace().enumerate() {
+                if k > 0 || starts_with_space {
+                    finish_inline_word(&mut current, &mut words, space);
+                }
+                let w = measure_text_width(word, size, metrics);
+                current.atoms.push((i, Some(word.to_string()), w));
+                current.width += w;
+            }
+            if text.ends_with(char::is_whitespace) {
+                finish_inline_word(&mut current, &mut words, space);
+            }
+        } else {
+            measure_layout_with_parent(child, max_width, parent_height);
+            finish_inline_word(&mut current, &mut words, 0.0);
+            let w = child.outer_width();
+            words.push(InlineWord { atoms: vec![(i, None, w)], width: w, space_after: 0.0 });
+        }
+    }
+    finish_inline_word(&mut current, &mut words, 0.0);
+
+    // 2) Break words into lines
+    let mut lines: Vec<InlineLine> = Vec::new();
+    let mut line = InlineLine { top: 0.0, height: 0.0, baseline: 0.0, width: 0.0, offset_x: 0.0, fragments: Vec::new() };
+    let mut pending_space = 0.0;
+
+    for word in &words {
+        if !line.fragments.is_empty() && line.width + pending_space + word.width > max_width {
+            lines.push(std::mem::replace(&mut line, InlineLine {
+                top: 0.0, height: 0.0, baseline: 0.0, width: 0.0, offset_x: 0.0, fragments: Vec::new(),
+            }));
+            pending_space = 0.0;
+        }
+
+        let spaced = pending_space > 0.0;
+        let mut x = if line.fragments.is_empty() { 0.0 } else { line.width + pending_space };
+        for (k, (child, text, w)) in word.atoms.iter().enumerate() {
+            // Consecutive text from the same span becomes a single fragment
+            match (line.fragments.last_mut(), text) {
+                (Some(last), Some(t)) if last.child == *child && last.text.is_some() => {
+                    let joined = last.text.as_mut().unwrap();
+                    if k == 0 && spaced {
+                        joined.push(' ');
+                    }
+                    joined.push_str(t);
+                    last.width = x + w - last.x;
+                }
+                _ => line.fragments.push(InlineFragment { child: *child, text: text.clone(), x, y: 0.0, width: *w }),
+            }
+            x += w;
+        }
+        line.width = x;
+        pending_space = word.space_after;
+    }
+    if !line.fragments.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    // 3) Vertical metrics: baseline from the tallest ascent, height from the tallest box
+    let strut_size = layout.font_size();
+    let strut_line_h = layout.line_height_multiplier();
+    let mut top = 0.0;
+    let mut content_width: f32 = 0.0;
+
+    for line in lines.iter_mut() {
+        let strut_ascent = layout.ascent();
+        let mut ascent = strut_ascent;
+        let mut descent = strut_size * strut_line_h - strut_ascent;
+        let mut edge_aligned_h: f32 = 0.0;
+
+        // (ascent above the baseline, baseline shift) per fragment
+        let mut extents: Vec<(f32, f32)> = Vec::with_capacity(line.fragments.len());
+        for frag in &line.fragments {
+            let child = &layout.children[frag.child];
+            let (asc, desc, shift) = inline_extent(child, strut_size, frag.text.is_some());
+            match child.vertical_align() {
+                VerticalAlign::Top | VerticalAlign::Bottom if frag.text.is_none() => {
+                    edge_aligned_h = edge_aligned_h.max(child.outer_height());
+                }
+                _ => {
+                    ascent = ascent.max(asc);
+                    descent = descent.max(desc);
+                }
+            }
+            extents.push((asc, shift));
+        }
+
+        line.top = top;
+        line.baseline = ascent;
+        line.height = (ascent + descent).max(edge_aligned_h);
+        // A bottom-aligned box taller than the line pushes the baseline down
+        if edge_aligned_h > ascent + descent {
+            let bottom_aligned = line.fragments.iter()
+                .any(|f| f.text.is_none() && layout.children[f.child].vertical_align() == VerticalAlign::Bottom);
+            if bottom_aligned {
+                line.baseline += edge_aligned_h - (ascent + descent);
+            }
+        }
+
+        for (frag, (asc, shift)) in line.fragments.iter_mut().zip(extents) {
+            let child = &layout.children[frag.child];
+            frag.y = if frag.text.is_some() {
+                top + line.baseline - shift
+            } else {
+                match child.vertical_align() {
+                    VerticalAlign::Top => top,
+                    VerticalAlign::Bottom => top + line.height - child.outer_height(),
+                    _ => top + line.baseline - asc,
+                }
+            };
+        }
+
+        top += line.height;
+        content_width = content_width.max(line.width);
+    }
+
+    layout.width = explicit_width.unwrap_or(content_width);
+    layout.height = layout.resolve_height(parent_height).unwrap_or(top);
+    layout.inline_lines = lines;
+}
+
+/// Ascent above and descent below the baseline of an inline item, plus the
+/// baseline shift for super/subscript
+fn inline_extent(child: &LayoutBox, strut_size: f32, is_text: bool) -> (f32, f32, f32) {
+    let (asc, desc, size) = if is_text {
+        let size = child.font_size();
+        let line_h = measure_line_height(size, child.line_height_multiplier());
+        (child.ascent(), line_h - child.ascent(), size)
+    } else {
+        (child.outer_height(), 0.0, strut_size)
+    };
+
+    let shift = match child.vertical_align() {
+        VerticalAlign::Super => size * 0.33,
+        VerticalAlign::Sub => -size * 0.2,
+        // Center boxes on the middle of the surrounding lowercase letters
+        VerticalAlign::Middle if !is_text => {
+            let h = child.outer_height();
+            return (h / 2.0 + strut_size * 0.26, h / 2.0 - strut_size * 0.26, 0.0);
+        }
+        _ => 0.0,
+    };
+
+    (asc + shift, desc - shift, shift)
+}
+
 fn measure_image(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
     // Images require explicit width/height; default to 100x100
     // Resolve percentage dimensions
@@ -295,9 +930,34 @@ fn measure_image(layout: &mut LayoutBox, parent_width: f32, parent_height: f32)
     layout.height = layout.resolve_height(parent_height).unwrap_or(100.0);
 }
 
+fn measure_file_attachment(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
+    // The paperclip icon's size unless given
+    layout.width = layout.resolve_width(parent_width).unwrap_or(12.0);
+    layout.height = layout.resolve_height(parent_height).unwrap_or(16.0);
+}
+
+/// Form fields are sized like browser inputs unless given a size. A radio group
+/// lists its options under each other, a button and a label on each line.
+fn measure_form_field(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
+    let size = layout.font_size();
+    let line = measure_line_height(size, layout.line_height_multiplier());
+    let (width, height) = match layout.node.node_type {
+        NodeType::Checkbox => (size, size),
+        NodeType::RadioGroup => {
+            let metrics = layout.font_metrics();
+            let options = layout.node.options.as_deref().unwrap_or(&[]);
+            let label = options.iter().map(|o| measure_text_width(o, size, metrics)).fold(0.0, f32::max);
+            (size * RADIO_LABEL_OFFSET + label, options.len() as f32 * line)
+        }
+        NodeType::SignatureField => (200.0, 50.0),
+        NodeType::TextField if layout.node.multiline == Some(true) => (200.0, 3.0 * line + 2.0 * FIELD_PADDING),
+        _ => (150.0, line + 2.0 * FIELD_PADDING),
+    };
+    layout.width = layout.resolve_width(parent_width).unwrap_or(width);
+    layout.height = layout.resolve_height(parent_height).unwrap_or(height);
+}
+
 fn measure_container(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
-    let dir = layout.node.style.direction.clone().unwrap_or(Direction::Column);
-    let gap = layout.node.style.gap.unwrap_or(0.0);
     let (pad_t, pad_r, pad_b, pad_l) = layout.node.style.padding_trbl();
 
     // Resolve this container's explicit dimensions first (for percentage children)
@@ -319,21 +979,11 @@ fn measure_container(layout: &mut LayoutBox, parent_width: f32, parent_height: f
         0.0
     };
 
-    // First, measure all children with resolved parent dimensions
-    for child in &mut layout.children {
-        measure_layout_with_parent(child, child_parent_width, child_parent_height);
-    }
-
-    let (content_w, content_h) = match dir {
-        Direction::Column => measure_column(&layout.children, gap),
-        Direction::Row => {
-            let wrap = layout.node.style.wrap.unwrap_or(false);
-            if wrap && explicit_width.is_some() {
-                measure_wrapping_row(&layout.children, gap, child_parent_width)
-            } else {
-                measure_row(&layout.children, gap)
-            }
-        }
+    let (content_w, content_h) = if layout.is_grid() {
+        measure_grid(layout, child_parent_width, child_parent_height)
+    } else {
+        let explicit = (explicit_width.is_some(), explicit_height.is_some());
+        measure_flex(layout, child_parent_width, child_parent_height, explicit)
     };
 
     // Add padding
@@ -366,6 +1016,243 @@ fn measure_container(layout: &mut LayoutBox, parent_width: f32, parent_height: f
     layout.height = height;
 }
 
+/// Measure the children of a flex container and return its content size
+fn measure_flex(
+    layout: &mut LayoutBox,
+    child_parent_width: f32,
+    child_parent_height: f32,
+    (has_width, has_height): (bool, bool),
+) -> (f32, f32) {
+    let dir = layout.direction();
+    let gap = layout.node.style.gap.unwrap_or(0.0);
+
+    // First, measure all children with resolved parent dimensions.
+    // flex-basis replaces the child's own size on the main axis.
+    for child in &mut layout.children {
+        let basis = child.node.style.flex_basis.clone();
+        child.used_width = match (dir.is_row(), &basis) {
+            (true, Some(basis)) => Some(basis.resolve(child_parent_width)),
+            _ => None,
+        };
+        measure_layout_with_parent(child, child_parent_width, child_parent_height);
+        if let (false, Some(basis)) = (dir.is_row(), basis) {
+            child.height = basis.resolve(child_parent_height);
+        }
+    }
+
+    let wrap = layout.node.style.wrap.unwrap_or(false);
+    if dir.is_row() && !wrap {
+        shrink_children(&mut layout.children, child_parent_width, gap, true, child_parent_height);
+    } else if !dir.is_row() && has_height && layout.node.node_type != NodeType::Page {
+        // Pages overflow onto the next page instead of shrinking their content
+        shrink_children(&mut layout.children, child_parent_height, gap, false, child_parent_height);
+    }
+
+    if !dir.is_row() {
+        measure_column(&layout.children, gap)
+    } else if wrap && has_width {
+        let order = flow_order(&layout.children, false);
+        measure_wrapping_row(&layout.children, &order, gap, child_parent_width, &layout.cross_align())
+    } else {
+        measure_row(&layout.children, gap, &layout.cross_align())
+    }
+}
+
+/// Measure a grid container: assign every child a cell area, size the tracks
+/// and return the content size
+fn measure_grid(layout: &mut LayoutBox, inner_w: f32, inner_h: f32) -> (f32, f32) {
+    let style = &layout.node.style;
+    let col_gap = style.column_gap.or(style.gap).unwrap_or(0.0);
+    let row_gap = style.row_gap.or(style.gap).unwrap_or(0.0);
+    let mut columns = style.grid_template_columns.clone().unwrap_or_default();
+    let mut rows = style.grid_template_rows.clone().unwrap_or_default();
+    if columns.is_empty() {
+        columns.push(GridTrack::Fr(1.0));
+    }
+
+    let areas = grid_areas(&layout.children, columns.len());
+
+    // Items placed past the template get implicit auto tracks
+    for area in areas.iter().flatten() {
+        while columns.len() < area.column + area.column_span {
+            columns.push(GridTrack::Auto);
+        }
+        while rows.len() < area.row + area.row_span {
+            rows.push(GridTrack::Auto);
+        }
+    }
+
+    // Auto columns fit the natural width of their single-column items
+    let mut auto_widths = vec![0.0f32; columns.len()];
+    for (child, area) in layout.children.iter_mut().zip(&areas) {
+        child.used_width = None;
+        if let Some(area) = area {
+            if area.column_span == 1 && columns[area.column] == GridTrack::Auto {
+                measure_layout_with_parent(child, inner_w, 0.0);
+                auto_widths[area.column] = auto_widths[area.column].max(child.outer_width());
+            }
+        }
+    }
+    let column_widths = size_grid_tracks(&columns, &auto_widths, inner_w, col_gap);
+
+    // Measure every item at the width of its area (items stretch across it)
+    for (child, area) in layout.children.iter_mut().zip(&areas) {
+        let Some(area) = area else {
+            measure_layout_with_parent(child, inner_w, inner_h);
+            continue;
+        };
+        let area_w = track_span(&column_widths, area.column, area.column_span, col_gap);
+        if child.node.style.width.is_none() {
+            child.used_width = Some((area_w - child.margin_left - child.margin_right).max(0.0));
+        }
+        measure_layout_with_parent(child, area_w, 0.0);
+    }
+
+    // Auto rows fit their tallest single-row item
+    let mut auto_heights = vec![0.0f32; rows.len()];
+    for (child, area) in layout.children.iter().zip(&areas) {
+        if let Some(area) = area.filter(|a| a.row_span == 1) {
+            auto_heights[area.row] = auto_heights[area.row].max(child.outer_height());
+        }
+    }
+    // Without a definite height, fr rows size like auto rows
+    if inner_h <= 0.0 {
+        for track in rows.iter_mut() {
+            if matches!(track, GridTrack::Fr(_)) {
+                *track = GridTrack::Auto;
+            }
+        }
+    }
+    let mut row_heights = size_grid_tracks(&rows, &auto_heights, inner_h, row_gap);
+    for (i, track) in rows.iter().enumerate() {
+        if matches!(track, GridTrack::Fr(_)) {
+            row_heights[i] = row_heights[i].max(auto_heights[i]);
+        }
+    }
+
+    // Items spanning several rows grow the last auto row they cover if they don't fit
+    for (child, area) in layout.children.iter().zip(&areas) {
+        if let Some(area) = area.filter(|a| a.row_span > 1) {
+            let missing = child.outer_height() - track_span(&row_heights, area.row, area.row_span, row_gap);
+            let last_auto = (area.row..area.row + area.row_span).rev().find(|&r| rows[r] == GridTrack::Auto);
+            if let (true, Some(r)) = (missing > 0.0, last_auto) {
+                row_heights[r] += missing;
+            }
+        }
+    }
+
+    let content_w = track_span(&column_widths, 0, column_widths.len(), col_gap);
+    let content_h = track_span(&row_heights, 0, row_heights.len(), row_gap);
+    layout.grid = Some(GridLayout { column_widths, row_heights, areas });
+    (content_w, content_h)
+}
+
+/// Assign grid areas: items with a row line first, then the rest in order,
+/// auto-placed row by row into the first free cells
+fn grid_areas(children: &[LayoutBox], template_columns: usize) -> Vec<Option<GridArea>> {
+    let placement = |c: &LayoutBox| {
+        let column = c.node.style.grid_column.clone();
+        let row = c.node.style.grid_row.clone();
+        (
+            column.as_ref().and_then(|p| p.start).map(|s| s - 1),
+            column.map_or(1, |p| p.span),
+            row.as_ref().and_then(|p| p.start).map(|s| s - 1),
+            row.map_or(1, |p| p.span),
+        )
+    };
+
+    let column_count = children.iter()
+        .map(|c| {
+            let (col, col_span, _, _) = placement(c);
+            col.unwrap_or(0) + col_span
+        })
+        .fold(template_columns, usize::max);
+
+    // Rows of occupied cells; rows past the end are free
+    let mut occupied: Vec<Vec<bool>> = Vec::new();
+    let fits = |occupied: &Vec<Vec<bool>>, row: usize, col: usize, row_span: usize, col_span: usize| {
+        col + col_span <= column_count
+            && (row..row + row_span).all(|r| (col..col + col_span).all(|c| !occupied.get(r).is_some_and(|cells| cells[c])))
+    };
+
+    let mut areas: Vec<Option<GridArea>> = vec![None; children.len()];
+    let order = flow_order(children, false);
+    let (locked, auto): (Vec<usize>, Vec<usize>) = order.into_iter().partition(|&i| placement(&children[i]).2.is_some());
+
+    let mut cursor = (0usize, 0usize);
+    for i in locked.into_iter().chain(auto) {
+        let (col, col_span, row, row_span) = placement(&children[i]);
+        let area = match (row, col) {
+            (Some(row), Some(col)) => {
+                GridArea { row, column: col, row_span, column_span: col_span }
+            }
+            (Some(row), None) => {
+                let col = (0..column_count).find(|&c| fits(&occupied, row, c, row_span, col_span)).unwrap_or(0);
+                GridArea { row, column: col, row_span, column_span: col_span }
+            }
+            (None, Some(col)) => {
+                let row = (0..).find(|&r| fits(&occupied, r, col, row_span, col_span)).unwrap_or(0);
+                GridArea { row, column: col, row_span, column_span: col_span }
+            }
+            (None, None) => {
+                let (mut r, mut c) = cursor;
+                while !fits(&occupied, r, c, row_span, col_span) {
+                    c += 1;
+                    if c >= column_count {
+                        c = 0;
+                        r += 1;
+                    }
+                }
+                cursor = (r, c + col_span);
+                GridArea { row: r, column: c, row_span, column_span: col_span }
+            }
+        };
+
+        while occupied.len() < area.row + area.row_span {
+            occupied.push(vec![false; column_count]);
+        }
+        for cells in &mut occupied[area.row..area.row + area.row_span] {
+            cells[area.column..area.column + area.column_span].fill(true);
+        }
+        areas[i] = Some(area);
+    }
+    areas
+}
+
+/// Resolve track sizes: fixed and percentage tracks first, auto tracks to their
+/// content, then fr tracks share what is left of `available`
+fn size_grid_tracks(tracks: &[GridTrack], auto_sizes: &[f32], available: f32, gap: f32) -> Vec<f32> {
+    let mut sizes: Vec<f32> = tracks.iter().zip(auto_sizes)
+        .map(|(track, &auto)| match track {
+            GridTrack::Pt(v) => *v,
+            GridTrack::Percent(p) => available * p / 100.0,
+            GridTrack::Auto => auto,
+            GridTrack::Fr(_) => 0.0,
+        })
+        .collect();
+
+    let total_fr: f32 = tracks.iter().map(|t| if let GridTrack::Fr(f) = t { *f } else { 0.0 }).sum();
+    if total_fr > 0.0 {
+        let used = sizes.iter().sum::<f32>() + gap * tracks.len().saturating_sub(1) as f32;
+        let free = (available - used).max(0.0);
+        for (size, track) in sizes.iter_mut().zip(tracks) {
+            if let GridTrack::Fr(f) = track {
+                *size = free * f / total_fr;
+            }
+        }
+    }
+    sizes
+}
+
+/// Size of `count` tracks starting at `start`, including the gaps between them
+fn track_span(sizes: &[f32], start: usize, count: usize, gap: f32) -> f32 {
+    let end = (start + count).min(sizes.len());
+    if start >= end {
+        return 0.0;
+    }
+    sizes[start..end].iter().sum::<f32>() + gap * (end - start - 1) as f32
+}
+
 fn measure_table(layout: &mut LayoutBox, parent_width: f32, parent_height: f32) {
     let row_count = layout.children.len();
     let row_gap = layout.node.style.gap.unwrap_or(0.0);
@@ -547,9 +1434,8 @@ fn measure_column(children: &[LayoutBox], gap: f32) -> (f32, f32) {
     (width, height)
 }
 
-fn measure_row(children: &[LayoutBox], gap: f32) -> (f32, f32) {
+fn measure_row(children: &[LayoutBox], gap: f32, cross_align: &CrossAlign) -> (f32, f32) {
     let mut width: f32 = 0.0;
-    let mut height: f32 = 0.0;
     let mut flow_index = 0;
 
     for child in children.iter() {
@@ -561,47 +1447,236 @@ fn measure_row(children: &[LayoutBox], gap: f32) -> (f32, f32) {
         if flow_index > 0 {
             width += gap;
         }
-        height = height.max(child.outer_height());
         flow_index += 1;
     }
 
-    (width, height)
+    (width, row_line_height(children.iter().filter(|c| !c.is_absolute()), cross_align))
 }
 
-fn measure_wrapping_row(children: &[LayoutBox], gap: f32, max_width: f32) -> (f32, f32) {
-    let mut total_width: f32 = 0.0;
-    let mut total_height: f32 = 0.0;
-    let mut line_width: f32 = 0.0;
-    let mut line_height: f32 = 0.0;
+/// Height of a line of row children: the tallest child, or for baseline-aligned
+/// children, the tallest ascent above their shared baseline plus the deepest
+/// descent below it
+fn row_line_height<'a>(children: impl Iterator<Item = &'a LayoutBox>, cross_align: &CrossAlign) -> f32 {
+    let mut height: f32 = 0.0;
+    let mut max_ascent: f32 = 0.0;
+    let mut max_descent: f32 = 0.0;
 
     for child in children {
-        // Absolute children don't contribute to parent size
-        if child.is_absolute() {
-            continue;
+        if child.align_self(cross_align) == CrossAlign::Baseline {
+            let ascent = child.margin_top + measured_baseline(child);
+            max_ascent = max_ascent.max(ascent);
+            max_descent = max_descent.max(child.outer_height() - ascent);
+        } else {
+            height = height.max(child.outer_height());
+        }
+    }
+    height.max(max_ascent + max_descent)
+}
+
+/// Estimate the first baseline from measured sizes, before children are placed.
+/// Boxes without text use their bottom edge, as in CSS.
+fn measured_baseline(layout: &LayoutBox) -> f32 {
+    match layout.node.node_type {
+        NodeType::Text => match layout.inline_lines.first() {
+            Some(line) => line.top + line.baseline,
+            None => layout.ascent(),
+        },
+        NodeType::Image | NodeType::Svg | NodeType::FileAttachment => layout.height,
+        ref t if t.is_form_field() => layout.height,
+        _ => {
+            let (pad_t, _, _, _) = layout.node.style.padding_trbl();
+            layout.children.iter()
+                .find(|c| !c.is_absolute())
+                .map(|c| pad_t + c.margin_top + measured_baseline(c))
+                .unwrap_or(layout.height)
+        }
+    }
+}
+
+fn measure_wrapping_row(
+    children: &[LayoutBox],
+    order: &[usize],
+    gap: f32,
+    max_width: f32,
+    cross_align: &CrossAlign,
+) -> (f32, f32) {
+    let lines = wrap_lines(children, order, gap, max_width);
+    let total_width = lines.iter()
+        .map(|line| {
+            line.iter().map(|&i| children[i].outer_width()).sum::<f32>() + gap * line.len().saturating_sub(1) as f32
+        })
+        .fold(0.0, f32::max);
+    let total_height = lines.iter()
+        .map(|line| row_line_height(line.iter().map(|&i| &children[i]), cross_align))
+        .sum::<f32>()
+        + gap * lines.len().saturating_sub(1) as f32;
+
+    (total_width, total_height)
+}
+
+/// Group the children (in `order`) into lines no wider than `max_width`
+fn wrap_lines(children: &[LayoutBox], order: &[usize], gap: f32, max_width: f32) -> Vec<Vec<usize>> {
+    let mut lines: Vec<Vec<usize>> = Vec::new();
+    let mut line_w: f32 = 0.0;
+
+    for &i in order {
+        let cw = children[i].outer_width();
+        match lines.last_mut() {
+            Some(line) if line_w + gap + cw <= max_width => {
+                line.push(i);
+                line_w += gap + cw;
+            }
+            _ => {
+                lines.push(vec![i]);
+                line_w = cw;
+            }
+        }
+    }
+    lines
+}
+
+/// Shrink flow children along the main axis when they overflow `available`,
+/// in proportion to flex-shrink times their size (like CSS flex-shrink). A child
+/// that reaches its minimum size stops there and the rest shrink further.
+fn shrink_children(children: &mut [LayoutBox], available: f32, gap: f32, is_row: bool, parent_height: f32) {
+    let flow: Vec<usize> = (0..children.len()).filter(|&i| !children[i].is_absolute()).collect();
+    if flow.is_empty() {
+        return;
+    }
+
+    let main_size = |c: &LayoutBox| if is_row { c.width } else { c.height };
+    let margins = |c: &LayoutBox| if is_row { c.margin_left + c.margin_right } else { c.margin_top + c.margin_bottom };
+    let base: Vec<f32> = flow.iter().map(|&i| main_size(&children[i])).collect();
+    let extra: f32 = flow.iter().map(|&i| margins(&children[i])).sum::<f32>() + gap * (flow.len() - 1) as f32;
+    if base.iter().sum::<f32>() + extra <= available {
+        return;
+    }
+
+    let weights: Vec<f32> = flow.iter().zip(&base).map(|(&i, size)| children[i].flex_shrink() * size).collect();
+    let mins: Vec<f32> = flow.iter().map(|&i| auto_min_size(&children[i], is_row, available)).collect();
+    let mut targets = base.clone();
+    let mut frozen: Vec<bool> = weights.iter().map(|w| *w <= 0.0).collect();
+    loop {
+        let used: f32 = (0..flow.len()).map(|k| if frozen[k] { targets[k] } else { base[k] }).sum::<f32>() + extra;
+        let overflow = used - available;
+        let total_weight: f32 = (0..flow.len()).filter(|&k| !frozen[k]).map(|k| weights[k]).sum();
+        if overflow <= 0.0 || total_weight <= 0.0 {
+            for k in (0..flow.len()).filter(|&k| !frozen[k]) {
+                targets[k] = base[k];
+            }
+            break;
         }
 
-        let cw = child.outer_width();
-        let ch = child.outer_height();
+        let mut clamped = false;
+        let unfrozen: Vec<usize> = (0..flow.len()).filter(|&k| !frozen[k]).collect();
+        for k in unfrozen {
+            targets[k] = base[k] - overflow * weights[k] / total_weight;
+            if targets[k] < mins[k] {
+                targets[k] = mins[k];
+                frozen[k] = true;
+                clamped = true;
+            }
+        }
+        if !clamped {
+            break;
+        }
+    }
 
-        if line_width == 0.0 {
-            line_width = cw;
-            line_height = ch;
-        } else if line_width + gap + cw > max_width {
-            // Wrap
-            total_width = total_width.max(line_width);
-            total_height += line_height + gap;
-            line_width = cw;
-            line_height = ch;
+    for (k, &i) in flow.iter().enumerate() {
+        let child = &mut children[i];
+        let (current, target) = (base[k], targets[k].max(0.0));
+        if target >= current {
+            continue;
+        }
+
+        // Images and icons keep their aspect ratio
+        let scale = target / current;
+        let replaced = matches!(child.node.node_type, NodeType::Image | NodeType::Svg | NodeType::FileAttachment);
+        if is_row {
+            // Re-measure at the narrower width so text wraps inside it
+            let height = child.height;
+            child.used_width = Some(target);
+            measure_layout_with_parent(child, available, parent_height);
+            if replaced {
+                child.height = height * scale;
+            }
         } else {
-            line_width += gap + cw;
-            line_height = line_height.max(ch);
+            if replaced {
+                child.width *= scale;
+            }
+            child.height = target;
         }
     }
+}
+
+/// Smallest main size a child can shrink to: its min-width/min-height if set,
+/// otherwise its content (its min-content width in a row; the full height of
+/// text, containers and images in a column). Clipped boxes and form fields can
+/// shrink freely.
+fn auto_min_size(child: &LayoutBox, is_row: bool, available: f32) -> f32 {
+    let explicit = if is_row { &child.node.style.min_width } else { &child.node.style.min_height };
+    if let Some(ref min) = explicit {
+        return min.resolve(available);
+    }
 
-    total_width = total_width.max(line_width);
-    total_height += line_height;
+    match child.node.node_type {
+        ref t if t.is_form_field() => 0.0,
+        _ if child.clips_overflow() => 0.0,
+        _ if is_row => min_content_width(child, available),
+        _ => child.height,
+    }
+}
 
-    (total_width, total_height)
+/// Narrowest a box gets without its content overflowing: the longest word of
+/// text, the measured size of images and icons, and for containers the
+/// minimums of their children side by side in a row, the largest of them
+/// otherwise, plus padding
+fn min_content_width(layout: &LayoutBox, available: f32) -> f32 {
+    if let Some(ref min) = layout.node.style.min_width {
+        return min.resolve(available);
+    }
+    let longest_word = |text: &LayoutBox| {
+        let size = text.font_size();
+        let metrics = text.font_metrics();
+        text.node.text.as_deref().unwrap_or("")
+            .split_whitespace()
+            .map(|w| measure_text_width(w, size, metrics))
+            .fold(0.0, f32::max)
+    };
+    let outer = |child: &LayoutBox| min_content_width(child, available) + child.margin_left + child.margin_right;
+
+    match layout.node.node_type {
+        NodeType::Image | NodeType::Svg | NodeType::FileAttachment => layout.width,
+        ref t if t.is_form_field() => 0.0,
+        NodeType::Text if layout.is_inline_context() => layout.children.iter()
+            .map(|c| if c.node.node_type == NodeType::Text { longest_word(c) } else { outer(c) })
+            .fold(0.0, f32::max),
+        NodeType::Text => longest_word(layout),
+        _ => {
+            let (_, pad_r, _, pad_l) = layout.node.style.padding_trbl();
+            let flow: Vec<&LayoutBox> = layout.children.iter().filter(|c| !c.is_absolute()).collect();
+            let side_by_side = layout.node.node_type == NodeType::Row
+                || (layout.direction().is_row() && !layout.is_grid() && !layout.node.style.wrap.unwrap_or(false));
+            let content = if side_by_side {
+                let gap = layout.node.style.gap.unwrap_or(0.0);
+                flow.iter().map(|c| outer(c)).sum::<f32>() + gap * flow.len().saturating_sub(1) as f32
+            } else {
+                flow.iter().map(|c| outer(c)).fold(0.0, f32::max)
+            };
+            pad_l + pad_r + content
+        }
+    }
+}
+
+/// Indices of the flow (non-absolute) children in placement order: sorted by
+/// `order`, then reversed for row-reverse/column-reverse
+fn flow_order(children: &[LayoutBox], reverse: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..children.len()).filter(|&i| !children[i].is_absolute()).collect();
+    order.sort_by_key(|&i| children[i].order());
+    if reverse {
+        order.reverse();
+    }
+    order
 }
 
 // ============================================================================
@@ -612,18 +1687,72 @@ pub fn place_layout(layout: &mut LayoutBox, x: f32, y: f32) {
     // Position is inside the margin
     layout.x = x + layout.margin_left;
     layout.y = y - layout.margin_top;
+    place_children(layout);
 
+    // Apply relative offset after normal placement
+    if layout.is_relative() {
+        apply_relative_offset(layout);
+    }
+}
+
+/// Place the children of a box whose own position is set
+fn place_children(layout: &mut LayoutBox) {
     match layout.node.node_type {
-        NodeType::Page | NodeType::View => place_container(layout),
+        NodeType::Page | NodeType::View | NodeType::List | NodeType::Toc => place_container(layout),
+        NodeType::Text if layout.is_inline_context() => place_inline(layout),
+        NodeType::ListItem => {
+            place_container(layout);
+            align_list_marker(layout);
+        }
         NodeType::Table => place_table(layout),
         NodeType::Row => place_row_element(layout),
         NodeType::Cell => place_cell(layout),
         _ => {} // Text/Image have no children to place
     }
+}
 
-    // Apply relative offset after normal placement
-    if layout.is_relative() {
-        apply_relative_offset(layout);
+/// Align each line of inline content and position the inline boxes.
+/// Spans are given the position of their first fragment.
+fn place_inline(layout: &mut LayoutBox) {
+    let align = layout.text_align();
+    let width = layout.width;
+    let (x, y) = (layout.x, layout.y);
+    let mut placed = vec![false; layout.children.len()];
+
+    for line in layout.inline_lines.iter_mut() {
+        line.offset_x = match align {
+            TextAlign::Center => (width - line.width) / 2.0,
+            TextAlign::Right => width - line.width,
+            TextAlign::Left | TextAlign::Justify => 0.0,
+        };
+
+        for frag in &line.fragments {
+            let child = &mut layout.children[frag.child];
+            if placed[frag.child] {
+                continue;
+            }
+            placed[frag.child] = true;
+
+            let frag_x = x + line.offset_x + frag.x;
+            if frag.text.is_some() {
+                let size = child.font_size();
+                child.x = frag_x;
+                child.y = y - frag.y + child.ascent();
+                child.width = frag.width;
+                child.height = measure_line_height(size, child.line_height_multiplier());
+            } else {
+                place_layout(child, frag_x, y - frag.y);
+            }
+        }
+    }
+}
+
+/// Put the marker on the baseline of the item's first line of text
+fn align_list_marker(layout: &mut LayoutBox) {
+    if let Some(baseline) = layout.first_baseline() {
+        if let Some(ref mut marker) = layout.marker {
+            marker.baseline = baseline;
+        }
     }
 }
 
@@ -644,10 +1773,15 @@ fn apply_relative_offset(layout: &mut LayoutBox) {
 }
 
 fn place_container(layout: &mut LayoutBox) {
-    let dir = layout.node.style.direction.clone().unwrap_or(Direction::Column);
+    let dir = layout.direction();
     let gap = layout.node.style.gap.unwrap_or(0.0);
     let (pad_t, pad_r, pad_b, pad_l) = layout.node.style.padding_trbl();
-    let main_align = layout.main_align();
+    // Reversed directions start from the main-axis end
+    let main_align = match (dir.is_reverse(), layout.main_align()) {
+        (true, MainAlign::Start) => MainAlign::End,
+        (true, MainAlign::End) => MainAlign::Start,
+        (_, align) => align,
+    };
     let cross_align = layout.cross_align();
 
     let inner_w = layout.width - pad_l - pad_r;
@@ -662,18 +1796,14 @@ fn place_container(layout: &mut LayoutBox) {
     }
 
     // Place flow children (non-absolute)
-    match dir {
-        Direction::Column => {
-            place_column(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
-        }
-        Direction::Row => {
-            let wrap = layout.node.style.wrap.unwrap_or(false);
-            if wrap {
-                place_wrapping_row(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
-            } else {
-                place_row(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
-            }
-        }
+    if layout.is_grid() {
+        place_grid(layout, pad_t, pad_l);
+    } else if !dir.is_row() {
+        place_column(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
+    } else if layout.node.style.wrap.unwrap_or(false) {
+        place_wrapping_row(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
+    } else {
+        place_row(layout, inner_w, inner_h, gap, pad_t, pad_l, main_align, cross_align);
     }
 
     // Place absolute children relative to this container
@@ -688,6 +1818,43 @@ fn place_container(layout: &mut LayoutBox) {
     }
 }
 
+/// Place grid items in their cell areas. Items stretch to the area height
+/// unless crossAlign/alignSelf says otherwise.
+fn place_grid(layout: &mut LayoutBox, pad_t: f32, pad_l: f32) {
+    let grid = match layout.grid.clone() {
+        Some(g) => g,
+        None => return,
+    };
+    let style = &layout.node.style;
+    let col_gap = style.column_gap.or(style.gap).unwrap_or(0.0);
+    let row_gap = style.row_gap.or(style.gap).unwrap_or(0.0);
+    let cross_align = style.cross_align.clone().unwrap_or(CrossAlign::Stretch);
+    let x = layout.x + pad_l;
+    let y = layout.y - pad_t;
+
+    for (child, area) in layout.children.iter_mut().zip(&grid.areas) {
+        let Some(area) = area else { continue };
+        let cell_x = x + track_span(&grid.column_widths, 0, area.column, col_gap)
+            + if area.column > 0 { col_gap } else { 0.0 };
+        let cell_y = y - track_span(&grid.row_heights, 0, area.row, row_gap)
+            - if area.row > 0 { row_gap } else { 0.0 };
+        let cell_h = track_span(&grid.row_heights, area.row, area.row_span, row_gap);
+
+        let free = cell_h - child.outer_height();
+        let child_y = match child.align_self(&cross_align) {
+            CrossAlign::Start | CrossAlign::Baseline => cell_y,
+            CrossAlign::Center => cell_y - free / 2.0,
+            CrossAlign::End => cell_y - free,
+            CrossAlign::Stretch => {
+                child.height = cell_h - child.margin_top - child.margin_bottom;
+                cell_y
+            }
+        };
+
+        place_layout(child, cell_x, child_y);
+    }
+}
+
 fn place_table(layout: &mut LayoutBox) {
     let table_layout = match layout.table.clone() {
         Some(t) => t,
@@ -805,6 +1972,11 @@ fn place_cell(layout: &mut LayoutBox) {
     }
 }
 
+/// Place a fixed box against the page box (its offsets are from the page edges)
+pub fn place_fixed(layout: &mut LayoutBox, page_width: f32, page_height: f32) {
+    place_absolute_child(layout, 0.0, page_height, page_width, page_height, 0.0, 0.0, 0.0, 0.0);
+}
+
 /// Place an absolutely positioned child relative to its container
 fn place_absolute_child(
     child: &mut LayoutBox,
@@ -841,12 +2013,7 @@ fn place_absolute_child(
 
     child.x = x;
     child.y = y;
-
-    // Recursively place children of the absolute element
-    match child.node.node_type {
-        NodeType::Page | NodeType::View => place_container(child),
-        _ => {}
-    }
+    place_children(child);
 }
 
 fn place_column(
@@ -902,12 +2069,10 @@ fn place_column(
         }
     };
 
-    let mut flow_index = 0;
-    for child in layout.children.iter_mut() {
-        // Skip absolute children - they're placed separately
-        if child.is_absolute() {
-            continue;
-        }
+    // Absolute children are skipped - they're placed separately
+    let order = flow_order(&layout.children, layout.direction().is_reverse());
+    for (flow_index, &i) in order.iter().enumerate() {
+        let child = &mut layout.children[i];
 
         // Apply flex growth
         if child.flex() > 0.0 && total_flex > 0.0 {
@@ -915,8 +2080,8 @@ fn place_column(
         }
 
         // Cross-axis alignment (horizontal)
-        let child_x = match cross_align {
-            CrossAlign::Start => x + pad_l + child.margin_left,
+        let child_x = match child.align_self(&cross_align) {
+            CrossAlign::Start | CrossAlign::Baseline => x + pad_l + child.margin_left,
             CrossAlign::Center => x + pad_l + (inner_w - child.outer_width()) / 2.0 + child.margin_left,
             CrossAlign::End => x + pad_l + inner_w - child.outer_width() + child.margin_left,
             CrossAlign::Stretch => {
@@ -933,7 +2098,6 @@ fn place_column(
         if flow_index < n - 1 {
             cursor_y -= base_gap;
         }
-        flow_index += 1;
     }
 }
 
@@ -990,12 +2154,11 @@ fn place_row(
         }
     };
 
-    let mut flow_index = 0;
-    for child in layout.children.iter_mut() {
-        // Skip absolute children - they're placed separately
-        if child.is_absolute() {
-            continue;
-        }
+    // Absolute children are skipped - they're placed separately
+    let order = flow_order(&layout.children, layout.direction().is_reverse());
+    let mut baseline_children: Vec<usize> = Vec::new();
+    for (flow_index, &i) in order.iter().enumerate() {
+        let child = &mut layout.children[i];
 
         // Apply flex growth
         if child.flex() > 0.0 && total_flex > 0.0 {
@@ -1003,8 +2166,12 @@ fn place_row(
         }
 
         // Cross-axis alignment (vertical)
-        let child_y = match cross_align {
-            CrossAlign::Start => y - pad_t - child.margin_top,
+        let align = child.align_self(&cross_align);
+        if align == CrossAlign::Baseline {
+            baseline_children.push(i);
+        }
+        let child_y = match align {
+            CrossAlign::Start | CrossAlign::Baseline => y - pad_t - child.margin_top,
             CrossAlign::Center => y - pad_t - (inner_h - child.outer_height()) / 2.0 - child.margin_top,
             CrossAlign::End => y - pad_t - (inner_h - child.outer_height()) - child.margin_top,
             CrossAlign::Stretch => {
@@ -1021,7 +2188,32 @@ fn place_row(
         if flow_index < n - 1 {
             cursor_x += base_gap;
         }
-        flow_index += 1;
+    }
+
+    align_baselines(&mut layout.children, &baseline_children, y - pad_t);
+}
+
+/// Shift children that were placed at `line_top` down so their first baselines line up
+fn align_baselines(children: &mut [LayoutBox], indices: &[usize], line_top: f32) {
+    let baselines: Vec<f32> = indices.iter()
+        .map(|&i| {
+            let child = &children[i];
+            line_top - child.y + child.first_baseline().unwrap_or(child.height)
+        })
+        .collect();
+    let shared = baselines.iter().cloned().fold(0.0, f32::max);
+
+    for (&i, baseline) in indices.iter().zip(baselines) {
+        translate_layout(&mut children[i], 0.0, baseline - shared);
+    }
+}
+
+/// Move a placed box and everything inside it
+fn translate_layout(layout: &mut LayoutBox, dx: f32, dy: f32) {
+    layout.x += dx;
+    layout.y += dy;
+    for child in &mut layout.children {
+        translate_layout(child, dx, dy);
     }
 }
 
@@ -1039,21 +2231,13 @@ fn place_wrapping_row(
     let y = layout.y;
 
     // Group children into lines
-    let mut lines: Vec<Vec<usize>> = vec![vec![]];
-    let mut line_w: f32 = 0.0;
+    let order = flow_order(&layout.children, false);
+    let mut lines = wrap_lines(&layout.children, &order, gap, inner_w);
 
-    for (i, child) in layout.children.iter().enumerate() {
-        let cw = child.outer_width();
-
-        if line_w == 0.0 {
-            lines.last_mut().unwrap().push(i);
-            line_w = cw;
-        } else if line_w + gap + cw > inner_w {
-            lines.push(vec![i]);
-            line_w = cw;
-        } else {
-            lines.last_mut().unwrap().push(i);
-            line_w += gap + cw;
+    // Reversed rows flip the order within each line
+    if layout.direction().is_reverse() {
+        for line in lines.iter_mut() {
+            line.reverse();
         }
     }
 
@@ -1070,9 +2254,8 @@ fn place_wrapping_row(
             .sum::<f32>()
             + gap * (line_indices.len().saturating_sub(1) as f32);
 
-        let line_h: f32 = line_indices.iter()
-            .map(|&i| layout.children[i].outer_height())
-            .fold(0.0, f32::max);
+        // Measured like the container, so baseline-aligned lines fit
+        let line_h = row_line_height(line_indices.iter().map(|&i| &layout.children[i]), &cross_align);
 
         let free = (inner_w - line_total_w).max(0.0);
         let n = line_indices.len();
@@ -1093,11 +2276,16 @@ fn place_wrapping_row(
             }
         };
 
+        let mut baseline_children: Vec<usize> = Vec::new();
         for (j, &child_idx) in line_indices.iter().enumerate() {
             let child = &mut layout.children[child_idx];
 
-            let child_y = match cross_align {
-                CrossAlign::Start => cursor_y - child.margin_top,
+            let align = child.align_self(&cross_align);
+            if align == CrossAlign::Baseline {
+                baseline_children.push(child_idx);
+            }
+            let child_y = match align {
+                CrossAlign::Start | CrossAlign::Baseline => cursor_y - child.margin_top,
                 CrossAlign::Center => cursor_y - (line_h - child.outer_height()) / 2.0 - child.margin_top,
                 CrossAlign::End => cursor_y - (line_h - child.outer_height()) - child.margin_top,
                 CrossAlign::Stretch => cursor_y - child.margin_top,
@@ -1113,6 +2301,255 @@ fn place_wrapping_row(
             }
         }
 
+        align_baselines(&mut layout.children, &baseline_children, cursor_y);
+
         cursor_y -= line_h + gap;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build, measure and place a node tree at the top of an A4 page
+    fn layout(json: &str) -> LayoutBox {
+        let node: JsonNode = serde_json::from_str(json).unwrap();
+        let mut root = build_layout(&node);
+        measure_layout(&mut root);
+        place_layout(&mut root, 0.0, 842.0);
+        root
+    }
+
+    #[test]
+    fn test_list_markers_share_a_column() {
+        let root = layout(r#"{ "type": "page", "children": [
+            { "type": "list", "style": { "listStyle": "decimal" }, "start": 9, "children": [
+                { "type": "listItem", "children": [{ "type": "text", "text": "Nine", "style": { "fontSize": 10 } }] },
+                { "type": "listItem", "children": [{ "type": "text", "text": "Ten", "style": { "fontSize": 10, "fontWeight": "bold" } }] }
+            ] }
+        ] }"#);
+        let list = &root.children[0];
+        let nine = list.children[0].marker.as_ref().unwrap();
+        let ten = list.children[1].marker.as_ref().unwrap();
+        assert_eq!((nine.text.as_str(), ten.text.as_str()), ("9.", "10."));
+        assert_eq!(nine.font_size, 10.0);
+
+        // The marker takes the weight of the item's first line
+        assert!(!nine.bold);
+        assert!(ten.bold);
+
+        // Content is indented past the widest marker, the same for every item
+        let bold = font_metrics::get_metrics(true, false);
+        let widest = measure_text_width("10.", 10.0, bold);
+        let indent = (widest + 5.0).max(18.0);
+        assert_eq!(list.children[0].node.style.padding_left, Some(indent));
+        assert_eq!(list.children[1].node.style.padding_left, Some(indent));
+        assert_eq!(ten.right, indent - 5.0);
+        assert_eq!(list.children[1].children[0].x, list.children[1].x + indent);
+    }
+
+    #[test]
+    fn test_inline_words_break_across_spans() {
+        let root = layout(r#"{ "type": "page", "children": [
+            { "type": "text", "style": { "width": 60 }, "children": [
+                { "type": "text", "text": "one " },
+                { "type": "text", "text": "bold", "style": { "fontWeight": "bold" } },
+                { "type": "text", "text": "ness two" }
+            ] }
+        ] }"#);
+        let text = &root.children[0];
+        let regular = font_metrics::get_metrics(false, false);
+        let bold = font_metrics::get_metrics(true, false);
+        let boldness = measure_text_width("bold", 12.0, bold) + measure_text_width("ness", 12.0, regular);
+        assert!(measure_text_width("one ", 12.0, regular) + boldness > 60.0);
+
+        // "bold" + "ness" is one word, so it moves to the next line as a whole
+        let lines: Vec<Vec<(usize, Option<&str>)>> = text.inline_lines.iter()
+            .map(|l| l.fragments.iter().map(|f| (f.child, f.text.as_deref())).collect())
+            .collect();
+        assert_eq!(lines, vec![
+            vec![(0, Some("one"))],
+            vec![(1, Some("bold")), (2, Some("ness"))],
+            vec![(2, Some("two"))],
+        ]);
+        let line = &text.inline_lines[1];
+        assert_eq!(line.fragments[1].x, measure_text_width("bold", 12.0, bold));
+        assert_eq!(line.width, boldness);
+        assert_eq!(text.height, 3.0 * 12.0 * 1.4);
+    }
+
+    #[test]
+    fn test_inline_line_fits_its_largest_font() {
+        let root = layout(r#"{ "type": "page", "children": [
+            { "type": "text", "style": { "fontSize": 10 }, "children": [
+                { "type": "text", "text": "small " },
+                { "type": "text", "text": "Large", "style": { "fontSize": 20 } }
+            ] }
+        ] }"#);
+        let text = &root.children[0];
+        let metrics = font_metrics::get_metrics(false, false);
+        let line = &text.inline_lines[0];
+
+        // The baseline sits below the tallest ascent, the line ends below the deepest descent
+        assert_eq!(line.baseline, metrics.ascent(20.0));
+        assert_eq!(line.height, 20.0 * 1.4);
+        assert_eq!(text.height, line.height);
+
+        // Both spans share the baseline; the second starts after the first and a space
+        let (small, large) = (&line.fragments[0], &line.fragments[1]);
+        assert_eq!((small.y, large.y), (line.baseline, line.baseline));
+        let space = metrics.char_width_pt(' ', 10.0);
+        assert_eq!(large.x, measure_text_width("small", 10.0, metrics) + space);
+    }
+
+    #[test]
+    fn test_inline_fragments_are_placed() {
+        let root = layout(r#"{ "type": "page", "style": { "padding": 20 }, "children": [
+            { "type": "text", "style": { "width": 200, "textAlign": "right" }, "children": [
+                { "type": "text", "text": "Total: " },
+                { "type": "image", "src": "x.png", "style": { "width": 10, "height": 10 }, "alt": "icon" }
+            ] }
+        ] }"#);
+        let text = &root.children[0];
+        let metrics = font_metrics::get_metrics(false, false);
+        let line = &text.inline_lines[0];
+        let label = measure_text_width("Total:", 12.0, metrics) + metrics.char_width_pt(' ', 12.0);
+        assert_eq!(line.width, label + 10.0);
+        assert_eq!(line.offset_x, 200.0 - line.width);
+
+        // Spans get their first fragment's position, boxes sit on the baseline
+        let (span, image) = (&text.children[0], &text.children[1]);
+        assert_eq!(span.x, 20.0 + line.offset_x);
+        assert_eq!(span.y, 842.0 - 20.0 - line.baseline + span.ascent());
+        assert_eq!(image.x, 20.0 + line.offset_x + label);
+        assert_eq!(image.y, 842.0 - 20.0 - (line.baseline - 10.0));
+    }
+
+    const BASELINE_ROW: &str = r#"
+        { "type": "text", "text": "Big", "style": { "fontSize": 20 } },
+        { "type": "view", "style": { "width": 60, "paddingBottom": 30 }, "children": [
+            { "type": "text", "text": "small", "style": { "fontSize": 10 } }
+        ] }"#;
+
+    #[test]
+    fn test_baseline_row_fits_shifted_children() {
+        let root = layout(&format!(r#"{{ "type": "page", "children": [
+            {{ "type": "view", "style": {{ "direction": "row", "crossAlign": "baseline" }}, "children": [{}] }}
+        ] }}"#, BASELINE_ROW));
+        let row = &root.children[0];
+        let (big, boxed) = (&row.children[0], &row.children[1]);
+        let metrics = font_metrics::get_metrics(false, false);
+        let (a20, a10) = (metrics.ascent(20.0), metrics.ascent(10.0));
+
+        // The box is shifted down to the big text's baseline, and the row grows to hold it
+        assert_eq!(big.y - big.first_baseline().unwrap(), boxed.y - boxed.first_baseline().unwrap());
+        assert_eq!(boxed.y, row.y - (a20 - a10));
+        assert_eq!(row.height, a20 + boxed.height - a10);
+        assert_eq!(boxed.y - boxed.height, row.y - row.height);
+    }
+
+    #[test]
+    fn test_wrapping_baseline_row_fits_shifted_children() {
+        let root = layout(&format!(r#"{{ "type": "page", "children": [
+            {{ "type": "view", "style": {{ "direction": "row", "wrap": true, "width": 100, "crossAlign": "baseline" }},
+              "children": [{}, {{ "type": "text", "text": "Next" }}] }}
+        ] }}"#, BASELINE_ROW));
+        let row = &root.children[0];
+        let (boxed, next) = (&row.children[1], &row.children[2]);
+        let metrics = font_metrics::get_metrics(false, false);
+        let first_line = metrics.ascent(20.0) + boxed.height - metrics.ascent(10.0);
+
+        // The second line starts below the shifted box instead of overlapping it
+        assert_eq!(next.x, row.x);
+        assert_eq!(next.y, row.y - first_line);
+        assert!(boxed.y - boxed.height >= next.y);
+        assert_eq!(row.height, first_line + 12.0 * 1.4);
+    }
+
+    #[test]
+    fn test_row_shrink_keeps_images_and_overflows_text_boxes() {
+        let root = layout(r#"{ "type": "page", "children": [
+            { "type": "view", "style": { "direction": "row" }, "children": [
+                { "type": "image", "src": "x.png", "alt": "x", "style": { "width": 400, "height": 200 } },
+                { "type": "view", "style": { "width": 400 }, "children": [{ "type": "text", "text": "Some words" }] }
+            ] }
+        ] }"#);
+        let row = &root.children[0];
+        let (image, text_box) = (&row.children[0], &row.children[1]);
+        // The image can't get narrower than its size, so the other box takes all the shrinking
+        assert_eq!((image.width, image.height), (400.0, 200.0));
+        assert_eq!(text_box.width, 195.0);
+    }
+
+    #[test]
+    fn test_row_shrink_stops_at_the_content_of_containers() {
+        let card = |text: &str, size: u32| format!(
+            r#"{{ "type": "view", "style": {{ "width": 400, "padding": 10 }}, "children": [
+                {{ "type": "view", "children": [{{ "type": "text", "text": "{}", "style": {{ "fontSize": {} }} }}] }}
+            ] }}"#, text, size);
+        let root = layout(&format!(r#"{{ "type": "page", "children": [
+            {{ "type": "view", "style": {{ "direction": "row" }}, "children": [{}, {}, {}] }}
+        ] }}"#, card("Extraordinarily", 30), card("a b", 12), card("c d", 12)));
+        let row = &root.children[0];
+
+        // The first card keeps its longest word; the others shrink further to make up for it
+        let min = measure_text_width("Extraordinarily", 30.0, font_metrics::get_metrics(false, false)) + 20.0;
+        assert!(min > 595.0 / 3.0);
+        assert_eq!(row.children[0].width, min);
+        assert_eq!(row.children[1].width, (595.0 - min) / 2.0);
+        assert_eq!(row.children[2].width, (595.0 - min) / 2.0);
+    }
+
+    #[test]
+    fn test_shrunk_image_keeps_its_aspect_ratio() {
+        let root = layout(r#"{ "type": "page", "children": [
+            { "type": "view", "style": { "direction": "row" }, "children": [
+                { "type": "image", "src": "x.png", "alt": "x", "style": { "width": 1190, "height": 400, "minWidth": 0 } }
+            ] }
+        ] }"#);
+        let image = &root.children[0].children[0];
+        assert_eq!((image.width, image.height), (595.0, 200.0));
+    }
+
+    #[test]
+    fn test_measuring_again_starts_from_the_authored_width() {
+        let node: JsonNode = serde_json::from_str(r#"{ "type": "view", "style": { "direction": "row" }, "children": [
+            { "type": "view", "style": { "width": 200 } },
+            { "type": "view", "style": { "width": 50, "flexBasis": 200 } }
+        ] }"#).unwrap();
+        let mut row = build_layout(&node);
+
+        measure_layout_with_parent(&mut row, 300.0, 842.0);
+        assert_eq!((row.children[0].width, row.children[1].width), (150.0, 150.0));
+        assert_eq!(row.children[0].node.style.width, Some(Dimension::Pt(200.0)));
+
+        measure_layout_with_parent(&mut row, 800.0, 842.0);
+        assert_eq!((row.children[0].width, row.children[1].width), (200.0, 200.0));
+    }
+
+    #[test]
+    fn test_grid_tracks_and_items_follow_the_width() {
+        let node: JsonNode = serde_json::from_str(r#"{ "type": "view",
+            "style": { "display": "grid", "gridTemplateColumns": [100, "1fr", "2fr"], "gap": 10 },
+            "children": [
+                { "type": "text", "text": "A" },
+                { "type": "text", "text": "B", "style": { "gridColumn": "2 / span 2" } },
+                { "type": "view", "style": { "width": 20, "height": 30 } }
+            ] }"#).unwrap();
+        let mut grid = build_layout(&node);
+
+        measure_layout_with_parent(&mut grid, 420.0, 842.0);
+        let tracks = &grid.grid.as_ref().unwrap();
+        assert_eq!(tracks.column_widths, vec![100.0, 100.0, 200.0]);
+        assert_eq!(tracks.row_heights, vec![12.0 * 1.4, 30.0]);
+        assert_eq!(grid.children[1].width, 310.0);
+        assert_eq!(grid.children[2].width, 20.0);
+
+        // Measured again narrower (like a shrinking row does), items fit the new tracks
+        measure_layout_with_parent(&mut grid, 270.0, 842.0);
+        assert_eq!(grid.grid.as_ref().unwrap().column_widths, vec![100.0, 50.0, 100.0]);
+        assert_eq!(grid.children[0].width, 100.0);
+        assert_eq!(grid.children[1].width, 160.0);
+        assert_eq!(grid.children[1].node.style.width, None);
+    }
+}
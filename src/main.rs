@@ -1,16 +1,26 @@
 use axum::{routing::post, Json, Router};
-use axum::response::IntoResponse;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use std::net::SocketAddr;
 
 use tower_http::cors::{CorsLayer, Any};
+use crate::html::{html_to_node, HtmlPayload};
 use crate::layout::LayoutPayload;
-use crate::pdf::from_layout;
+use crate::pdf::{from_layout, RenderError};
 
 mod pdf;
 mod layout;
 mod layout_box;
 mod font_metrics;
 mod svg;
+mod html;
+mod pdf_resources;
+mod pdf_metadata;
+mod pdf_a;
+mod pdf_attachments;
+mod pdf_tags;
+mod pdf_forms;
+mod toc;
 
 
 #[tokio::main]
@@ -23,6 +33,7 @@ async fn main() {
 
     let app = Router::new()
         .route("/render-pdf", post(render_pdf))
+        .route("/render-html", post(render_html))
         .layer(cors);  // <-- attach CORS middleware
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
@@ -39,14 +50,47 @@ async fn main() {
 
 
 
-async fn render_pdf(Json(payload): Json<LayoutPayload>) -> impl IntoResponse {
-    let pdf_bytes = from_layout(&payload.root);
+async fn render_pdf(Json(payload): Json<LayoutPayload>) -> Response {
+    pdf_response(from_layout(&payload.root, &payload.document))
+}
+
+async fn render_html(Json(payload): Json<HtmlPayload>) -> Response {
+    match html_to_node(&payload.html) {
+        Ok(root) => pdf_response(from_layout(&root, &payload.document)),
+        Err(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+    }
+}
 
-    (
-        [
-            ("Content-Type", "application/pdf"),
-            ("Content-Disposition", "attachment; filename=\"doc.pdf\""),
-        ],
-        pdf_bytes,
-    )
-}
\ No newline at end of file
+fn pdf_response(result: Result<Vec<u8>, RenderError>) -> Response {
+    match result {
+        Ok(pdf_bytes) => (
+            [
+                ("Content-Type", "application/pdf"),
+                ("Content-Disposition", "attachment; filename=\"doc.pdf\""),
+            ],
+            pdf_bytes,
+        )
+            .into_response(),
+        // Our failure, not the request's
+        Err(e @ RenderError::Finish(_)) => {
+            eprintln!("{}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        // Valid JSON, but a document that can't be made as asked
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_errors_map_to_statuses() {
+        let status = |e: RenderError| pdf_response(Err(e)).status();
+        assert_eq!(status(RenderError::Conformance("no fonts".into())), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(status(RenderError::Invoice("empty".into())), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(status(RenderError::Finish("bad xref".into())), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(pdf_response(Ok(b"%PDF".to_vec())).status(), StatusCode::OK);
+    }
+}
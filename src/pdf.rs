@@ -1,17 +1,31 @@
 use printpdf::*;
 use printpdf::path::{PaintMode, WindingOrder};
+use std::borrow::Cow;
+use std::fmt;
 use std::io::{BufWriter, Cursor};
 
-use crate::layout::{JsonNode, NodeType, TextAlign, Color, Style};
-use crate::layout_box::{LayoutBox, build_layout, measure_layout, place_layout};
+use crate::layout::{
+    BorderStyle, BoxShadow, Color, Dimension, DocumentOptions, FontWeight, Gradient, GradientType, JsonNode, ListStyle,
+    Metadata, NodeType, ObjectFit, Role, Style, TextAlign, TransformFunction, Watermark, WatermarkLayer,
+};
+use crate::layout_box::{LayoutBox, RADIO_LABEL_OFFSET, build_layout, measure_layout, place_fixed, place_layout};
+use crate::pdf_a::FontFiles;
+use crate::pdf_attachments;
+use crate::pdf_forms::{self, FormField};
+use crate::pdf_resources;
+use crate::pdf_tags;
+use crate::toc::{self, TocEntry};
 use crate::svg::{self, SvgDocument, SvgElement, PathCommand};
 
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
+const PAGE_WIDTH_PT: f32 = 595.0;   // A4 width in points
 const PAGE_HEIGHT_PT: f32 = 842.0;  // A4 height in points
 const PT_TO_MM: f32 = 0.352_777_78;
+/// Most layout passes spent settling the page numbers of a table of contents
+const TOC_PASSES: usize = 4;
 
 // ============================================================================
 // PAGE STRUCTURE FOR PAGINATION
@@ -22,6 +36,8 @@ const PT_TO_MM: f32 = 0.352_777_78;
 struct PageContent {
     /// The layout boxes to render on this page
     children: Vec<LayoutBox>,
+    /// Fixed boxes, repeated on every page
+    fixed: Vec<LayoutBox>,
     /// Page style (for background, borders, etc.)
     style: Style,
     /// Page dimensions
@@ -38,34 +54,96 @@ struct PageContent {
 // PUBLIC API
 // ============================================================================
 
-pub fn from_layout(root: &JsonNode) -> Vec<u8> {
-    // 1) Build layout tree
-    let mut root_box = build_layout(root);
+/// Why a layout could not be rendered
+#[derive(Debug)]
+pub enum RenderError {
+    /// The document can't meet the requested PDF/A conformance
+    Conformance(String),
+    /// The e-invoice to embed is unusable
+    Invoice(String),
+    /// The drawn pages could not be turned into the final PDF
+    Finish(String),
+}
 
-    // 2) Measure pass
-    measure_layout(&mut root_box);
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Conformance(msg) => write!(f, "PDF/A: {}", msg),
+            RenderError::Invoice(msg) => write!(f, "Invoice: {}", msg),
+            RenderError::Finish(msg) => write!(f, "Failed to finish the PDF: {}", msg),
+        }
+    }
+}
 
-    // 3) Place pass (start at top-left with margin)
-    place_layout(&mut root_box, 0.0, PAGE_HEIGHT_PT);
+pub fn from_layout(root: &JsonNode, document: &DocumentOptions) -> Result<Vec<u8>, RenderError> {
+    // The root's language is the document's, unless the metadata gives one
+    let mut document = Cow::Borrowed(document);
+    if let Some(ref lang) = root.lang {
+        if document.metadata.as_ref().and_then(|m| m.language.as_ref()).is_none() {
+            document.to_mut().metadata.get_or_insert_with(Metadata::default).language = Some(lang.clone());
+        }
+    }
+    let document = document.as_ref();
 
-    // 4) Paginate - split content across pages if needed
-    let pages = paginate(&root_box);
+    if let Some(ref invoice) = document.invoice {
+        pdf_attachments::check_invoice(invoice, document.conformance).map_err(RenderError::Invoice)?;
+    }
+    pdf_attachments::check_conformance(root, document).map_err(RenderError::Conformance)?;
+    pdf_forms::check_conformance(root, document).map_err(RenderError::Conformance)?;
+
+    // 1-4) Lay out and paginate (repeatedly, when a table of contents needs page numbers)
+    let pages = if toc::has_toc(root) {
+        layout_with_toc(root)
+    } else {
+        layout_pages(root)
+    };
+
+    let watermark = document.watermark.as_ref().and_then(watermark_layout);
+
+    // PDF/A embeds its fonts, so all the text must be in them
+    let font_files = match document.conformance {
+        Some(_) => {
+            let files = FontFiles::load().map_err(RenderError::Conformance)?;
+            let mut texts = Vec::new();
+            for page in &pages {
+                for layout in page.children.iter().chain(&page.fixed) {
+                    drawn_text(layout, &mut texts);
+                }
+            }
+            if let Some((ref mark, _)) = watermark {
+                drawn_text(mark, &mut texts);
+            }
+            files.check_coverage(&texts).map_err(RenderError::Conformance)?;
+            Some(files)
+        }
+        None => None,
+    };
 
     // 5) Create PDF document
+    let title = document.metadata.as_ref().and_then(|m| m.title.as_deref()).unwrap_or("PDF Document");
     let (doc, page1, layer1) =
-        PdfDocument::new("PDF Document", Mm(210.0), Mm(297.0), "Layer 1");
+        PdfDocument::new(title, Mm(210.0), Mm(297.0), "Layer 1");
 
     // Load fonts
-    let font_regular = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
-    let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
-    let font_italic = doc.add_builtin_font(BuiltinFont::HelveticaOblique).unwrap();
-    let font_bold_italic = doc.add_builtin_font(BuiltinFont::HelveticaBoldOblique).unwrap();
-
-    let fonts = Fonts {
-        regular: font_regular,
-        bold: font_bold,
-        italic: font_italic,
-        bold_italic: font_bold_italic,
+    let fonts = match font_files {
+        Some(ref files) => {
+            let embed = |bytes: &[u8]| {
+                doc.add_external_font(Cursor::new(bytes))
+                    .map_err(|e| RenderError::Conformance(format!("Failed to embed font: {}", e)))
+            };
+            Fonts {
+                regular: embed(&files.regular)?,
+                bold: embed(&files.bold)?,
+                italic: embed(&files.italic)?,
+                bold_italic: embed(&files.bold_italic)?,
+            }
+        }
+        None => Fonts {
+            regular: doc.add_builtin_font(BuiltinFont::Helvetica).unwrap(),
+            bold: doc.add_builtin_font(BuiltinFont::HelveticaBold).unwrap(),
+            italic: doc.add_builtin_font(BuiltinFont::HelveticaOblique).unwrap(),
+            bold_italic: doc.add_builtin_font(BuiltinFont::HelveticaBoldOblique).unwrap(),
+        },
     };
 
     // 6) Draw each page
@@ -78,7 +156,7 @@ pub fn from_layout(root: &JsonNode) -> Vec<u8> {
             doc.get_page(new_page).get_layer(new_layer)
         };
 
-        draw_page(&page_content, &layer, &fonts, &doc);
+        draw_page(&page_content, watermark.as_ref(), &layer, &fonts, &doc);
     }
 
     // 7) Export to bytes
@@ -89,7 +167,55 @@ pub fn from_layout(root: &JsonNode) -> Vec<u8> {
         doc.save(&mut writer).unwrap();
     }
 
-    buf
+    // 8) Turn placeholders into real page resources and annotations, and apply document
+    // settings. The pages refer to resources only this step adds, so there is no fallback.
+    pdf_resources::finish(&buf, document).map_err(RenderError::Finish)
+}
+
+fn layout_pages(root: &JsonNode) -> Vec<PageContent> {
+    // 1) Build layout tree
+    let mut root_box = build_layout(root);
+
+    // 2) Measure pass
+    measure_layout(&mut root_box);
+
+    // 3) Place pass (start at top-left with margin)
+    place_layout(&mut root_box, 0.0, PAGE_HEIGHT_PT);
+
+    // 4) Paginate - split content across pages if needed
+    paginate(&root_box)
+}
+
+/// Lay out until the page numbers in the table of contents match the pages
+/// their nodes land on: filling them in can move the nodes
+fn layout_with_toc(root: &JsonNode) -> Vec<PageContent> {
+    let mut root = root.clone();
+    let entries = toc::collect_entries(&mut root);
+    let mut numbers = vec![None; entries.len()];
+
+    let mut pages = Vec::new();
+    for _ in 0..TOC_PASSES {
+        pages = layout_pages(&toc::fill(&root, &entries, &numbers));
+        let found = entry_pages(&pages, &entries);
+        if found == numbers {
+            break;
+        }
+        numbers = found;
+    }
+    pages
+}
+
+/// The page number (from 1) each entry's node landed on
+fn entry_pages(pages: &[PageContent], entries: &[TocEntry]) -> Vec<Option<usize>> {
+    fn find(layout: &LayoutBox, id: &str) -> bool {
+        layout.node.id.as_deref() == Some(id)
+            || (!layout.is_inline_context() && layout.children.iter().any(|child| find(child, id)))
+    }
+
+    entries.iter()
+        .map(|entry| pages.iter().position(|page| page.children.iter().any(|child| find(child, &entry.id))))
+        .map(|index| index.map(|i| i + 1))
+        .collect()
 }
 
 // ============================================================================
@@ -116,6 +242,14 @@ fn find_content_page(root: &LayoutBox) -> &LayoutBox {
 fn paginate(root: &LayoutBox) -> Vec<PageContent> {
     let mut pages: Vec<PageContent> = Vec::new();
 
+    // Fixed boxes are placed against the page box once and stamped onto every page
+    let mut fixed = Vec::new();
+    collect_fixed(root, &mut fixed);
+    for layout in &mut fixed {
+        place_fixed(layout, PAGE_WIDTH_PT, PAGE_HEIGHT_PT);
+    }
+    fixed.sort_by_key(|layout| layout.z_index().unwrap_or(0));
+
     // Find the actual content container (handle nested pages)
     let page = find_content_page(root);
 
@@ -124,6 +258,7 @@ fn paginate(root: &LayoutBox) -> Vec<PageContent> {
         // Non-page root: just render as single page
         pages.push(PageContent {
             children: vec![page.clone()],
+            fixed,
             style: Style::default(),
             width: page.width,
             height: page.height,
@@ -195,6 +330,7 @@ fn paginate(root: &LayoutBox) -> Vec<PageContent> {
         
         pages.push(PageContent {
             children: children_for_page,
+            fixed: fixed.clone(),
             style: page.node.style.clone(),
             width: page_width,
             height: page_height,
@@ -208,6 +344,17 @@ fn paginate(root: &LayoutBox) -> Vec<PageContent> {
     pages
 }
 
+/// Clone the fixed boxes in the tree (not looking into inline text or fixed boxes)
+fn collect_fixed(layout: &LayoutBox, out: &mut Vec<LayoutBox>) {
+    for child in &layout.children {
+        if child.is_fixed() {
+            out.push(child.clone());
+        } else if !child.is_inline_context() {
+            collect_fixed(child, out);
+        }
+    }
+}
+
 /// Reposition a layout box and all its children by an offset
 fn reposition_layout(layout: &mut LayoutBox, x_offset: f32, y_offset: f32) {
     layout.x += x_offset;
@@ -218,17 +365,82 @@ fn reposition_layout(layout: &mut LayoutBox, x_offset: f32, y_offset: f32) {
 }
 
 /// Draw a single page
-fn draw_page(page: &PageContent, layer: &PdfLayerReference, fonts: &Fonts, doc: &PdfDocumentReference) {
+fn draw_page(
+    page: &PageContent,
+    watermark: Option<&(LayoutBox, WatermarkLayer)>,
+    layer: &PdfLayerReference,
+    fonts: &Fonts,
+    doc: &PdfDocumentReference,
+) {
     // Draw page background if any
+    layer.add_operation(pdf_tags::begin_artifact());
     if let Some(ref bg) = page.style.background_color {
         set_fill_color(layer, bg);
         draw_rect(layer, 0.0, 0.0, page.width, page.height, true, false);
     }
 
-    // Draw all children on this page
-    for child in &page.children {
-        draw_layout(child, layer, fonts, doc);
+    if let Some((mark, WatermarkLayer::Under)) = watermark {
+        draw_layout(mark, layer, fonts, doc);
+    }
+    layer.add_operation(pdf_tags::end());
+
+    // Draw all children on this page; the page is the root stacking context, which
+    // fixed boxes join: under the content with a negative zIndex, over it otherwise
+    let (below, above): (Vec<&LayoutBox>, Vec<&LayoutBox>) =
+        page.fixed.iter().partition(|layout| layout.z_index().unwrap_or(0) < 0);
+    // Fixed boxes repeat on every page (headers, footers), so they aren't content
+    layer.add_operation(pdf_tags::begin_artifact());
+    for layout in below {
+        draw_layout(layout, layer, fonts, doc);
+    }
+    layer.add_operation(pdf_tags::end());
+    draw_children(&page.children, true, layer, fonts, doc);
+    layer.add_operation(pdf_tags::begin_artifact());
+    for layout in above {
+        draw_layout(layout, layer, fonts, doc);
+    }
+
+    if let Some((mark, WatermarkLayer::Over)) = watermark {
+        draw_layout(mark, layer, fonts, doc);
     }
+    layer.add_operation(pdf_tags::end());
+}
+
+/// Lay out a watermark as a node centered on the page, faded and rotated
+fn watermark_layout(watermark: &Watermark) -> Option<(LayoutBox, WatermarkLayer)> {
+    let mut node = if let Some(ref text) = watermark.text {
+        let mut node = JsonNode::new(NodeType::Text);
+        node.text = Some(text.clone());
+        node.style.font_size = Some(watermark.font_size.unwrap_or(96.0));
+        node.style.font_weight = Some(watermark.font_weight.clone().unwrap_or(FontWeight::Bold));
+        node.style.font_style = watermark.font_style.clone();
+        node.style.color = Some(watermark.color.clone().unwrap_or(Color { r: 128, g: 128, b: 128, a: 1.0 }));
+        node.style.text_align = Some(TextAlign::Center);
+        node
+    } else if let Some(ref src) = watermark.image {
+        let mut node = JsonNode::new(NodeType::Image);
+        node.src = Some(src.clone());
+        node
+    } else if let Some(ref svg) = watermark.svg {
+        let mut node = JsonNode::new(NodeType::Svg);
+        node.src = Some(svg.clone());
+        node
+    } else {
+        eprintln!("Watermark has no text, image or svg");
+        return None;
+    };
+    node.style.width = watermark.width.map(Dimension::Pt);
+    node.style.height = watermark.height.map(Dimension::Pt);
+    node.style.opacity = Some(watermark.opacity.unwrap_or(0.2));
+    node.role = Some(Role::Artifact);
+    node.style.transform = Some(vec![TransformFunction::Rotate(watermark.rotation.unwrap_or(-45.0))]);
+
+    let mut layout = build_layout(&node);
+    measure_layout(&mut layout);
+    let x = (PAGE_WIDTH_PT - layout.width) / 2.0;
+    let y = (PAGE_HEIGHT_PT + layout.height) / 2.0;
+    place_layout(&mut layout, x, y);
+    Some((layout, watermark.layer.clone().unwrap_or(WatermarkLayer::Over)))
 }
 
 // ============================================================================
@@ -253,6 +465,34 @@ impl Fonts {
     }
 }
 
+/// Every string drawn for the box and its children, with the bold and italic
+/// flags of the font it's drawn in
+fn drawn_text<'a>(layout: &'a LayoutBox, out: &mut Vec<(bool, bool, &'a str)>) {
+    let (bold, italic) = (layout.is_bold(), layout.is_italic());
+    out.extend(layout.lines.iter().map(|line| (bold, italic, line.as_str())));
+    for line in &layout.inline_lines {
+        for frag in &line.fragments {
+            if let (Some(text), Some(child)) = (&frag.text, layout.children.get(frag.child)) {
+                out.push((child.is_bold(), child.is_italic(), text.as_str()));
+            }
+        }
+    }
+    if let Some(ref marker) = layout.marker {
+        out.push((marker.bold, false, marker.text.as_str()));
+    }
+    // Table of contents leaders are dots in the title's font
+    if layout.node.node_type == NodeType::Toc {
+        for entry in &layout.children {
+            if let Some(title) = entry.children.first() {
+                out.push((title.is_bold(), title.is_italic(), "."));
+            }
+        }
+    }
+    for child in &layout.children {
+        drawn_text(child, out);
+    }
+}
+
 // ============================================================================
 // TEXT MEASUREMENT
 // ============================================================================
@@ -272,80 +512,525 @@ fn line_height(font_size: f32, line_height_mult: f32) -> f32 {
 // ============================================================================
 
 fn draw_layout(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts, doc: &PdfDocumentReference) {
-    // 1) Draw background (if any)
-    draw_background(layout, layer);
+    // Opacity applies to the box and its subtree as one transparency group
+    let opacity = layout.node.style.opacity();
+    if opacity <= 0.0 {
+        return;
+    }
+
+    // Structure element (or artifact) around everything the node draws, for tagged
+    // PDFs. The content of an opacity group is painted at once, so the group needs one too.
+    let grouped = opacity < 1.0;
+    let semantics = &layout.semantics;
+    let artifact = semantics.role == Some(Role::Artifact);
+    let element = pdf_tags::structure_type(layout).or(if grouped && !artifact { Some("Div") } else { None });
+    if artifact {
+        layer.add_operation(pdf_tags::begin_artifact());
+    } else if let Some(kind) = element {
+        let alt = if kind == "Figure" { semantics.alt.as_deref() } else { None };
+        layer.add_operation(pdf_tags::begin_element(kind, alt, semantics.lang.as_deref()));
+    }
+
+    if grouped {
+        layer.add_operation(pdf_resources::begin_opacity_group(opacity));
+    }
+
+    // Transforms move the drawing, not the layout
+    let transform = transform_matrix(layout);
+    if let Some(matrix) = transform {
+        layer.save_graphics_state();
+        layer.set_ctm(CurTransMat::Raw(matrix));
+    }
+    if let Some(ref id) = layout.node.id {
+        layer.add_operation(pdf_resources::anchor(id, layout.x, layout.y));
+    }
+    if let Some((title, level)) = layout.node.outline_entry() {
+        layer.add_operation(pdf_resources::bookmark(&title, level, layout.x, layout.y));
+    }
+
+    // 1) Draw background (if any), between the outer and inset shadows.
+    // Form field widgets draw their own background and border.
+    let field = layout.node.node_type.is_form_field();
+    layer.add_operation(pdf_tags::begin_artifact());
+    let shadow = layout.node.style.box_shadow.as_ref();
+    if let Some(shadow) = shadow.filter(|s| !s.inset) {
+        draw_box_shadow(layout, layer, shadow);
+    }
+    if !field {
+        draw_background(layout, layer);
+    }
+    if let Some(shadow) = shadow.filter(|s| s.inset) {
+        draw_box_shadow(layout, layer, shadow);
+    }
 
     // 2) Draw border (if any)
-    draw_border(layout, layer);
+    if !field {
+        draw_border(layout, layer);
+    }
+    layer.add_operation(pdf_tags::end());
+
+    // 3) Draw content, clipped to the box for overflow: hidden
+    let clipped = layout.clips_overflow();
+    if clipped {
+        layer.save_graphics_state();
+        clip_to_box(layout, layer);
+    }
 
-    // 3) Draw content
     match layout.node.node_type {
+        NodeType::Text if layout.is_inline_context() => draw_inline(layout, layer, fonts, doc),
         NodeType::Text => draw_text(layout, layer, fonts),
         NodeType::Image => draw_image(layout, layer, doc),
         NodeType::Svg => draw_svg(layout, layer),
+        NodeType::FileAttachment => draw_file_attachment(layout, layer),
+        ref t if t.is_form_field() => draw_form_field(layout, layer, fonts),
+        NodeType::ListItem => {
+            let alpha = layout.marker.as_ref().map_or(1.0, |m| m.color.a);
+            layer.add_operation(pdf_tags::begin_element("Lbl", None, None));
+            with_alpha(layer, alpha, alpha, || draw_list_marker(layout, layer, fonts));
+            layer.add_operation(pdf_tags::end());
+            layer.add_operation(pdf_tags::begin_element("LBody", None, None));
+            draw_children(&layout.children, stacks_descendants(layout), layer, fonts, doc);
+            layer.add_operation(pdf_tags::end());
+        }
+        NodeType::Toc => {
+            draw_children(&layout.children, stacks_descendants(layout), layer, fonts, doc);
+            layer.add_operation(pdf_tags::begin_artifact());
+            for entry in &layout.children {
+                draw_toc_leader(entry, layer, fonts);
+            }
+            layer.add_operation(pdf_tags::end());
+        }
         _ => {
             // Container: draw children
-            for child in &layout.children {
-                draw_layout(child, layer, fonts, doc);
-            }
+            draw_children(&layout.children, stacks_descendants(layout), layer, fonts, doc);
+        }
+    }
+
+    if clipped {
+        layer.restore_graphics_state();
+    }
+
+    // 4) Link over the box (text links each of its lines or fragments instead)
+    if let Some(ref href) = layout.node.href {
+        if layout.node.node_type != NodeType::Text {
+            let bottom = layout.y - layout.height;
+            layer.add_operation(pdf_resources::link(layout.x, bottom, layout.width, layout.height, href));
         }
     }
+
+    if transform.is_some() {
+        layer.restore_graphics_state();
+    }
+    if grouped {
+        layer.add_operation(pdf_resources::end_opacity_group());
+    }
+    if artifact || element.is_some() {
+        layer.add_operation(pdf_tags::end());
+    }
 }
 
-fn draw_background(layout: &LayoutBox, layer: &PdfLayerReference) {
-    if let Some(ref bg) = layout.node.style.background_color {
-        let opacity = layout.node.style.opacity();
-        if bg.a <= 0.0 || opacity <= 0.0 {
-            return; // Transparent
+/// Draw children in paint order
+fn draw_children(
+    children: &[LayoutBox],
+    context: bool,
+    layer: &PdfLayerReference,
+    fonts: &Fonts,
+    doc: &PdfDocumentReference,
+) {
+    for child in paint_order(children, context) {
+        draw_layout(child, layer, fonts, doc);
+    }
+}
+
+/// The boxes to paint for `children`, in order: like CSS, positioned children paint
+/// over their in-flow siblings. Boxes with a zIndex are painted by their stacking
+/// context (`context` is true when the parent is one), below its content when
+/// negative, above it otherwise, in zIndex then document order.
+fn paint_order(children: &[LayoutBox], context: bool) -> Vec<&LayoutBox> {
+    let mut stacked = Vec::new();
+    if context {
+        collect_stacked(children, &mut stacked);
+        stacked.sort_by_key(|child| child.z_index());
+    }
+
+    let mut order: Vec<&LayoutBox> = stacked.iter().copied().filter(|child| child.z_index() < Some(0)).collect();
+    // Fixed boxes are drawn by the page
+    let children = children.iter().filter(|c| !c.is_fixed());
+    for positioned in [false, true] {
+        order.extend(children.clone().filter(|c| c.z_index().is_none() && c.is_positioned() == positioned));
+    }
+    order.extend(stacked.iter().copied().filter(|child| child.z_index() >= Some(0)));
+    order
+}
+
+/// The zIndex boxes among `children` and their descendants, stopping at boxes
+/// that stack their own
+fn collect_stacked<'a>(children: &'a [LayoutBox], out: &mut Vec<&'a LayoutBox>) {
+    for child in children {
+        if child.is_fixed() {
+            continue;
+        } else if child.z_index().is_some() {
+            out.push(child);
+        } else if !stacks_descendants(child)
+            && !matches!(child.node.node_type, NodeType::Text | NodeType::Image | NodeType::Svg)
+        {
+            collect_stacked(&child.children, out);
         }
+    }
+}
 
-        let x = layout.x;
-        let y = layout.y - layout.height; // Bottom-left corner
-        let w = layout.width;
-        let h = layout.height;
-        let (r_tl, r_tr, r_br, r_bl) = layout.node.style.border_radii();
-
-        // Apply opacity to background color
-        let bg_with_opacity = Color {
-            r: bg.r,
-            g: bg.g,
-            b: bg.b,
-            a: bg.a * opacity,
+/// Whether the box paints the zIndex boxes inside it: stacking contexts do, and
+/// so do boxes drawn as a structure element or artifact (a list item, a table
+/// cell), which keeps the lifted boxes' content inside the element
+fn stacks_descendants(layout: &LayoutBox) -> bool {
+    layout.forms_stacking_context()
+        || layout.semantics.role == Some(Role::Artifact)
+        || pdf_tags::structure_type(layout).is_some()
+}
+
+/// The node's transform as a PDF matrix, applied around its transform origin
+fn transform_matrix(layout: &LayoutBox) -> Option<[f32; 6]> {
+    let functions = layout.node.style.transform.as_ref().filter(|f| !f.is_empty())?;
+    let (w, h) = (layout.width, layout.height);
+
+    // Compose in CSS space (y down, around the origin); the last function applies first
+    let mut m = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    for function in functions {
+        let next = match function {
+            TransformFunction::Rotate(deg) => {
+                let (sin, cos) = deg.to_radians().sin_cos();
+                [cos, sin, -sin, cos, 0.0, 0.0]
+            }
+            TransformFunction::Scale(sx, sy) => [*sx, 0.0, 0.0, *sy, 0.0, 0.0],
+            TransformFunction::Translate(tx, ty) => [1.0, 0.0, 0.0, 1.0, tx.resolve(w), ty.resolve(h)],
+            TransformFunction::Skew(ax, ay) => [1.0, ay.to_radians().tan(), ax.to_radians().tan(), 1.0, 0.0, 0.0],
+            TransformFunction::Matrix(matrix) => *matrix,
         };
-        set_fill_color(layer, &bg_with_opacity);
+        m = [
+            m[0] * next[0] + m[2] * next[1],
+            m[1] * next[0] + m[3] * next[1],
+            m[0] * next[2] + m[2] * next[3],
+            m[1] * next[2] + m[3] * next[3],
+            m[0] * next[4] + m[2] * next[5] + m[4],
+            m[1] * next[4] + m[3] * next[5] + m[5],
+        ];
+    }
 
-        if r_tl > 0.0 || r_tr > 0.0 || r_br > 0.0 || r_bl > 0.0 {
-            draw_rounded_rect_corners(layer, x, y, w, h, r_tl, r_tr, r_br, r_bl, true, false);
-        } else {
-            draw_rect(layer, x, y, w, h, true, false);
-        }
+    // Flip y into PDF space, then move the origin from (0, 0) to the transform origin
+    let [a, b, c, d, e, f] = [m[0], -m[1], -m[2], m[3], m[4], -m[5]];
+    let origin = layout.node.style.transform_origin.clone().unwrap_or_default();
+    let ox = layout.x + origin.x.resolve(w);
+    let oy = layout.y - origin.y.resolve(h);
+    Some([a, b, c, d, e + ox - a * ox - c * oy, f + oy - b * ox - d * oy])
+}
+
+/// Run `draw` with constant fill and stroke alpha (PDF colors have no alpha channel)
+fn with_alpha(layer: &PdfLayerReference, fill: f32, stroke: f32, draw: impl FnOnce()) {
+    if fill >= 1.0 && stroke >= 1.0 {
+        draw();
+        return;
     }
+    layer.save_graphics_state();
+    layer.add_operation(pdf_resources::set_alpha_state(fill, stroke));
+    draw();
+    layer.restore_graphics_state();
 }
 
-fn draw_border(layout: &LayoutBox, layer: &PdfLayerReference) {
-    let border_width = layout.node.style.border_width();
-    let border_color = layout.node.style.border_color();
+/// Intersect the clipping path with the inside of the box's border (following its radii)
+fn clip_to_box(layout: &LayoutBox, layer: &PdfLayerReference) {
+    clip_path(layer, box_clip_points(layout));
+}
+
+/// The outline of the inside of the box's border
+fn box_clip_points(layout: &LayoutBox) -> Vec<(Point, bool)> {
+    // Borders are stroked on the box edge, so their inner half lies inside the box
+    let inset = layout.node.style.border_width() / 2.0;
+    let (r_tl, r_tr, r_br, r_bl) = layout.node.style.border_radii();
+    let inner = |r: f32| (r - inset).max(0.0);
+
+    rounded_rect_points(
+        layout.x + inset,
+        layout.y - layout.height + inset,
+        (layout.width - 2.0 * inset).max(0.0),
+        (layout.height - 2.0 * inset).max(0.0),
+        (inner(r_tl), inner(r_tr), inner(r_br), inner(r_bl)),
+    )
+}
+
+fn clip_path(layer: &PdfLayerReference, points: Vec<(Point, bool)>) {
+    layer.add_polygon(Polygon {
+        rings: vec![points],
+        mode: PaintMode::Clip,
+        winding_order: WindingOrder::NonZero,
+    });
+}
+
+fn draw_background(layout: &LayoutBox, layer: &PdfLayerReference) {
+    let x = layout.x;
+    let y = layout.y - layout.height; // Bottom-left corner
+    let w = layout.width;
+    let h = layout.height;
     let (r_tl, r_tr, r_br, r_bl) = layout.node.style.border_radii();
 
-    // Check if we have any border
-    if border_width <= 0.0 {
+    if let Some(ref bg) = layout.node.style.background_color {
+        if bg.a > 0.0 {
+            set_fill_color(layer, bg);
+            with_alpha(layer, bg.a, 1.0, || {
+                if r_tl > 0.0 || r_tr > 0.0 || r_br > 0.0 || r_bl > 0.0 {
+                    draw_rounded_rect_corners(layer, x, y, w, h, r_tl, r_tr, r_br, r_bl, true, false);
+                } else {
+                    draw_rect(layer, x, y, w, h, true, false);
+                }
+            });
+        }
+    }
+
+    if let Some(ref gradient) = layout.node.style.background_image {
+        let stops = gradient.resolved_stops();
+        if stops.is_empty() || w <= 0.0 || h <= 0.0 {
+            return;
+        }
+        layer.save_graphics_state();
+        clip_path(layer, rounded_rect_points(x, y, w, h, (r_tl, r_tr, r_br, r_bl)));
+        layer.add_operation(pdf_resources::paint_shading(&gradient_shading(gradient, stops, x, y, w, h)));
+        layer.restore_graphics_state();
+    }
+}
+
+/// Number of translucent layers a blurred shadow is built from
+const SHADOW_LAYERS: usize = 8;
+
+/// Draw a box shadow: outer shadows only show outside the box, inset ones only inside.
+/// Blur is approximated by stacking progressively larger translucent shapes.
+fn draw_box_shadow(layout: &LayoutBox, layer: &PdfLayerReference, shadow: &BoxShadow) {
+    let color = shadow.color.clone().unwrap_or_else(Color::black);
+    if color.a <= 0.0 || layout.width <= 0.0 || layout.height <= 0.0 {
         return;
     }
 
-    let color = border_color.unwrap_or_else(Color::black);
-    set_stroke_color(layer, &color);
-    layer.set_outline_thickness(border_width);
+    let x = layout.x;
+    let y = layout.y - layout.height;
+    let w = layout.width;
+    let h = layout.height;
+    let (r_tl, r_tr, r_br, r_bl) = layout.node.style.border_radii();
+    let blur = shadow.blur.max(0.0);
+    let layers = if blur > 0.0 { SHADOW_LAYERS } else { 1 };
+
+    // Where all layers overlap they add up to the color's alpha
+    let alpha = 1.0 - (1.0 - color.a.min(1.0)).powf(1.0 / layers as f32);
+    // How far layer i grows the box, from the outer edge of the blur inwards
+    let grow = |i: usize| shadow.spread + blur / 2.0 - blur * (i as f32 + 0.5) / layers as f32;
+    // The box moved by the offset (positive y is down) and grown by d
+    let shape = |d: f32| {
+        let radius = |r: f32| if r > 0.0 || blur > 0.0 { (r + d).max(0.0) } else { 0.0 };
+        rounded_rect_points(
+            x + shadow.offset_x - d,
+            y - shadow.offset_y - d,
+            (w + 2.0 * d).max(0.0),
+            (h + 2.0 * d).max(0.0),
+            (radius(r_tl), radius(r_tr), radius(r_br), radius(r_bl)),
+        )
+    };
+    // A rectangle around everything the shadow can reach
+    let reach = shadow.offset_x.abs() + shadow.offset_y.abs() + shadow.spread.abs() + blur + 1.0;
+    let frame = rounded_rect_points(x - reach, y - reach, w + 2.0 * reach, h + 2.0 * reach, (0.0, 0.0, 0.0, 0.0));
+
+    layer.save_graphics_state();
+    set_fill_color(layer, &color);
+    layer.add_operation(pdf_resources::set_alpha_state(alpha, 1.0));
+    if shadow.inset {
+        clip_to_box(layout, layer);
+        for i in 0..layers {
+            fill_even_odd(layer, vec![frame.clone(), shape(-grow(i))]);
+        }
+    } else {
+        layer.add_polygon(Polygon {
+            rings: vec![frame, rounded_rect_points(x, y, w, h, (r_tl, r_tr, r_br, r_bl))],
+            mode: PaintMode::Clip,
+            winding_order: WindingOrder::EvenOdd,
+        });
+        for i in 0..layers {
+            fill_even_odd(layer, vec![shape(grow(i))]);
+        }
+    }
+    layer.restore_graphics_state();
+}
+
+fn fill_even_odd(layer: &PdfLayerReference, rings: Vec<Vec<(Point, bool)>>) {
+    layer.add_polygon(Polygon {
+        rings,
+        mode: PaintMode::Fill,
+        winding_order: WindingOrder::EvenOdd,
+    });
+}
+
+/// Shading geometry for a gradient filling the box at (x, y) (bottom-left), following CSS
+fn gradient_shading(gradient: &Gradient, stops: Vec<(f32, Color)>, x: f32, y: f32, w: f32, h: f32) -> pdf_resources::Shading {
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    let coords = match gradient.kind {
+        GradientType::LinearGradient => {
+            // CSS angles run clockwise from "to top"; the gradient line is long enough
+            // for the corners to get the first and last colors
+            let angle = gradient.angle.unwrap_or(180.0).to_radians();
+            let (dx, dy) = (angle.sin(), angle.cos());
+            let half = ((w * dx).abs() + (h * dy).abs()) / 2.0;
+            vec![cx - dx * half, cy - dy * half, cx + dx * half, cy + dy * half]
+        }
+        GradientType::RadialGradient => {
+            let radius = (w * w + h * h).sqrt() / 2.0;
+            vec![cx, cy, 0.0, cx, cy, radius]
+        }
+    };
+    pdf_resources::Shading {
+        radial: gradient.kind == GradientType::RadialGradient,
+        coords,
+        stops,
+    }
+}
+
+/// Draw the border: one path around the box when all sides match, so dashes run
+/// around the corners; otherwise each side (with half of each corner) on its own
+fn draw_border(layout: &LayoutBox, layer: &PdfLayerReference) {
+    let style = &layout.node.style;
+    let (w_t, w_r, w_b, w_l) = style.border_widths();
+    let (c_t, c_r, c_b, c_l) = style.border_colors();
+    let (s_t, s_r, s_b, s_l) = style.border_styles();
+    let sides = [
+        (BoxSide::Top, w_t, c_t.unwrap_or_else(Color::black), s_t),
+        (BoxSide::Right, w_r, c_r.unwrap_or_else(Color::black), s_r),
+        (BoxSide::Bottom, w_b, c_b.unwrap_or_else(Color::black), s_b),
+        (BoxSide::Left, w_l, c_l.unwrap_or_else(Color::black), s_l),
+    ];
 
     let x = layout.x;
     let y = layout.y - layout.height;
     let w = layout.width;
     let h = layout.height;
+    let radii = style.border_radii();
+    // The box grown by d (negative shrinks it), keeping the corners concentric
+    let grown = |d: f32| {
+        let (r_tl, r_tr, r_br, r_bl) = radii;
+        let r = |r: f32| if r > 0.0 { (r + d).max(0.0) } else { 0.0 };
+        (x - d, y - d, (w + 2.0 * d).max(0.0), (h + 2.0 * d).max(0.0), (r(r_tl), r(r_tr), r(r_br), r(r_bl)))
+    };
+
+    let (_, width, color, border_style) = &sides[0];
+    if sides.iter().all(|(_, sw, sc, ss)| sw == width && sc == color && ss == border_style) {
+        stroke_border(layer, *width, color, border_style, |d| {
+            let (x, y, w, h, radii) = grown(d);
+            (rounded_rect_points(x, y, w, h, radii), true)
+        });
+        return;
+    }
+
+    for (i, (side, width, color, border_style)) in sides.iter().enumerate() {
+        // Square corners are extended to cover the neighbouring sides' half widths
+        let before = sides[(i + 3) % 4].1 / 2.0;
+        let after = sides[(i + 1) % 4].1 / 2.0;
+        stroke_border(layer, *width, color, border_style, |d| {
+            let (x, y, w, h, radii) = grown(d);
+            let extend = ((before - d.abs()).max(0.0), (after - d.abs()).max(0.0));
+            (border_side_points(x, y, w, h, radii, side, extend), false)
+        });
+    }
+}
+
+/// Stroke a border path; `path(d)` gives the outline grown by `d` and whether it is closed
+fn stroke_border(
+    layer: &PdfLayerReference,
+    width: f32,
+    color: &Color,
+    style: &BorderStyle,
+    path: impl Fn(f32) -> (Vec<(Point, bool)>, bool),
+) {
+    if width <= 0.0 || *style == BorderStyle::None {
+        return;
+    }
+    set_stroke_color(layer, color);
+    with_alpha(layer, 1.0, color.a, || {
+        layer.save_graphics_state();
+        if *style == BorderStyle::Double {
+            // Two lines, each a third of the width, along the outer and inner edges
+            layer.set_outline_thickness(width / 3.0);
+            for d in [width / 3.0, -width / 3.0] {
+                let (points, is_closed) = path(d);
+                layer.add_line(Line { points, is_closed });
+            }
+        } else {
+            let (points, is_closed) = path(0.0);
+            layer.set_outline_thickness(width);
+            if let Some(pattern) = dash_pattern(style, width, path_length(&points, is_closed), is_closed) {
+                if *style == BorderStyle::Dotted {
+                    layer.set_line_cap_style(LineCapStyle::Round);
+                }
+                layer.add_operation(lopdf::content::Operation::new(
+                    "d",
+                    vec![
+                        lopdf::Object::Array(pattern.into_iter().map(lopdf::Object::Real).collect()),
+                        lopdf::Object::Integer(0),
+                    ],
+                ));
+            }
+            layer.add_line(Line { points, is_closed });
+        }
+        layer.restore_graphics_state();
+    });
+}
 
-    if r_tl > 0.0 || r_tr > 0.0 || r_br > 0.0 || r_bl > 0.0 {
-        draw_rounded_rect_corners(layer, x, y, w, h, r_tl, r_tr, r_br, r_bl, false, true);
+/// Dash and gap lengths for a border style, stretched so the pattern fits the path
+/// exactly: closed paths repeat it, open ones start and end with a dash (or dot)
+fn dash_pattern(style: &BorderStyle, width: f32, length: f32, closed: bool) -> Option<Vec<f32>> {
+    let (dash, gap) = match style {
+        BorderStyle::Dashed => (3.0 * width, 3.0 * width),
+        // Zero-length dashes with round caps are dots one width across
+        BorderStyle::Dotted => (0.0, 2.0 * width),
+        _ => return None,
+    };
+    if length <= 0.0 {
+        return None;
+    }
+    let scale = if closed {
+        length / ((length / (dash + gap)).round().max(1.0) * (dash + gap))
     } else {
-        draw_rect(layer, x, y, w, h, false, true);
+        // One more dash than gaps; dots need two to fill the ends
+        let count = ((length + gap) / (dash + gap)).round().max(if dash > 0.0 { 1.0 } else { 2.0 });
+        length / (count * dash + (count - 1.0) * gap)
+    };
+    Some(vec![dash * scale, gap * scale])
+}
+
+/// Length of a path as printpdf draws it: a point and the next both flagged
+/// start a cubic Bézier, which is measured along the curve, not its control points
+fn path_length(points: &[(Point, bool)], closed: bool) -> f32 {
+    let xy = |i: usize| (points[i].0.x.0, points[i].0.y.0);
+    let distance = |a: (f32, f32), b: (f32, f32)| ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+
+    let mut length = 0.0;
+    let mut i = 1;
+    while i < points.len() {
+        if points[i - 1].1 && points[i].1 && i + 2 < points.len() {
+            // Controls equal to each other make printpdf end at the last point instead
+            let (p0, p1, p2, p3) = match (xy(i - 1), xy(i), xy(i + 1), xy(i + 2)) {
+                (p0, p1, p2, p3) if p1 == p2 && p0 != p1 => (p0, p1, p3, p3),
+                points => points,
+            };
+            let at = |t: f32| {
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                (a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1)
+            };
+            let steps = 32;
+            length += (1..=steps).map(|k| distance(at((k - 1) as f32 / steps as f32), at(k as f32 / steps as f32))).sum::<f32>();
+            i += 3;
+        } else {
+            length += distance(xy(i - 1), xy(i));
+            i += 1;
+        }
     }
+    if closed && points.len() > 1 {
+        length += distance(xy(points.len() - 1), xy(0));
+    }
+    length
 }
 
 fn draw_line(layer: &PdfLayerReference, x1: f32, y1: f32, x2: f32, y2: f32) {
@@ -395,6 +1080,89 @@ fn draw_rounded_rect_corners(
     fill: bool,
     stroke: bool,
 ) {
+    let points = rounded_rect_points(x, y, w, h, (r_tl, r_tr, r_br, r_bl));
+
+    if fill {
+        let polygon = Polygon {
+            rings: vec![points.clone()],
+            mode: if stroke {
+                PaintMode::FillStroke
+            } else {
+                PaintMode::Fill
+            },
+            winding_order: WindingOrder::NonZero,
+        };
+        layer.add_polygon(polygon);
+    } else if stroke {
+        let line = Line { points, is_closed: true };
+        layer.add_line(line);
+    }
+}
+
+/// Which edge of a box a border side runs along
+enum BoxSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// One side of a rounded rectangle, running clockwise from the middle of the corner
+/// before it to the middle of the corner after it. Square corners are extended by
+/// (before, after) instead, so neighbouring sides meet without a notch.
+fn border_side_points(
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    radii: (f32, f32, f32, f32),
+    side: &BoxSide,
+    extend: (f32, f32),
+) -> Vec<(Point, bool)> {
+    let max_r = (w / 2.0).min(h / 2.0);
+    let (r_tl, r_tr, r_br, r_bl) = radii;
+    let (r_tl, r_tr, r_br, r_bl) = (r_tl.min(max_r), r_tr.min(max_r), r_br.min(max_r), r_bl.min(max_r));
+    let eighth = std::f32::consts::PI / 4.0;
+
+    // Each corner's arc center, radius, and the angle pointing at the middle of the arc
+    let top_left = (x + r_tl, y + h - r_tl, r_tl, 3.0 * eighth);
+    let top_right = (x + w - r_tr, y + h - r_tr, r_tr, eighth);
+    let bottom_right = (x + w - r_br, y + r_br, r_br, -eighth);
+    let bottom_left = (x + r_bl, y + r_bl, r_bl, -3.0 * eighth);
+    let (start, end, (dx, dy)) = match side {
+        BoxSide::Top => (top_left, top_right, (1.0, 0.0)),
+        BoxSide::Right => (top_right, bottom_right, (0.0, -1.0)),
+        BoxSide::Bottom => (bottom_right, bottom_left, (-1.0, 0.0)),
+        BoxSide::Left => (bottom_left, top_left, (0.0, 1.0)),
+    };
+
+    let point = |px: f32, py: f32| (Point::new(Mm(px * PT_TO_MM), Mm(py * PT_TO_MM)), false);
+    let arc = |points: &mut Vec<(Point, bool)>, (cx, cy, r, _): (f32, f32, f32, f32), from: f32, to: f32| {
+        let segments = 4;
+        for i in 0..=segments {
+            let angle = from + (to - from) * i as f32 / segments as f32;
+            points.push(point(cx + r * angle.cos(), cy + r * angle.sin()));
+        }
+    };
+
+    let mut points = Vec::new();
+    if start.2 > 0.0 {
+        arc(&mut points, start, start.3, start.3 - eighth);
+    } else {
+        points.push(point(start.0 - dx * extend.0, start.1 - dy * extend.0));
+    }
+    if end.2 > 0.0 {
+        arc(&mut points, end, end.3 + eighth, end.3);
+    } else {
+        points.push(point(end.0 + dx * extend.1, end.1 + dy * extend.1));
+    }
+    points
+}
+
+/// Outline of a rectangle with (top-left, top-right, bottom-right, bottom-left)
+/// corner radii; arcs are approximated by line segments
+fn rounded_rect_points(x: f32, y: f32, w: f32, h: f32, radii: (f32, f32, f32, f32)) -> Vec<(Point, bool)> {
+    let (r_tl, r_tr, r_br, r_bl) = radii;
     // Clamp radii to half the smallest dimension
     let max_r = (w / 2.0).min(h / 2.0);
     let r_tl = r_tl.min(max_r);
@@ -467,21 +1235,7 @@ fn draw_rounded_rect_corners(
         add_arc(&mut points, cx, cy, r_bl, pi, 3.0 * pi / 2.0);
     }
 
-    if fill {
-        let polygon = Polygon {
-            rings: vec![points.clone()],
-            mode: if stroke {
-                PaintMode::FillStroke
-            } else {
-                PaintMode::Fill
-            },
-            winding_order: WindingOrder::NonZero,
-        };
-        layer.add_polygon(polygon);
-    } else if stroke {
-        let line = Line { points, is_closed: true };
-        layer.add_line(line);
-    }
+    points
 }
 
 fn draw_text(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
@@ -497,11 +1251,8 @@ fn draw_text(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
     let font = fonts.get(is_bold, is_italic);
 
     // Text color
-    if let Some(ref color) = layout.node.style.color {
-        set_fill_color(layer, color);
-    } else {
-        set_fill_color(layer, &Color::black());
-    }
+    let color = layout.node.style.color.clone().unwrap_or_else(Color::black);
+    set_fill_color(layer, &color);
 
     let lines = if layout.lines.is_empty() {
         vec![layout.node.text.clone().unwrap_or_default()]
@@ -513,10 +1264,18 @@ fn draw_text(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
     
     // PDF text is drawn from the baseline, not the top.
     // We need to offset down from layout.y (which is the top of the text box)
-    // by approximately the font's ascent. For most fonts, ascent ≈ 0.8 * font_size.
-    let baseline_offset = size * 0.8;
+    // by the font's ascent, the same offset the layout uses for baseline alignment.
+    let baseline_offset = layout.ascent();
+
+    // A link gets one rectangle per line, so wrapped links don't cover the margins
+    let link_line = |i: usize, x: f32, width: f32| {
+        if let Some(ref href) = layout.node.href {
+            let bottom = layout.y - (i + 1) as f32 * line_height_px;
+            layer.add_operation(pdf_resources::link(x, bottom, width, line_height_px, href));
+        }
+    };
 
-    for (i, line) in lines.iter().enumerate() {
+    with_alpha(layer, color.a, 1.0, || for (i, line) in lines.iter().enumerate() {
         let y = layout.y - baseline_offset - (i as f32 * line_height_px);
 
         let x = match text_align {
@@ -536,15 +1295,158 @@ fn draw_text(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
                     layout.x
                 } else {
                     draw_justified_line(layer, font, line, size, layout.x, y, box_width, metrics);
+                    link_line(i, layout.x, box_width);
                     continue; // Skip normal drawing
                 }
             }
         };
 
         layer.use_text(line, size, Mm(x * PT_TO_MM), Mm(y * PT_TO_MM), font);
+        link_line(i, x, text_width(line, size, metrics));
+    });
+}
+
+/// Draw the lines of an inline formatting context: span text fragments and inline boxes
+fn draw_inline(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts, doc: &PdfDocumentReference) {
+    let mut anchored = vec![false; layout.children.len()];
+    for line in &layout.inline_lines {
+        for frag in &line.fragments {
+            let child = &layout.children[frag.child];
+            let text = match frag.text {
+                Some(ref t) => t,
+                None => {
+                    draw_layout(child, layer, fonts, doc);
+                    continue;
+                }
+            };
+
+            let size = child.font_size();
+            let metrics = child.font_metrics();
+            let x = layout.x + line.offset_x + frag.x;
+            let baseline = layout.y - frag.y;
+
+            // Span highlights and links cover the font's ascender to descender
+            let units = metrics.units_per_em as f32;
+            let bottom = baseline + metrics.descender as f32 / units * size;
+            let top = baseline + metrics.ascender as f32 / units * size;
+            if let Some(ref bg) = child.node.style.background_color {
+                if bg.a > 0.0 {
+                    set_fill_color(layer, bg);
+                    with_alpha(layer, bg.a, 1.0, || {
+                        draw_rect(layer, x, bottom, frag.width, top - bottom, true, false)
+                    });
+                }
+            }
+
+            // Text in another language than the paragraph is a Span of its own
+            let lang = child.semantics.lang.as_deref().filter(|&lang| Some(lang) != layout.semantics.lang.as_deref());
+            if lang.is_some() {
+                layer.add_operation(pdf_tags::begin_element("Span", None, lang));
+            }
+            let color = child.node.style.color.clone().unwrap_or_else(Color::black);
+            set_fill_color(layer, &color);
+            let font = fonts.get(child.is_bold(), child.is_italic());
+            with_alpha(layer, color.a, 1.0, || {
+                layer.use_text(text.as_str(), size, Mm(x * PT_TO_MM), Mm(baseline * PT_TO_MM), font)
+            });
+            if lang.is_some() {
+                layer.add_operation(pdf_tags::end());
+            }
+
+            // A span wrapped over several lines links each of its fragments
+            if let Some(ref href) = child.node.href {
+                layer.add_operation(pdf_resources::link(x, bottom, frag.width, top - bottom, href));
+            }
+            if let Some(ref id) = child.node.id {
+                if !anchored[frag.child] {
+                    anchored[frag.child] = true;
+                    layer.add_operation(pdf_resources::anchor(id, x, top));
+                }
+            }
+        }
     }
 }
 
+/// Fill the gap between a table of contents entry's title and page number with
+/// dots, on the baseline of the title's last line. Dots sit on a grid running
+/// from the right, so they line up from entry to entry.
+fn draw_toc_leader(entry: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
+    let [title, leader, _] = entry.children.as_slice() else {
+        return;
+    };
+    let size = title.font_size();
+    let metrics = title.font_metrics();
+    let step = text_width(". ", size, metrics);
+    let count = ((leader.width - step) / step).floor();
+    if count < 1.0 {
+        return;
+    }
+
+    let x = leader.x + leader.width - count * step;
+    let baseline = leader.y - title.ascent();
+    let color = title.node.style.color.clone().unwrap_or_else(Color::black);
+    set_fill_color(layer, &color);
+    let dots = ". ".repeat(count as usize);
+    let font = fonts.get(title.is_bold(), title.is_italic());
+    with_alpha(layer, color.a, 1.0, || {
+        layer.use_text(dots.trim_end(), size, Mm(x * PT_TO_MM), Mm(baseline * PT_TO_MM), font)
+    });
+}
+
+/// Draw a list item's marker right-aligned in its indent, on the first baseline
+fn draw_list_marker(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
+    let marker = match layout.marker {
+        Some(ref m) => m,
+        None => return,
+    };
+    let size = marker.font_size;
+    let baseline = layout.y - marker.baseline;
+    let right = layout.x + marker.right;
+
+    set_fill_color(layer, &marker.color);
+
+    if !marker.text.is_empty() {
+        let metrics = font_metrics::get_metrics(marker.bold, false);
+        let x = right - text_width(&marker.text, size, metrics);
+        let font = fonts.get(marker.bold, false);
+        layer.use_text(marker.text.as_str(), size, Mm(x * PT_TO_MM), Mm(baseline * PT_TO_MM), font);
+        return;
+    }
+
+    // Bullet shapes are drawn as vectors, centered on the x-height
+    let r = size * 0.17;
+    let cx = right - r;
+    let cy = baseline + size * 0.3;
+    match marker.style {
+        ListStyle::Disc => draw_circle(layer, cx, cy, r, PaintMode::Fill),
+        ListStyle::Circle => {
+            set_stroke_color(layer, &marker.color);
+            layer.set_outline_thickness(size * 0.06);
+            draw_circle(layer, cx, cy, r - size * 0.03, PaintMode::Stroke);
+        }
+        ListStyle::Square => draw_rect(layer, cx - r, cy - r, r * 2.0, r * 2.0, true, false),
+        _ => {}
+    }
+}
+
+fn draw_circle(layer: &PdfLayerReference, cx: f32, cy: f32, r: f32, mode: PaintMode) {
+    let segments = 24;
+    let points: Vec<(Point, bool)> = (0..segments)
+        .map(|i| {
+            let angle = 2.0 * std::f32::consts::PI * i as f32 / segments as f32;
+            let px = cx + r * angle.cos();
+            let py = cy + r * angle.sin();
+            (Point::new(Mm(px * PT_TO_MM), Mm(py * PT_TO_MM)), false)
+        })
+        .collect();
+
+    layer.add_polygon(Polygon {
+        rings: vec![points],
+        mode,
+        winding_order: WindingOrder::NonZero,
+    });
+}
+
 fn draw_justified_line(
     layer: &PdfLayerReference,
     font: &IndirectFontRef,
@@ -629,6 +1531,48 @@ fn draw_image(layout: &LayoutBox, layer: &PdfLayerReference, doc: &PdfDocumentRe
     }
 }
 
+/// The annotation and its paperclip icon are added when the document is finished
+fn draw_file_attachment(layout: &LayoutBox, layer: &PdfLayerReference) {
+    match layout.node.file {
+        Some(ref file) => {
+            let bottom = layout.y - layout.height;
+            layer.add_operation(pdf_resources::file_attachment(layout.x, bottom, layout.width, layout.height, file));
+        }
+        None => eprintln!("File attachment node missing file"),
+    }
+}
+
+/// Mark the widgets of a form field. A radio group has a button per option,
+/// one per line, with the option drawn as its label.
+fn draw_form_field(layout: &LayoutBox, layer: &PdfLayerReference, fonts: &Fonts) {
+    if layout.node.node_type != NodeType::RadioGroup {
+        let field = FormField::from_layout(layout, None);
+        let bottom = layout.y - layout.height;
+        layer.add_operation(pdf_resources::form_field(layout.x, bottom, layout.width, layout.height, &field));
+        return;
+    }
+
+    let size = layout.font_size();
+    let line = line_height(size, layout.line_height_multiplier());
+    let metrics = layout.font_metrics();
+    let font = fonts.get(layout.is_bold(), layout.is_italic());
+    let options = layout.node.options.as_deref().unwrap_or(&[]);
+    if options.is_empty() {
+        eprintln!("Radio group {:?} has no options", layout.node.name.as_deref().unwrap_or(""));
+    }
+    for (i, option) in options.iter().enumerate() {
+        let center = layout.y - (i as f32 + 0.5) * line;
+        let field = FormField::from_layout(layout, Some(i));
+        layer.add_operation(pdf_resources::form_field(layout.x, center - size / 2.0, size, size, &field));
+
+        let color = layout.node.style.color.clone().unwrap_or_else(Color::black);
+        set_fill_color(layer, &color);
+        let baseline = center - metrics.ascent(size) / 2.0;
+        let x = layout.x + size * RADIO_LABEL_OFFSET;
+        layer.use_text(option.as_str(), size, Mm(x * PT_TO_MM), Mm(baseline * PT_TO_MM), font);
+    }
+}
+
 fn draw_image_placeholder(layout: &LayoutBox, layer: &PdfLayerReference) {
     // Draw a light gray rectangle as placeholder
     let x = layout.x;
@@ -728,13 +1672,53 @@ fn add_image_to_pdf(
 
     let img_w = img_width as f32;
     let img_h = img_height as f32;
+    let object_fit = layout.node.style.object_fit.clone().unwrap_or_default();
+    let (render_w, render_h, offset_x, offset_y) = fit_image(&object_fit, container_w, container_h, img_w, img_h);
+
+    let final_x = container_x + offset_x;
+    let final_y = container_y + offset_y;
+
+    // Match layout units (points) to PDF image units by forcing 72 DPI so that
+    // 1px == 1pt; then scale to the requested render_w/render_h.
+    let dpi = 72.0;
+    let scale_x = render_w / img_w;
+    let scale_y = render_h / img_h;
+
+    // Cover and none can extend past the box: clip them to it
+    let clipped = matches!(object_fit, ObjectFit::Cover | ObjectFit::None);
+    if clipped {
+        layer.save_graphics_state();
+        let points = rounded_rect_points(container_x, container_y, container_w, container_h, (0.0, 0.0, 0.0, 0.0));
+        clip_path(layer, points);
+    }
+
+    // Add image to layer with transformation
+    image.add_to_layer(
+        layer.clone(),
+        printpdf::ImageTransform {
+            translate_x: Some(Mm(final_x * PT_TO_MM)),
+            translate_y: Some(Mm(final_y * PT_TO_MM)),
+            scale_x: Some(scale_x),
+            scale_y: Some(scale_y),
+            dpi: Some(dpi),
+            ..Default::default()
+        },
+    );
+
+    if clipped {
+        layer.restore_graphics_state();
+    }
+
+    Ok(())
+}
+
+/// The size of an image drawn in a box with `objectFit`, and its offset from the
+/// box's bottom-left corner
+fn fit_image(fit: &ObjectFit, container_w: f32, container_h: f32, img_w: f32, img_h: f32) -> (f32, f32, f32, f32) {
     let img_aspect = img_w / img_h;
     let container_aspect = container_w / container_h;
 
-    use crate::layout::ObjectFit;
-    let object_fit = layout.node.style.object_fit.clone().unwrap_or_default();
-
-    let (render_w, render_h, offset_x, offset_y) = match object_fit {
+    match fit {
         ObjectFit::Fill => {
             // Stretch to fill exactly (distorts aspect ratio)
             (container_w, container_h, 0.0, 0.0)
@@ -762,7 +1746,7 @@ fn add_image_to_pdf(
                 // Image is taller - fit to width, crop height
                 (container_w, container_w / img_aspect)
             };
-            // Center the image (overflow will be clipped)
+            // Center the image (the overflow is clipped below)
             let ox = (container_w - w) / 2.0;
             let oy = (container_h - h) / 2.0;
             (w, h, ox, oy)
@@ -792,31 +1776,7 @@ fn add_image_to_pdf(
             let oy = (container_h - h) / 2.0;
             (w, h, ox, oy)
         }
-    };
-
-    let final_x = container_x + offset_x;
-    let final_y = container_y + offset_y;
-
-    // Match layout units (points) to PDF image units by forcing 72 DPI so that
-    // 1px == 1pt; then scale to the requested render_w/render_h.
-    let dpi = 72.0;
-    let scale_x = render_w / img_w;
-    let scale_y = render_h / img_h;
-
-    // Add image to layer with transformation
-    image.add_to_layer(
-        layer.clone(),
-        printpdf::ImageTransform {
-            translate_x: Some(Mm(final_x * PT_TO_MM)),
-            translate_y: Some(Mm(final_y * PT_TO_MM)),
-            scale_x: Some(scale_x),
-            scale_y: Some(scale_y),
-            dpi: Some(dpi),
-            ..Default::default()
-        },
-    );
-
-    Ok(())
+    }
 }
 
 // ============================================================================
@@ -954,7 +1914,33 @@ fn render_svg_element(
     scale: f32,
     svg_height: f32,
 ) {
-    match element {
+    // Groups fade as a whole; shapes get their fill/stroke opacity as constant alpha
+    let style = match element {
+        SvgElement::Group(group) => {
+            let opacity = group.style.opacity.clamp(0.0, 1.0);
+            if opacity < 1.0 {
+                layer.add_operation(pdf_resources::begin_opacity_group(opacity));
+            }
+            for child in &group.elements {
+                render_svg_element(child, layer, offset_x, offset_y, scale, svg_height);
+            }
+            if opacity < 1.0 {
+                layer.add_operation(pdf_resources::end_opacity_group());
+            }
+            return;
+        }
+        SvgElement::Path(e) => &e.style,
+        SvgElement::Rect(e) => &e.style,
+        SvgElement::Circle(e) => &e.style,
+        SvgElement::Ellipse(e) => &e.style,
+        SvgElement::Line(e) => &e.style,
+        SvgElement::Polyline(e) => &e.style,
+        SvgElement::Polygon(e) => &e.style,
+    };
+    let fill_alpha = style.opacity * style.fill_opacity * style.fill.as_ref().map_or(1.0, |c| c.a);
+    let stroke_alpha = style.opacity * style.stroke_opacity * style.stroke.as_ref().map_or(1.0, |c| c.a);
+
+    with_alpha(layer, fill_alpha, stroke_alpha, || match element {
         SvgElement::Path(path) => {
             render_svg_path(path, layer, offset_x, offset_y, scale, svg_height);
         }
@@ -976,13 +1962,8 @@ fn render_svg_element(
         SvgElement::Polygon(polygon) => {
             render_svg_polygon(polygon, layer, offset_x, offset_y, scale, svg_height);
         }
-        SvgElement::Group(group) => {
-            // Apply group transform and render children
-            for child in &group.elements {
-                render_svg_element(child, layer, offset_x, offset_y, scale, svg_height);
-            }
-        }
-    }
+        SvgElement::Group(_) => {}
+    });
 }
 
 fn render_svg_path(
@@ -1414,3 +2395,479 @@ fn set_stroke_color(layer: &PdfLayerReference, color: &Color) {
         None,
     )));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(json: &str) -> LayoutBox {
+        let node: JsonNode = serde_json::from_str(json).unwrap();
+        let mut root = build_layout(&node);
+        measure_layout(&mut root);
+        place_layout(&mut root, 0.0, PAGE_HEIGHT_PT);
+        root
+    }
+
+    fn pt(points: &[(Point, bool)]) -> Vec<(f32, f32)> {
+        points.iter().map(|(p, _)| (p.x.0, p.y.0)).collect()
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn test_overflow_clip_follows_the_inside_of_the_border() {
+        let root = layout(r#"{ "type": "view", "style": {
+            "width": 200, "height": 100, "borderWidth": 4, "borderRadius": 10, "overflow": "hidden" } }"#);
+        assert!(root.clips_overflow());
+        let points = pt(&box_clip_points(&root));
+
+        // Inset by half the border, which is stroked on the box edge
+        let (left, bottom, right, top) = (2.0, PAGE_HEIGHT_PT - 98.0, 198.0, PAGE_HEIGHT_PT - 2.0);
+        assert!(points.iter().all(|&(x, y)| x > left - 0.01 && x < right + 0.01 && y > bottom - 0.01 && y < top + 0.01));
+        assert!(points.iter().any(|&(x, _)| approx(x, left)) && points.iter().any(|&(_, y)| approx(y, top)));
+
+        // The corners are rounded with the inner radius (10 less the inset)
+        assert!(approx(points[0].0, left + 8.0) && approx(points[0].1, bottom));
+        assert!(!points.iter().any(|&(x, y)| approx(x, left) && approx(y, top)));
+        let (cx, cy) = (left + 8.0, top - 8.0);
+        for &(x, y) in points.iter().filter(|&&(x, y)| x < cx && y > cy) {
+            assert!(approx(((x - cx).powi(2) + (y - cy).powi(2)).sqrt(), 8.0));
+        }
+    }
+
+    #[test]
+    fn test_square_clip_is_the_box() {
+        let root = layout(r#"{ "type": "view", "style": { "width": 200, "height": 100, "overflow": "hidden" } }"#);
+        let points = pt(&box_clip_points(&root));
+        for corner in [(0.0, PAGE_HEIGHT_PT - 100.0), (200.0, PAGE_HEIGHT_PT - 100.0), (200.0, PAGE_HEIGHT_PT), (0.0, PAGE_HEIGHT_PT)] {
+            assert!(points.iter().any(|&(x, y)| approx(x, corner.0) && approx(y, corner.1)), "{:?}", corner);
+        }
+    }
+
+    #[test]
+    fn test_cover_image_fills_the_box_centered() {
+        // A wide image is fitted to the height and cropped on both sides
+        assert_eq!(fit_image(&ObjectFit::Cover, 100.0, 100.0, 200.0, 100.0), (200.0, 100.0, -50.0, 0.0));
+        // A tall one is fitted to the width and cropped at the top and bottom
+        assert_eq!(fit_image(&ObjectFit::Cover, 100.0, 100.0, 100.0, 400.0), (100.0, 400.0, 0.0, -150.0));
+        // Contain leaves space instead
+        assert_eq!(fit_image(&ObjectFit::Contain, 100.0, 100.0, 200.0, 100.0), (100.0, 50.0, 0.0, 25.0));
+        assert_eq!(fit_image(&ObjectFit::Fill, 100.0, 100.0, 200.0, 100.0), (100.0, 100.0, 0.0, 0.0));
+    }
+
+    fn ids(boxes: Vec<&LayoutBox>) -> Vec<&str> {
+        boxes.iter().map(|b| b.node.id.as_deref().unwrap_or("")).collect()
+    }
+
+    #[test]
+    fn test_paint_order_stacks_positioned_and_z_index_boxes() {
+        let root = layout(r#"{ "type": "view", "children": [
+            { "type": "view", "id": "a", "style": { "height": 10 } },
+            { "type": "view", "id": "b", "style": { "position": "relative", "height": 10 } },
+            { "type": "view", "id": "c", "style": { "position": "absolute", "zIndex": 2, "height": 10 } },
+            { "type": "view", "id": "d", "children": [
+                { "type": "view", "id": "e", "style": { "position": "relative", "zIndex": -1, "height": 10 } },
+                { "type": "view", "id": "f", "style": { "position": "relative", "zIndex": 1, "height": 10 } }
+            ] },
+            { "type": "view", "id": "g", "style": { "zIndex": 3, "height": 10 } },
+            { "type": "view", "id": "h", "style": { "opacity": 0.5 }, "children": [
+                { "type": "view", "id": "i", "style": { "position": "relative", "zIndex": 5, "height": 10 } }
+            ] },
+            { "type": "view", "id": "j", "style": { "position": "relative", "zIndex": 2, "height": 10 } },
+            { "type": "view", "id": "k", "style": { "position": "fixed", "height": 10 } }
+        ] }"#);
+
+        // Negative zIndex, in-flow, positioned, then zIndex boxes (ties in document order);
+        // nested zIndex boxes are lifted into the context, fixed boxes are left to the page.
+        // A static box ignores its zIndex and keeps its place in the flow.
+        assert_eq!(ids(paint_order(&root.children, true)), ["e", "a", "d", "g", "h", "b", "f", "c", "j"]);
+        assert!(!root.children[4].forms_stacking_context());
+
+        // The lifted boxes aren't painted again by their parent
+        let d = &root.children[3];
+        assert!(ids(paint_order(&d.children, stacks_descendants(d))).is_empty());
+
+        // A stacking context keeps its own zIndex boxes
+        let h = &root.children[5];
+        assert!(h.forms_stacking_context());
+        assert_eq!(ids(paint_order(&h.children, true)), ["i"]);
+    }
+
+    #[test]
+    fn test_structure_elements_keep_their_z_index_boxes() {
+        let root = layout(r#"{ "type": "view", "children": [
+            { "type": "list", "children": [
+                { "type": "listItem", "id": "item", "children": [
+                    { "type": "view", "children": [
+                        { "type": "text", "id": "badge", "text": "New", "style": { "position": "relative", "zIndex": 1 } }
+                    ] }
+                ] }
+            ] },
+            { "type": "table", "children": [{ "type": "row", "children": [
+                { "type": "cell", "id": "cell", "children": [
+                    { "type": "text", "id": "mark", "text": "!", "style": { "position": "relative", "zIndex": -1 } }
+                ] }
+            ] }] }
+        ] }"#);
+
+        // Painted inside the LI and TD elements rather than by the page
+        assert_eq!(ids(paint_order(&root.children, true)), ["", ""]);
+        let item = &root.children[0].children[0];
+        assert!(stacks_descendants(item));
+        assert_eq!(ids(paint_order(&item.children, stacks_descendants(item))), ["", "badge"]);
+        let cell = &root.children[1].children[0].children[0];
+        assert_eq!(ids(paint_order(&cell.children, stacks_descendants(cell))), ["mark"]);
+    }
+
+    #[test]
+    fn test_fixed_boxes_repeat_on_every_page() {
+        let node: JsonNode = serde_json::from_str(r#"{ "type": "page", "style": { "padding": 40 }, "children": [
+            { "type": "view", "id": "header", "style": { "position": "fixed", "top": 0, "left": 0, "width": 595, "height": 30 } },
+            { "type": "view", "style": { "height": 500 } },
+            { "type": "view", "style": { "height": 500 }, "children": [
+                { "type": "view", "id": "footer",
+                  "style": { "position": "fixed", "bottom": 10, "right": 10, "width": 50, "height": 20 } }
+            ] },
+            { "type": "view", "style": { "height": 500 } },
+            { "type": "text", "id": "note", "style": { "position": "fixed", "top": 50, "left": 100, "width": 200 }, "children": [
+                { "type": "text", "text": "Draft " },
+                { "type": "text", "text": "copy", "style": { "fontWeight": "bold" } }
+            ] },
+            { "type": "table", "id": "stamp", "style": { "position": "fixed", "bottom": 40, "left": 20 }, "children": [
+                { "type": "row", "children": [
+                    { "type": "cell", "style": { "padding": 4 }, "children": [{ "type": "text", "text": "Approved" }] },
+                    { "type": "cell", "style": { "padding": 4 }, "children": [{ "type": "text", "text": "2026" }] }
+                ] }
+            ] }
+        ] }"#).unwrap();
+        let pages = layout_pages(&node);
+        assert!(pages.len() >= 2);
+
+        for page in &pages {
+            // Placed against the page box, at the same spot on every page
+            let ids: Vec<&str> = page.fixed.iter().map(|b| b.node.id.as_deref().unwrap()).collect();
+            assert_eq!(ids, ["header", "footer", "note", "stamp"]);
+            let (header, footer) = (&page.fixed[0], &page.fixed[1]);
+            assert_eq!((header.x, header.y), (0.0, PAGE_HEIGHT_PT));
+            assert_eq!((footer.x, footer.y), (PAGE_WIDTH_PT - 60.0, 30.0));
+
+            // Inline content follows its text box
+            let note = &page.fixed[2];
+            assert_eq!((note.x, note.y), (100.0, PAGE_HEIGHT_PT - 50.0));
+            let line = &note.inline_lines[0];
+            let (draft, copy) = (&note.children[0], &note.children[1]);
+            assert_eq!((draft.x, draft.y), (100.0, note.y - line.baseline + draft.ascent()));
+            assert_eq!((copy.x, copy.y), (100.0 + line.fragments[1].x, note.y - line.baseline + copy.ascent()));
+
+            // Rows, cells and their content follow the table
+            let stamp = &page.fixed[3];
+            assert!(stamp.x == 20.0 && approx(stamp.y - stamp.height, 40.0));
+            let row = &stamp.children[0];
+            assert_eq!((row.x, row.y), (stamp.x, stamp.y));
+            let (first, second) = (&row.children[0], &row.children[1]);
+            assert_eq!((first.x, first.y), (stamp.x, stamp.y));
+            assert_eq!((second.x, second.y), (stamp.x + first.width, stamp.y));
+            assert_eq!((first.children[0].x, first.children[0].y), (first.x + 4.0, first.y - 4.0));
+            assert_eq!((second.children[0].x, second.children[0].y), (second.x + 4.0, second.y - 4.0));
+
+            // and not painted again with the page's content
+            assert!(paint_order(&page.children, true).iter().all(|b| !b.is_fixed()));
+        }
+    }
+
+    /// Render a layout with document options, and load the result
+    fn render(json: &str, document: &str) -> lopdf::Document {
+        let root: JsonNode = serde_json::from_str(json).unwrap();
+        let document: DocumentOptions = serde_json::from_str(document).unwrap();
+        lopdf::Document::load_mem(&from_layout(&root, &document).unwrap()).unwrap()
+    }
+
+    /// Render a layout and decode the content operators of each page
+    fn page_operations(json: &str, document: &str) -> Vec<Vec<lopdf::content::Operation>> {
+        let pdf = render(json, document);
+        pdf.get_pages().values().map(|&id| pdf.get_and_decode_page_content(id).unwrap().operations).collect()
+    }
+
+    fn point(x: f32, y: f32, next_is_control: bool) -> (Point, bool) {
+        (Point::new(Mm(x * PT_TO_MM), Mm(y * PT_TO_MM)), next_is_control)
+    }
+
+    #[test]
+    fn test_dash_patterns_fit_the_path() {
+        // Dashes and gaps are three widths, stretched to repeat a whole number of times
+        let closed = dash_pattern(&BorderStyle::Dashed, 2.0, 100.0, true).unwrap();
+        assert_eq!(closed, vec![6.25, 6.25]);
+
+        // Open paths (single sides) start and end with a dash
+        let open = dash_pattern(&BorderStyle::Dashed, 2.0, 100.0, false).unwrap();
+        assert!(approx(open[0], open[1]) && open[0] < 6.0);
+        assert!(approx(9.0 * open[0] + 8.0 * open[1], 100.0));
+
+        // Dots are zero-length dashes (drawn with round caps) two widths apart, on both ends
+        let dotted = dash_pattern(&BorderStyle::Dotted, 2.0, 100.0, false).unwrap();
+        assert_eq!(dotted, vec![0.0, 4.0]);
+        let dotted = dash_pattern(&BorderStyle::Dotted, 3.0, 100.0, true).unwrap();
+        assert!(approx(dotted[1], 100.0 / 17.0));
+
+        assert_eq!(dash_pattern(&BorderStyle::Solid, 2.0, 100.0, true), None);
+        assert_eq!(dash_pattern(&BorderStyle::Double, 2.0, 100.0, true), None);
+        assert_eq!(dash_pattern(&BorderStyle::Dashed, 2.0, 0.0, true), None);
+    }
+
+    #[test]
+    fn test_path_length_follows_curves() {
+        let square = [point(0.0, 0.0, false), point(10.0, 0.0, false), point(10.0, 10.0, false), point(0.0, 10.0, false)];
+        assert!(approx(path_length(&square, false), 30.0));
+        assert!(approx(path_length(&square, true), 40.0));
+
+        // A quarter circle as a Bézier is a quarter of the circumference long
+        let (r, k) = (20.0, 0.552_284_8 * 20.0);
+        let arc = [point(r, 0.0, true), point(r, k, true), point(k, r, false), point(0.0, r, false)];
+        let quarter = r * std::f32::consts::FRAC_PI_2;
+        assert!((path_length(&arc, false) - quarter).abs() < 0.01 * quarter);
+        let control_polygon = k + ((r - k).powi(2) * 2.0).sqrt() + k;
+        assert!(control_polygon > 1.05 * quarter);
+    }
+
+    #[test]
+    fn test_rounded_dashed_border_joins_at_the_corners() {
+        // The outline of a dashed border: straight sides and segmented quarter arcs
+        let points = rounded_rect_points(0.0, 0.0, 100.0, 50.0, (10.0, 10.0, 10.0, 10.0));
+        let length = path_length(&points, true);
+        let perimeter = 2.0 * (80.0 + 30.0) + 2.0 * std::f32::consts::PI * 10.0;
+        assert!((length - perimeter).abs() < 0.005 * perimeter);
+
+        // The pattern repeats a whole number of times around it, so the last gap meets the first dash
+        let pattern = dash_pattern(&BorderStyle::Dashed, 2.0, length, true).unwrap();
+        let repeats = length / (pattern[0] + pattern[1]);
+        assert!(approx(repeats, repeats.round()));
+
+        // Rendered with the dash pattern, and dots with round caps
+        let render = |style: &str| page_operations(
+            &format!(r#"{{ "type": "view", "style": {{ "width": 100, "height": 50, "borderRadius": 10,
+                "borderWidth": 2, "borderStyle": "{}" }} }}"#, style),
+            "{}",
+        ).remove(0);
+        let ops = render("dashed");
+        let dash = ops.iter().find(|op| op.operator == "d").unwrap();
+        let array = dash.operands[0].as_array().unwrap();
+        let dash_len = array[0].as_float().unwrap();
+        assert!((dash_len - pattern[0]).abs() < 0.01);
+        assert!(!ops.iter().any(|op| op.operator == "J"));
+
+        let ops = render("dotted");
+        let cap = ops.iter().find(|op| op.operator == "J").unwrap();
+        assert_eq!(cap.operands[0].as_i64().unwrap(), 1);
+        let dash = ops.iter().find(|op| op.operator == "d").unwrap();
+        assert_eq!(dash.operands[0].as_array().unwrap()[0].as_float().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_transform_matrix_follows_css() {
+        // A row whose second box is transformed: layout ignores the transform
+        let json = |transform: &str, origin: &str| format!(
+            r#"{{ "type": "view", "style": {{ "direction": "row", "padding": 10 }}, "children": [
+                {{ "type": "view", "style": {{ "width": 40, "height": 20 }} }},
+                {{ "type": "view", "style": {{ "width": 100, "height": 20, "transform": "{}", "transformOrigin": "{}" }} }}
+            ] }}"#, transform, origin);
+        let plain = layout(&json("", "center"));
+        let rotated = layout(&json("rotate(90deg)", "top left"));
+        let (a, b) = (&plain.children[1], &rotated.children[1]);
+        assert!(approx(a.x, b.x) && approx(a.y, b.y) && approx(b.x, 50.0));
+        assert_eq!(transform_matrix(a), None);
+
+        // Where a point given relative to the box's top-left corner (y down) ends up in PDF space
+        let apply = |json: &str, x: f32, y: f32| {
+            let root = layout(json);
+            let node = &root.children[1];
+            let [a, b, c, d, e, f] = transform_matrix(node).unwrap();
+            let (px, py) = (node.x + x, node.y - y);
+            (a * px + c * py + e, b * px + d * py + f)
+        };
+        let top = rotated.children[1].y;
+
+        // Positive angles turn clockwise on the page: the top right corner swings below the top left
+        let (x, y) = apply(&json("rotate(90deg)", "top left"), 100.0, 0.0);
+        assert!(approx(x, 50.0) && approx(y, top - 100.0));
+
+        // Around the center, the center stays put and the corners turn about it
+        let (x, y) = apply(&json("rotate(90deg)", "center"), 50.0, 10.0);
+        assert!(approx(x, 100.0) && approx(y, top - 10.0));
+        let (x, y) = apply(&json("rotate(90deg)", "center"), 0.0, 0.0);
+        assert!(approx(x, 110.0) && approx(y, top + 40.0));
+
+        // The last function applies first: translate moves along the rotated x axis (down)...
+        let (x, y) = apply(&json("rotate(90deg) translate(10, 0)", "top left"), 0.0, 0.0);
+        assert!(approx(x, 50.0) && approx(y, top - 10.0));
+        // ...or along the page's x axis when written first
+        let (x, y) = apply(&json("translate(10, 0) rotate(90deg)", "top left"), 0.0, 0.0);
+        assert!(approx(x, 60.0) && approx(y, top));
+        // Scale applies before both: (5, 0) doubles, shifts to 20 and turns downwards
+        let (x, y) = apply(&json("rotate(90deg) translate(10, 0) scale(2)", "top left"), 5.0, 0.0);
+        assert!(approx(x, 50.0) && approx(y, top - 20.0));
+    }
+
+    /// A painting or clipping operator and the subpaths it applies to
+    type PaintedPath<'a> = (&'a str, Vec<Vec<(f32, f32)>>);
+
+    /// Paths in drawing order, with the operator that paints or clips to them
+    fn painted_paths(ops: &[lopdf::content::Operation]) -> Vec<PaintedPath<'_>> {
+        let mut paths = Vec::new();
+        let mut current: Vec<Vec<(f32, f32)>> = Vec::new();
+        for op in ops {
+            let xy = |i: usize| (op.operands[i].as_float().unwrap(), op.operands[i + 1].as_float().unwrap());
+            match op.operator.as_str() {
+                "m" => current.push(vec![xy(0)]),
+                "l" => current.last_mut().unwrap().push(xy(0)),
+                "c" => current.last_mut().unwrap().push(xy(4)),
+                "v" | "y" => current.last_mut().unwrap().push(xy(2)),
+                "h" | "n" => {}
+                painting if !current.is_empty() => paths.push((painting, std::mem::take(&mut current))),
+                _ => {}
+            }
+        }
+        paths
+    }
+
+    /// (left, bottom, right, top) of a subpath
+    fn bounds(points: &[(f32, f32)]) -> (f32, f32, f32, f32) {
+        points.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(l, b, r, t), &(x, y)| {
+            (l.min(x), b.min(y), r.max(x), t.max(y))
+        })
+    }
+
+    fn has_point(points: &[(f32, f32)], x: f32, y: f32) -> bool {
+        points.iter().any(|&(px, py)| (px - x).abs() < 0.01 && (py - y).abs() < 0.01)
+    }
+
+    #[test]
+    fn test_box_shadow_layers_clip_and_radii() {
+        // A 100x50 box at (100, 692)-(200, 742) in PDF space with rounded corners and a border
+        let render = |inset: bool| {
+            let json = format!(
+                r#"{{ "type": "view", "style": {{ "padding": 100 }}, "children": [{{ "type": "view", "style": {{
+                    "width": 100, "height": 50, "borderRadius": 10, "borderWidth": 4,
+                    "borderColor": {{ "r": 255, "g": 0, "b": 0 }}, "boxShadow": {{ "offsetX": 5, "offsetY": 5,
+                    "blur": 8, "color": {{ "r": 0, "g": 0, "b": 0, "a": 0.5 }}, "inset": {} }} }} }}] }}"#,
+                inset
+            );
+            let root: JsonNode = serde_json::from_str(&json).unwrap();
+            let pdf = lopdf::Document::load_mem(&from_layout(&root, &DocumentOptions::default()).unwrap()).unwrap();
+            let page = *pdf.get_pages().values().next().unwrap();
+            let ops = pdf.get_and_decode_page_content(page).unwrap().operations;
+
+            // The fill alpha each layer is drawn with
+            let state = ops.iter().find(|op| op.operator == "gs").unwrap().operands[0].as_name().unwrap().to_vec();
+            let (_, resource_ids) = pdf.get_page_resources(page);
+            let resources = pdf.get_dictionary(resource_ids[0]).unwrap();
+            let states = resources.get(b"ExtGState").unwrap().as_dict().unwrap();
+            let alpha = states.get(&state).unwrap().as_dict().unwrap().get(b"ca").unwrap().as_float().unwrap();
+            (alpha, ops)
+        };
+
+        // Outer shadow: the layers' alpha compounds to the shadow's
+        let (alpha, ops) = render(false);
+        assert!((1.0 - (1.0 - alpha).powi(SHADOW_LAYERS as i32) - 0.5).abs() < 0.01);
+        let paths = painted_paths(&ops);
+        let (clip, layers) = (&paths[0], &paths[1..=SHADOW_LAYERS]);
+
+        // It is clipped out of the box: an even-odd clip of a frame around the rounded box
+        assert_eq!(clip.0, "W*");
+        assert_eq!(clip.1.len(), 2);
+        let (l, b, r, t) = bounds(&clip.1[0]);
+        assert!(l < 90.0 && b < 680.0 && r > 210.0 && t > 750.0);
+        let (l, b, r, t) = bounds(&clip.1[1]);
+        assert!(approx(l, 100.0) && approx(b, 692.0) && approx(r, 200.0) && approx(t, 742.0));
+        assert!(has_point(&clip.1[1], 110.0, 692.0) && !has_point(&clip.1[1], 100.0, 692.0));
+
+        // Layers move with the offset (down is negative y), spread over the blur, with grown radii
+        assert!(layers.iter().all(|(op, rings)| *op == "f*" && rings.len() == 1));
+        let outer = &layers[0].1[0];
+        let (l, b, r, t) = bounds(outer);
+        assert!(approx(l, 101.5) && approx(b, 683.5) && approx(r, 208.5) && approx(t, 740.5));
+        assert!(has_point(outer, 115.0, 683.5) && !has_point(outer, 101.5, 683.5));
+        let (l, _, _, _) = bounds(&layers[SHADOW_LAYERS - 1].1[0]);
+        assert!(approx(l, 108.5));
+
+        // Inset shadow: clipped to the inside of the border, then painted as holes in a frame
+        let (alpha, ops) = render(true);
+        assert!((1.0 - (1.0 - alpha).powi(SHADOW_LAYERS as i32) - 0.5).abs() < 0.01);
+        let paths = painted_paths(&ops);
+        let (clip, layers) = (&paths[0], &paths[1..=SHADOW_LAYERS]);
+        assert_eq!(clip.0, "W");
+        let (l, b, r, t) = bounds(&clip.1[0]);
+        assert!(approx(l, 102.0) && approx(b, 694.0) && approx(r, 198.0) && approx(t, 740.0));
+        assert!(has_point(&clip.1[0], 110.0, 694.0) && !has_point(&clip.1[0], 102.0, 694.0));
+        assert!(layers.iter().all(|(op, rings)| *op == "f*" && rings.len() == 2));
+        let hole = &layers[0].1[1];
+        let (l, b, r, t) = bounds(hole);
+        assert!(approx(l, 108.5) && approx(b, 690.5) && approx(r, 201.5) && approx(t, 733.5));
+        assert!(has_point(hole, 115.0, 690.5) && !has_point(hole, 108.5, 690.5));
+    }
+
+    #[test]
+    fn test_watermark_is_centered_rotated_and_faded() {
+        let watermark: Watermark = serde_json::from_str(r#"{ "text": "DRAFT", "opacity": 0.3 }"#).unwrap();
+        let (mark, layer) = watermark_layout(&watermark).unwrap();
+        assert_eq!(layer, WatermarkLayer::Over);
+        assert!(approx(mark.x + mark.width / 2.0, PAGE_WIDTH_PT / 2.0));
+        assert!(approx(mark.y - mark.height / 2.0, PAGE_HEIGHT_PT / 2.0));
+        assert_eq!(mark.node.style.opacity, Some(0.3));
+
+        // The default -45 degrees turns it counterclockwise about the page center
+        let [a, b, c, d, e, f] = transform_matrix(&mark).unwrap();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(approx(a, half) && approx(b, half) && approx(c, -half) && approx(d, half));
+        let (cx, cy) = (PAGE_WIDTH_PT / 2.0, PAGE_HEIGHT_PT / 2.0);
+        assert!(approx(a * cx + c * cy + e, cx) && approx(b * cx + d * cy + f, cy));
+    }
+
+    #[test]
+    fn test_watermark_layer_on_every_page() {
+        // Two pages, each filled by one box
+        let json = r#"{ "type": "page", "style": { "width": 595, "height": 842, "padding": 40,
+            "backgroundColor": { "r": 255, "g": 255, "b": 200 } }, "children": [
+            { "type": "view", "style": { "width": 515, "height": 762, "backgroundColor": { "r": 0, "g": 0, "b": 255 } } },
+            { "type": "view", "style": { "width": 515, "height": 762, "backgroundColor": { "r": 0, "g": 255, "b": 0 } } }
+        ] }"#;
+
+        for (layer, under) in [("under", true), ("over", false)] {
+            let pdf = render(json, &format!(r#"{{ "watermark": {{ "text": "DRAFT", "opacity": 0.3, "layer": "{}" }} }}"#, layer));
+            let pages = pdf.get_pages();
+            assert_eq!(pages.len(), 2);
+            for &page in pages.values() {
+                let ops = pdf.get_and_decode_page_content(page).unwrap().operations;
+                let position = |operator: &str, n: usize| {
+                    ops.iter().enumerate().filter(|(_, op)| op.operator == operator).nth(n).unwrap().0
+                };
+
+                // The watermark is one faded group: above the page background, and under or over the box
+                let (background, content, mark) = (position("f", 0), position("f", 1), position("Do", 0));
+                assert_eq!(ops.iter().filter(|op| op.operator == "Do").count(), 1);
+                assert!(background < mark);
+                assert_eq!(mark < content, under);
+
+                let (_, resource_ids) = pdf.get_page_resources(page);
+                let resources = pdf.get_dictionary(resource_ids[0]).unwrap();
+                let state = ops[mark - 1].operands[0].as_name().unwrap();
+                let states = resources.get(b"ExtGState").unwrap().as_dict().unwrap();
+                let alpha = states.get(state).unwrap().as_dict().unwrap().get(b"ca").unwrap().as_float().unwrap();
+                assert!(approx(alpha, 0.3));
+
+                // Drawn rotated about the page center
+                let group = ops[mark].operands[0].as_name().unwrap();
+                let groups = resources.get(b"XObject").unwrap().as_dict().unwrap();
+                let group = pdf.get_object(groups.get(group).unwrap().as_reference().unwrap()).unwrap();
+                let group = group.as_stream().unwrap();
+                let content = lopdf::content::Content::decode(&group.decompressed_content().unwrap_or_else(|_| group.content.clone())).unwrap();
+                let cm = content.operations.iter().find(|op| op.operator == "cm").unwrap();
+                let m: Vec<f32> = cm.operands.iter().map(|o| o.as_float().unwrap()).collect();
+                let (cx, cy) = (PAGE_WIDTH_PT / 2.0, PAGE_HEIGHT_PT / 2.0);
+                assert!(approx(m[1], std::f32::consts::FRAC_1_SQRT_2) && approx(m[2], -std::f32::consts::FRAC_1_SQRT_2));
+                assert!(approx(m[0] * cx + m[2] * cy + m[4], cx) && approx(m[1] * cx + m[3] * cy + m[5], cy));
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! PDF/A archival conformance.
+//!
+//! PDF/A files must carry every font they use, so instead of the builtin
+//! Helvetica the renderer embeds a Helvetica-metric family (Liberation Sans or
+//! Arimo), found in `INKWELL_FONT_DIR` or the usual system font directories.
+//! Text is laid out with Helvetica's metrics either way. Text the fonts have no
+//! glyphs for can't be embedded, and is refused before anything is drawn.
+//!
+//! The saved file then gets an sRGB output intent (which also allows the
+//! transparency PDF/A-2 and 3 support), names for the optional content
+//! configuration and images without interpolation. The matching XMP
+//! identification is written with the rest of the metadata.
+
+use std::path::PathBuf;
+
+use printpdf::lopdf::{Dictionary, Document, Object, Stream, StringFormat};
+
+/// Directory searched first for the fonts to embed
+const FONT_DIR_VAR: &str = "INKWELL_FONT_DIR";
+const FONT_DIRS: &[&str] = &[
+    "/usr/share/fonts/truetype/liberation",
+    "/usr/share/fonts/truetype/liberation2",
+    "/usr/share/fonts/liberation-sans",
+    "/usr/share/fonts/truetype/croscore",
+    "/usr/share/fonts/google-croscore",
+    "/Library/Fonts",
+];
+/// Regular, bold, italic and bold italic files of each family, in order of preference
+const FONT_FAMILIES: &[[&str; 4]] = &[
+    ["LiberationSans-Regular.ttf", "LiberationSans-Bold.ttf", "LiberationSans-Italic.ttf", "LiberationSans-BoldItalic.ttf"],
+    ["Arimo-Regular.ttf", "Arimo-Bold.ttf", "Arimo-Italic.ttf", "Arimo-BoldItalic.ttf"],
+];
+const SRGB_NAME: &str = "sRGB IEC61966-2.1";
+
+// ============================================================================
+// FONTS
+// ============================================================================
+
+/// Font files to embed, one per style
+pub struct FontFiles {
+    pub regular: Vec<u8>,
+    pub bold: Vec<u8>,
+    pub italic: Vec<u8>,
+    pub bold_italic: Vec<u8>,
+}
+
+impl FontFiles {
+    pub fn load() -> Result<FontFiles, String> {
+        let mut dirs: Vec<PathBuf> = std::env::var_os(FONT_DIR_VAR).map(PathBuf::from).into_iter().collect();
+        dirs.extend(FONT_DIRS.iter().map(PathBuf::from));
+
+        for dir in &dirs {
+            for family in FONT_FAMILIES {
+                if family.iter().all(|file| dir.join(file).is_file()) {
+                    let read = |file: &str| {
+                        let path = dir.join(file);
+                        std::fs::read(&path).map_err(|e| format!("Failed to read font {}: {}", path.display(), e))
+                    };
+                    return Ok(FontFiles {
+                        regular: read(family[0])?,
+                        bold: read(family[1])?,
+                        italic: read(family[2])?,
+                        bold_italic: read(family[3])?,
+                    });
+                }
+            }
+        }
+        Err(format!(
+            "PDF/A needs embeddable fonts: install Liberation Sans or Arimo, or point {} at a directory with {}",
+            FONT_DIR_VAR,
+            FONT_FAMILIES[0].join(", ")
+        ))
+    }
+
+    pub fn get(&self, bold: bool, italic: bool) -> &[u8] {
+        match (bold, italic) {
+            (true, true) => &self.bold_italic,
+            (true, false) => &self.bold,
+            (false, true) => &self.italic,
+            (false, false) => &self.regular,
+        }
+    }
+
+    /// Check that every string (with the bold and italic flags of its font) has
+    /// a glyph for each of its characters
+    pub fn check_coverage(&self, texts: &[(bool, bool, &str)]) -> Result<(), String> {
+        let mut faces = Vec::with_capacity(4);
+        for (bold, italic) in [(false, false), (true, false), (false, true), (true, true)] {
+            let face = ttf_parser::Face::parse(self.get(bold, italic), 0)
+                .map_err(|e| format!("Failed to parse font: {}", e))?;
+            faces.push(((bold, italic), face));
+        }
+
+        let mut missing: Vec<char> = Vec::new();
+        for &(bold, italic, text) in texts {
+            let face = &faces.iter().find(|(style, _)| *style == (bold, italic)).unwrap().1;
+            for c in text.chars() {
+                if !c.is_control() && face.glyph_index(c).is_none() && !missing.contains(&c) {
+                    missing.push(c);
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let listed: Vec<String> = missing.iter().map(|c| format!("'{}' (U+{:04X})", c, *c as u32)).collect();
+        Err(format!("The embedded fonts have no glyphs for {}", listed.join(", ")))
+    }
+}
+
+// ============================================================================
+// DOCUMENT
+// ============================================================================
+
+/// Bring the saved document in line with PDF/A, apart from the metadata
+pub fn apply(doc: &mut Document) -> Result<(), String> {
+    // Transparency groups and optional content need PDF 1.4 and 1.5
+    doc.version = "1.7".to_string();
+
+    let profile_id = doc.add_object(srgb_profile_stream());
+    let mut intent = Dictionary::new();
+    intent.set("Type", Object::Name(b"OutputIntent".to_vec()));
+    intent.set("S", Object::Name(b"GTS_PDFA1".to_vec()));
+    intent.set("OutputConditionIdentifier", Object::string_literal(SRGB_NAME));
+    intent.set("RegistryName", Object::string_literal("http://www.color.org"));
+    intent.set("Info", Object::string_literal(SRGB_NAME));
+    intent.set("DestOutputProfile", Object::Reference(profile_id));
+
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    catalog.set("OutputIntents", Object::Array(vec![Object::Dictionary(intent)]));
+    // Every optional content configuration needs a name
+    if let Ok(Object::Dictionary(properties)) = catalog.get_mut(b"OCProperties") {
+        if let Ok(Object::Dictionary(config)) = properties.get_mut(b"D") {
+            config.set("Name", Object::String(b"Default".to_vec(), StringFormat::Literal));
+        }
+    }
+
+    for object in doc.objects.values_mut() {
+        match object {
+            Object::Stream(stream) if is_name(&stream.dict, b"Subtype", b"Image") => {
+                stream.dict.set("Interpolate", Object::Boolean(false));
+            }
+            // printpdf leaves the glyph mapping of embedded fonts implicit
+            Object::Dictionary(font) if is_name(font, b"Subtype", b"Type0") => {
+                if let Ok(Object::Array(descendants)) = font.get_mut(b"DescendantFonts") {
+                    for descendant in descendants {
+                        if let Object::Dictionary(descendant) = descendant {
+                            if is_name(descendant, b"Subtype", b"CIDFontType2") && !descendant.has(b"CIDToGIDMap") {
+                                descendant.set("CIDToGIDMap", Object::Name(b"Identity".to_vec()));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_name(dict: &Dictionary, key: &[u8], name: &[u8]) -> bool {
+    dict.get(key).and_then(|o| o.as_name()).is_ok_and(|n| n == name)
+}
+
+// ============================================================================
+// SRGB PROFILE
+// ============================================================================
+
+fn srgb_profile_stream() -> Stream {
+    let mut dict = Dictionary::new();
+    dict.set("N", Object::Integer(3));
+    dict.set("Alternate", Object::Name(b"DeviceRGB".to_vec()));
+    let mut stream = Stream::new(dict, srgb_profile());
+    let _ = stream.compress();
+    stream
+}
+
+/// A minimal ICC v2 display profile for sRGB: the D50-adapted primaries and
+/// the sRGB transfer curve as a lookup table
+fn srgb_profile() -> Vec<u8> {
+    fn s15f16(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for value in [x, y, z] {
+            tag.extend(s15f16(value));
+        }
+        tag
+    }
+
+    let mut description = b"desc\0\0\0\0".to_vec();
+    description.extend((SRGB_NAME.len() as u32 + 1).to_be_bytes());
+    description.extend(SRGB_NAME.as_bytes());
+    description.push(0);
+    // Empty Unicode and ScriptCode descriptions
+    description.extend([0u8; 4 + 4 + 2 + 1 + 67]);
+
+    let mut copyright = b"text\0\0\0\0".to_vec();
+    copyright.extend(b"No copyright, use freely\0");
+
+    const CURVE_POINTS: u32 = 1024;
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend(CURVE_POINTS.to_be_bytes());
+    for i in 0..CURVE_POINTS {
+        let v = i as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+        curve.extend(((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    // The three curves share one table
+    let tags: [(&[u8; 4], usize); 9] = [
+        (b"desc", 0), (b"cprt", 1), (b"wtpt", 2), (b"rXYZ", 3), (b"gXYZ", 4), (b"bXYZ", 5),
+        (b"rTRC", 6), (b"gTRC", 6), (b"bTRC", 6),
+    ];
+    let data = [
+        description,
+        copyright,
+        xyz(0.9642, 1.0, 0.8249),
+        xyz(0.4361, 0.2225, 0.0139),
+        xyz(0.3851, 0.7169, 0.0971),
+        xyz(0.1431, 0.0606, 0.7141),
+        curve,
+    ];
+
+    let mut offsets = Vec::with_capacity(data.len());
+    let mut offset = 128 + 4 + tags.len() * 12;
+    for block in &data {
+        offsets.push(offset);
+        offset += block.len().next_multiple_of(4);
+    }
+    let size = offset;
+
+    let mut profile = Vec::with_capacity(size);
+    profile.extend((size as u32).to_be_bytes());
+    profile.extend([0u8; 4]); // preferred CMM
+    profile.extend(0x0210_0000u32.to_be_bytes()); // version 2.1
+    profile.extend(b"mntrRGB XYZ ");
+    // Creation date: 2024-01-01 00:00:00
+    for field in [2024u16, 1, 1, 0, 0, 0] {
+        profile.extend(field.to_be_bytes());
+    }
+    profile.extend(b"acsp");
+    profile.extend([0u8; 4 + 4 + 4 + 4 + 8 + 4]); // platform, flags, device, attributes, intent
+    profile.extend(s15f16(0.9642));
+    profile.extend(s15f16(1.0));
+    profile.extend(s15f16(0.8249));
+    profile.resize(128, 0);
+
+    profile.extend((tags.len() as u32).to_be_bytes());
+    for (signature, block) in tags {
+        profile.extend(signature);
+        profile.extend((offsets[block] as u32).to_be_bytes());
+        profile.extend((data[block].len() as u32).to_be_bytes());
+    }
+    for block in &data {
+        profile.extend(block);
+        profile.resize(profile.len().next_multiple_of(4), 0);
+    }
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::DocumentOptions;
+    use printpdf::{Mm, PdfDocument};
+
+    #[test]
+    fn test_srgb_profile_is_well_formed() {
+        let profile = srgb_profile();
+        assert_eq!(u32::from_be_bytes(profile[0..4].try_into().unwrap()) as usize, profile.len());
+        assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+        let count = u32::from_be_bytes(profile[128..132].try_into().unwrap()) as usize;
+        assert_eq!(count, 9);
+        for i in 0..count {
+            let entry = &profile[132 + i * 12..144 + i * 12];
+            let offset = u32::from_be_bytes(entry[4..8].try_into().unwrap()) as usize;
+            let size = u32::from_be_bytes(entry[8..12].try_into().unwrap()) as usize;
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+        }
+    }
+
+    #[test]
+    fn test_pdf_a_output_intent_and_identification() {
+        let options: DocumentOptions = serde_json::from_str(r#"{ "conformance": "pdf-a-3b" }"#).unwrap();
+        let (doc, _, _) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let mut doc = Document::load_mem(&doc.save_to_bytes().unwrap()).unwrap();
+        let mut image = Dictionary::new();
+        image.set("Subtype", Object::Name(b"Image".to_vec()));
+        image.set("Interpolate", Object::Boolean(true));
+        let image_id = doc.add_object(Stream::new(image, Vec::new()));
+        crate::pdf_metadata::apply(&mut doc, &options).unwrap();
+
+        assert_eq!(doc.version, "1.7");
+        let catalog = doc.catalog().unwrap();
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name_str().unwrap(), "GTS_PDFA1");
+        let profile_id = intent.get(b"DestOutputProfile").unwrap().as_reference().unwrap();
+        assert_eq!(doc.get_object(profile_id).unwrap().as_stream().unwrap().dict.get(b"N").unwrap().as_i64().unwrap(), 3);
+        let config = catalog.get(b"OCProperties").unwrap().as_dict().unwrap().get(b"D").unwrap().as_dict().unwrap();
+        assert!(config.has(b"Name"));
+
+        let image = doc.get_object(image_id).unwrap().as_stream().unwrap();
+        assert!(!image.dict.get(b"Interpolate").unwrap().as_bool().unwrap());
+
+        // Metadata is written even when none is given, with the PDF/A identification
+        let xmp_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = String::from_utf8(doc.get_object(xmp_id).unwrap().as_stream().unwrap().content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>3</pdfaid:part>"));
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+    }
+}
@@ -0,0 +1,439 @@
+//! Files embedded in the PDF: the payload's attachments, the XML of a
+//! Factur-X / ZUGFeRD e-invoice and the files of fileAttachment nodes.
+//!
+//! Document-level files are listed in the catalog's EmbeddedFiles name tree
+//! (the viewer's attachments panel); node files get a FileAttachment annotation
+//! with a paperclip icon where the node was laid out. Every file is also in the
+//! catalog's AF array of associated files, with its relationship to the
+//! document, as PDF/A-3 requires.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::layout::{Attachment, Conformance, DocumentOptions, Invoice, InvoiceProfile, JsonNode, NodeType};
+use crate::pdf_resources::{self, text_string};
+
+/// Directory attachment paths are read from; without it files must be data URIs
+const ATTACHMENT_DIR_VAR: &str = "INKWELL_ATTACHMENT_DIR";
+/// Set to `1` or `true` to let attachments be downloaded from http(s) URLs
+const REMOTE_ATTACHMENTS_VAR: &str = "INKWELL_REMOTE_ATTACHMENTS";
+
+/// Where attachment files may come from besides data URIs. Payloads come from
+/// anyone who can reach the server, so reading files and URLs is opt-in.
+struct FileSources {
+    dir: Option<PathBuf>,
+    remote: bool,
+}
+
+impl FileSources {
+    fn from_env() -> FileSources {
+        FileSources {
+            dir: std::env::var_os(ATTACHMENT_DIR_VAR).filter(|d| !d.is_empty()).map(PathBuf::from),
+            remote: std::env::var(REMOTE_ATTACHMENTS_VAR).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+        }
+    }
+}
+
+/// A file to embed
+struct EmbeddedFile {
+    name: String,
+    mime: String,
+    data: Vec<u8>,
+    description: Option<String>,
+    /// AFRelationship: Source, Data, Alternative, Supplement or Unspecified
+    relationship: &'static str,
+}
+
+impl EmbeddedFile {
+    fn load(attachment: &Attachment, sources: &FileSources) -> Result<EmbeddedFile, String> {
+        let (data, data_type) = load_file(&attachment.src, sources)?;
+        let mime = attachment.mime_type.clone()
+            .or(data_type)
+            .unwrap_or_else(|| guess_mime_type(&attachment.name).to_string());
+        Ok(EmbeddedFile {
+            name: attachment.name.clone(),
+            mime,
+            data,
+            description: attachment.description.clone(),
+            relationship: "Unspecified",
+        })
+    }
+}
+
+/// Embed the document's files, and the files of the attachment marks (page,
+/// rectangle and file) as annotations
+pub fn apply(doc: &mut Document, options: &DocumentOptions, placed: Vec<(ObjectId, [f32; 4], Attachment)>) -> Result<(), String> {
+    let sources = FileSources::from_env();
+    let mut listed = Vec::new();
+    if let Some(ref invoice) = options.invoice {
+        let profile = invoice.profile.unwrap_or(InvoiceProfile::En16931);
+        listed.push(EmbeddedFile {
+            name: profile.file_name().to_string(),
+            mime: "text/xml".to_string(),
+            data: invoice.xml.as_bytes().to_vec(),
+            description: Some("Factur-X invoice".to_string()),
+            relationship: profile.relationship(),
+        });
+    }
+    for attachment in options.attachments.iter().flatten() {
+        match EmbeddedFile::load(attachment, &sources) {
+            Ok(file) => listed.push(file),
+            Err(e) => eprintln!("Failed to load attachment {}: {}", attachment.name, e),
+        }
+    }
+    if listed.is_empty() && placed.is_empty() {
+        return Ok(());
+    }
+
+    // Stamp the files with the document's modification date
+    let modified = doc.trailer.get(b"Info")
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_dictionary(id))
+        .and_then(|info| info.get(b"ModDate"))
+        .ok()
+        .cloned();
+
+    let mut associated = Vec::new();
+    let mut names = Vec::with_capacity(listed.len());
+    for file in &listed {
+        let spec_id = add_file(doc, file, modified.as_ref());
+        names.push((file.name.clone(), spec_id));
+        associated.push(Object::Reference(spec_id));
+    }
+
+    // Fixed nodes repeat on every page, but their file is embedded once
+    let mut specs: Vec<(Attachment, ObjectId)> = Vec::new();
+    let mut icons: HashMap<(u32, u32), ObjectId> = HashMap::new();
+    let mut annotations: HashMap<ObjectId, Vec<Object>> = HashMap::new();
+    for (page_id, rect, attachment) in placed {
+        let spec_id = match specs.iter().find(|(a, _)| *a == attachment) {
+            Some((_, id)) => *id,
+            None => match EmbeddedFile::load(&attachment, &sources) {
+                Ok(file) => {
+                    let id = add_file(doc, &file, modified.as_ref());
+                    associated.push(Object::Reference(id));
+                    specs.push((attachment.clone(), id));
+                    id
+                }
+                Err(e) => {
+                    eprintln!("Failed to load attachment {}: {}", attachment.name, e);
+                    continue;
+                }
+            },
+        };
+
+        let (width, height) = (rect[2] - rect[0], rect[3] - rect[1]);
+        let icon_id = *icons.entry((width.to_bits(), height.to_bits())).or_insert_with(|| {
+            let mut icon = Dictionary::new();
+            icon.set("Type", Object::Name(b"XObject".to_vec()));
+            icon.set("Subtype", Object::Name(b"Form".to_vec()));
+            icon.set("BBox", Object::Array(vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)]));
+            doc.add_object(Stream::new(icon, paperclip(width, height).into_bytes()))
+        });
+        let mut appearance = Dictionary::new();
+        appearance.set("N", Object::Reference(icon_id));
+
+        let mut annotation = Dictionary::new();
+        annotation.set("Type", Object::Name(b"Annot".to_vec()));
+        annotation.set("Subtype", Object::Name(b"FileAttachment".to_vec()));
+        annotation.set("Rect", Object::Array(rect.into_iter().map(Object::Real).collect()));
+        annotation.set("FS", Object::Reference(spec_id));
+        annotation.set("Contents", text_string(attachment.description.as_deref().unwrap_or(&attachment.name)));
+        annotation.set("Name", Object::Name(b"Paperclip".to_vec()));
+        // Printable, as PDF/A requires of every annotation
+        annotation.set("F", Object::Integer(4));
+        annotation.set("AP", Object::Dictionary(appearance));
+        let id = doc.add_object(annotation);
+        annotations.entry(page_id).or_default().push(Object::Reference(id));
+    }
+    pdf_resources::add_annotations(doc, annotations)?;
+
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    if !names.is_empty() {
+        // Name tree keys must be sorted
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut tree = Dictionary::new();
+        tree.set(
+            "Names",
+            Object::Array(names.into_iter().flat_map(|(name, id)| [text_string(&name), Object::Reference(id)]).collect()),
+        );
+        let mut name_dict = match catalog.get(b"Names") {
+            Ok(Object::Dictionary(dict)) => dict.clone(),
+            _ => Dictionary::new(),
+        };
+        name_dict.set("EmbeddedFiles", Object::Dictionary(tree));
+        catalog.set("Names", Object::Dictionary(name_dict));
+    }
+    catalog.set("AF", Object::Array(associated));
+    Ok(())
+}
+
+/// An e-invoice can only be embedded in PDF/A-3, and only a CII invoice
+pub fn check_invoice(invoice: &Invoice, conformance: Option<Conformance>) -> Result<(), String> {
+    if conformance != Some(Conformance::PdfA3b) {
+        return Err("an embedded invoice needs \"conformance\": \"pdf-a-3b\"".to_string());
+    }
+    let xml = invoice.xml.trim_start_matches('\u{feff}').trim_start();
+    if !xml.starts_with('<') || !xml.contains("CrossIndustryInvoice") {
+        return Err("the invoice XML is not a Cross Industry Invoice".to_string());
+    }
+    Ok(())
+}
+
+/// PDF/A-2 only allows embedding PDF/A files, which can't be checked here
+pub fn check_conformance(root: &JsonNode, options: &DocumentOptions) -> Result<(), String> {
+    fn has_attachment_node(node: &JsonNode) -> bool {
+        node.node_type == NodeType::FileAttachment || node.children.iter().any(has_attachment_node)
+    }
+    let has_files = options.attachments.as_ref().is_some_and(|a| !a.is_empty()) || has_attachment_node(root);
+    if has_files && options.conformance == Some(Conformance::PdfA2b) {
+        return Err("attachments need \"conformance\": \"pdf-a-3b\"".to_string());
+    }
+    Ok(())
+}
+
+/// Add the embedded file stream and its file specification, returning the latter
+fn add_file(doc: &mut Document, file: &EmbeddedFile, modified: Option<&Object>) -> ObjectId {
+    let mut params = Dictionary::new();
+    params.set("Size", Object::Integer(file.data.len() as i64));
+    if let Some(modified) = modified {
+        params.set("ModDate", modified.clone());
+    }
+    let mut stream_dict = Dictionary::new();
+    stream_dict.set("Type", Object::Name(b"EmbeddedFile".to_vec()));
+    stream_dict.set("Subtype", Object::Name(file.mime.as_bytes().to_vec()));
+    stream_dict.set("Params", Object::Dictionary(params));
+    let mut stream = Stream::new(stream_dict, file.data.clone());
+    let _ = stream.compress();
+    let stream_id = doc.add_object(stream);
+
+    let mut embedded = Dictionary::new();
+    embedded.set("F", Object::Reference(stream_id));
+    embedded.set("UF", Object::Reference(stream_id));
+    let mut spec = Dictionary::new();
+    spec.set("Type", Object::Name(b"Filespec".to_vec()));
+    spec.set("F", text_string(&file.name));
+    spec.set("UF", text_string(&file.name));
+    spec.set("EF", Object::Dictionary(embedded));
+    if let Some(ref description) = file.description {
+        spec.set("Desc", text_string(description));
+    }
+    spec.set("AFRelationship", Object::Name(file.relationship.as_bytes().to_vec()));
+    doc.add_object(spec)
+}
+
+/// The file's bytes, and its MIME type if a data URI gives one
+fn load_file(src: &str, sources: &FileSources) -> Result<(Vec<u8>, Option<String>), String> {
+    if let Some(data_url) = src.strip_prefix("data:") {
+        // data:text/csv;base64,xxxxx or data:text/csv,a%2Cb
+        let (header, content) = data_url.split_once(',').ok_or("Invalid data URL format")?;
+        let mime = header.split(';').next().filter(|m| !m.is_empty()).map(str::to_string);
+        let data = if header.ends_with(";base64") {
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, content)
+                .map_err(|e| format!("Base64 decode error: {}", e))?
+        } else {
+            urlencoding::decode_binary(content.as_bytes()).into_owned()
+        };
+        Ok((data, mime))
+    } else if src.starts_with("http://") || src.starts_with("https://") {
+        if !sources.remote {
+            return Err(format!("Remote files are disabled (set {} to allow them)", REMOTE_ATTACHMENTS_VAR));
+        }
+        let response = ureq::get(src)
+            .call()
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        let mut bytes = Vec::new();
+        response.into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        Ok((bytes, None))
+    } else {
+        let dir = sources.dir.as_ref()
+            .ok_or_else(|| format!("Local files are disabled (set {} to allow them)", ATTACHMENT_DIR_VAR))?;
+        let path = file_in_dir(dir, src)?;
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+        Ok((bytes, None))
+    }
+}
+
+/// Resolve `src` against `dir`, refusing paths that lead out of it (through `..`,
+/// an absolute path or a symlink)
+fn file_in_dir(dir: &Path, src: &str) -> Result<PathBuf, String> {
+    let dir = dir.canonicalize().map_err(|e| format!("Invalid attachment directory {}: {}", dir.display(), e))?;
+    let path = dir.join(src).canonicalize().map_err(|e| format!("Failed to read file: {}", e))?;
+    if !path.starts_with(&dir) {
+        return Err(format!("{} is outside the attachment directory", src));
+    }
+    Ok(path)
+}
+
+fn guess_mime_type(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "xml" => "text/xml",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Appearance of an attachment annotation: a paperclip, drawn on a 12 x 16
+/// grid scaled to fit the annotation and centered in it
+fn paperclip(width: f32, height: f32) -> String {
+    let mut path = String::from("7.5 5 m\n7.5 11 l\n");
+    half_circle(&mut path, 6.0, 11.0, 1.5, 0.0);
+    path.push_str("4.5 3.5 l\n");
+    half_circle(&mut path, 7.0, 3.5, 2.5, 180.0);
+    path.push_str("9.5 12 l\n");
+    half_circle(&mut path, 6.0, 12.0, 3.5, 0.0);
+    path.push_str("2.5 6 l\n");
+
+    let scale = (width / 12.0).min(height / 16.0);
+    format!(
+        "q\n{0} 0 0 {0} {1} {2} cm\n0.25 0.25 0.25 RG\n1 w\n1 J\n1 j\n{3}S\nQ\n",
+        scale,
+        (width - 12.0 * scale) / 2.0,
+        (height - 16.0 * scale) / 2.0,
+        path
+    )
+}
+
+/// Append a counterclockwise half circle starting at `from` degrees, as two
+/// Bezier quarter circles
+pub fn half_circle(path: &mut String, cx: f32, cy: f32, r: f32, from: f32) {
+    const KAPPA: f32 = 0.552_284_8;
+    for quarter in 0..2 {
+        let a0 = (from + 90.0 * quarter as f32).to_radians();
+        let a1 = a0 + std::f32::consts::FRAC_PI_2;
+        let (x0, y0) = (cx + r * a0.cos(), cy + r * a0.sin());
+        let (x1, y1) = (cx + r * a1.cos(), cy + r * a1.sin());
+        path.push_str(&format!(
+            "{:.3} {:.3} {:.3} {:.3} {:.3} {:.3} c\n",
+            x0 - KAPPA * r * a0.sin(), y0 + KAPPA * r * a0.cos(),
+            x1 + KAPPA * r * a1.sin(), y1 - KAPPA * r * a1.cos(),
+            x1, y1
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::{Mm, PdfDocument};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rsm:CrossIndustryInvoice xmlns:rsm="urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100"/>"#;
+
+    #[test]
+    fn test_invoice_is_embedded_as_associated_file() {
+        let json = format!(r#"{{ "conformance": "pdf-a-3b", "invoice": {{ "xml": {:?}, "profile": "basic" }} }}"#, XML);
+        let options: DocumentOptions = serde_json::from_str(&json).unwrap();
+        let invoice = options.invoice.as_ref().unwrap();
+        assert!(check_invoice(invoice, options.conformance).is_ok());
+        assert!(check_invoice(invoice, Some(Conformance::PdfA2b)).is_err());
+
+        let (doc, _, _) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let mut doc = Document::load_mem(&doc.save_to_bytes().unwrap()).unwrap();
+        crate::pdf_metadata::apply(&mut doc, &options).unwrap();
+        apply(&mut doc, &options, Vec::new()).unwrap();
+
+        let catalog = doc.catalog().unwrap();
+        let tree = catalog.get(b"Names").unwrap().as_dict().unwrap().get(b"EmbeddedFiles").unwrap().as_dict().unwrap();
+        let names = tree.get(b"Names").unwrap().as_array().unwrap();
+        assert_eq!(names[0].as_str().unwrap(), b"factur-x.xml");
+        let spec_id = names[1].as_reference().unwrap();
+        assert_eq!(catalog.get(b"AF").unwrap().as_array().unwrap()[0].as_reference().unwrap(), spec_id);
+
+        let spec = doc.get_dictionary(spec_id).unwrap();
+        assert_eq!(spec.get(b"AFRelationship").unwrap().as_name_str().unwrap(), "Alternative");
+        let stream_id = spec.get(b"EF").unwrap().as_dict().unwrap().get(b"F").unwrap().as_reference().unwrap();
+        let stream = doc.get_object(stream_id).unwrap().as_stream().unwrap();
+        assert_eq!(stream.dict.get(b"Subtype").unwrap().as_name_str().unwrap(), "text/xml");
+        // Left as is when compressing doesn't make it smaller
+        let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+        assert_eq!(content, XML.as_bytes());
+
+        let xmp_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = String::from_utf8(doc.get_object(xmp_id).unwrap().as_stream().unwrap().content.clone()).unwrap();
+        assert!(xmp.contains("<fx:ConformanceLevel>BASIC</fx:ConformanceLevel>"));
+        assert!(xmp.contains("<pdfaSchema:prefix>fx</pdfaSchema:prefix>"));
+    }
+
+    #[test]
+    fn test_attachments_and_annotations() {
+        let json = r#"{ "attachments": [
+            { "name": "totals.json", "data": "data:,%7B%7D" },
+            { "name": "report.csv", "src": "data:text/csv;base64,YSxiCjEsMg==", "description": "Source data" }
+        ] }"#;
+        let options: DocumentOptions = serde_json::from_str(json).unwrap();
+        let root: JsonNode = serde_json::from_str(r#"{ "type": "page", "children": [ { "type": "fileAttachment" } ] }"#).unwrap();
+        assert!(check_conformance(&root, &options).is_ok());
+        let strict = DocumentOptions { conformance: Some(Conformance::PdfA2b), ..DocumentOptions::default() };
+        assert!(check_conformance(&root, &strict).is_err());
+
+        let (doc, _, _) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let mut doc = Document::load_mem(&doc.save_to_bytes().unwrap()).unwrap();
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let file = options.attachments.as_ref().unwrap()[1].clone();
+        // The same node on two spots embeds its file once
+        let placed = vec![(page_id, [10.0, 10.0, 22.0, 26.0], file.clone()), (page_id, [40.0, 10.0, 52.0, 26.0], file)];
+        apply(&mut doc, &options, placed).unwrap();
+
+        let catalog = doc.catalog().unwrap();
+        let tree = catalog.get(b"Names").unwrap().as_dict().unwrap().get(b"EmbeddedFiles").unwrap().as_dict().unwrap();
+        let names = tree.get(b"Names").unwrap().as_array().unwrap();
+        assert_eq!(names[0].as_str().unwrap(), b"report.csv");
+        assert_eq!(names[2].as_str().unwrap(), b"totals.json");
+        assert_eq!(catalog.get(b"AF").unwrap().as_array().unwrap().len(), 3);
+
+        let spec = doc.get_dictionary(names[3].as_reference().unwrap()).unwrap();
+        let stream_id = spec.get(b"EF").unwrap().as_dict().unwrap().get(b"F").unwrap().as_reference().unwrap();
+        let stream = doc.get_object(stream_id).unwrap().as_stream().unwrap();
+        // Guessed from the name
+        assert_eq!(stream.dict.get(b"Subtype").unwrap().as_name_str().unwrap(), "application/json");
+
+        let annots = doc.get_dictionary(page_id).unwrap().get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annots.len(), 2);
+        let annotation = doc.get_dictionary(annots[0].as_reference().unwrap()).unwrap();
+        assert_eq!(annotation.get(b"Subtype").unwrap().as_name_str().unwrap(), "FileAttachment");
+        assert_eq!(annotation.get(b"Contents").unwrap().as_str().unwrap(), b"Source data");
+        assert!(annotation.get(b"AP").unwrap().as_dict().unwrap().has(b"N"));
+        let fs = |annot: &Object| doc.get_dictionary(annot.as_reference().unwrap()).unwrap().get(b"FS").unwrap().clone();
+        assert_eq!(fs(&annots[0]).as_reference().unwrap(), fs(&annots[1]).as_reference().unwrap());
+    }
+
+    #[test]
+    fn test_files_are_read_from_the_attachment_directory_only() {
+        let root = std::env::temp_dir().join(format!("inkwell-attachments-{}", std::process::id()));
+        let dir = root.join("files");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.csv"), "a,b").unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let sources = FileSources { dir: Some(dir.clone()), remote: false };
+        assert_eq!(load_file("report.csv", &sources).unwrap().0, b"a,b");
+        assert_eq!(load_file(dir.join("report.csv").to_str().unwrap(), &sources).unwrap().0, b"a,b");
+        assert!(load_file("../secret.txt", &sources).unwrap_err().contains("outside"));
+        assert!(load_file(root.join("secret.txt").to_str().unwrap(), &sources).unwrap_err().contains("outside"));
+        assert!(load_file("https://example.com/report.csv", &sources).unwrap_err().contains(REMOTE_ATTACHMENTS_VAR));
+
+        // Without a directory only data URIs are embedded
+        let sources = FileSources { dir: None, remote: false };
+        assert!(load_file("report.csv", &sources).unwrap_err().contains(ATTACHMENT_DIR_VAR));
+        assert_eq!(load_file("data:text/csv,a%2Cb", &sources).unwrap(), (b"a,b".to_vec(), Some("text/csv".to_string())));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
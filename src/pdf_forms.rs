@@ -0,0 +1,668 @@
+//! Interactive form fields (AcroForm).
+//!
+//! Field nodes are drawn as `/InkField << ... >> DP` marks holding the field's
+//! settings and rectangle. Once every page is finished they become widget
+//! annotations, with appearance streams for their initial state so they look
+//! the same in every viewer, and the fields are listed in the catalog's
+//! AcroForm. Widgets sharing a name (the buttons of a radio group, or a field
+//! repeated on every page) are kids of one field, showing one value.
+//!
+//! Field text is set in the standard Helvetica (check marks in ZapfDingbats),
+//! which viewers also use when the reader types, so forms can't be PDF/A, where
+//! every font must be embedded.
+
+use std::collections::HashMap;
+
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::font_metrics;
+use crate::layout::{Color, DocumentOptions, JsonNode, NodeType, TextAlign};
+use crate::layout_box::{LayoutBox, FIELD_PADDING};
+use crate::pdf_attachments::half_circle;
+use crate::pdf_resources::{self, text_string};
+
+// Field flags (Ff)
+const READ_ONLY: i64 = 1;
+const REQUIRED: i64 = 1 << 1;
+const MULTILINE: i64 = 1 << 12;
+const NO_TOGGLE_TO_OFF: i64 = 1 << 14;
+const RADIO: i64 = 1 << 15;
+const COMBO: i64 = 1 << 17;
+
+/// Border of fields that don't set one, like a browser input's
+const DEFAULT_BORDER: (f32, [f32; 3]) = (0.75, [0.6, 0.6, 0.6]);
+
+/// One widget of a form field, as drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    pub kind: NodeType,
+    pub name: String,
+    pub value: Option<String>,
+    pub checked: bool,
+    pub options: Vec<String>,
+    /// The option this widget is the button of, in a radio group
+    pub option: Option<usize>,
+    pub required: bool,
+    pub read_only: bool,
+    pub multiline: bool,
+    pub font_size: f32,
+    pub color: [f32; 3],
+    pub align: TextAlign,
+    /// Width and color
+    pub border: Option<(f32, [f32; 3])>,
+    pub background: Option<[f32; 3]>,
+    /// Description screen readers announce
+    pub tooltip: Option<String>,
+}
+
+fn rgb(color: &Color) -> [f32; 3] {
+    [color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0]
+}
+
+impl FormField {
+    /// The field of a form field node; radio groups have one per option
+    pub fn from_layout(layout: &LayoutBox, option: Option<usize>) -> FormField {
+        let node = &layout.node;
+        let style = &node.style;
+        let border = if style.border_width.is_none() && style.border.is_none() {
+            Some(DEFAULT_BORDER)
+        } else {
+            let color = style.border_color().unwrap_or_else(Color::black);
+            Some((style.border_width(), rgb(&color))).filter(|(width, _)| *width > 0.0)
+        };
+        FormField {
+            kind: node.node_type.clone(),
+            name: node.name.clone().unwrap_or_default(),
+            value: node.value.clone(),
+            checked: node.checked == Some(true),
+            options: node.options.clone().unwrap_or_default(),
+            option,
+            required: node.required == Some(true),
+            read_only: node.read_only == Some(true),
+            multiline: node.multiline == Some(true),
+            font_size: layout.font_size(),
+            color: rgb(&style.color.clone().unwrap_or_else(Color::black)),
+            align: layout.text_align(),
+            border,
+            background: style.background_color.as_ref().filter(|c| c.a > 0.0).map(rgb),
+            tooltip: layout.semantics.alt.clone().or_else(|| node.name.clone()),
+        }
+    }
+
+    pub fn to_properties(&self) -> Dictionary {
+        let string = |value: &str| Object::String(value.as_bytes().to_vec(), StringFormat::Literal);
+        let color = |c: [f32; 3]| Object::Array(c.into_iter().map(Object::Real).collect());
+        let kind: &[u8] = match self.kind {
+            NodeType::Checkbox => b"Checkbox",
+            NodeType::RadioGroup => b"Radio",
+            NodeType::Dropdown => b"Dropdown",
+            NodeType::SignatureField => b"Signature",
+            _ => b"Text",
+        };
+        let align: &[u8] = match self.align {
+            TextAlign::Center => b"Center",
+            TextAlign::Right => b"Right",
+            _ => b"Left",
+        };
+
+        let mut properties = Dictionary::new();
+        properties.set("Kind", Object::Name(kind.to_vec()));
+        properties.set("Name", string(&self.name));
+        if let Some(ref value) = self.value {
+            properties.set("Value", string(value));
+        }
+        properties.set("Checked", Object::Boolean(self.checked));
+        properties.set("Options", Object::Array(self.options.iter().map(|o| string(o)).collect()));
+        if let Some(option) = self.option {
+            properties.set("Option", Object::Integer(option as i64));
+        }
+        properties.set("Required", Object::Boolean(self.required));
+        properties.set("ReadOnly", Object::Boolean(self.read_only));
+        properties.set("Multiline", Object::Boolean(self.multiline));
+        properties.set("FontSize", Object::Real(self.font_size));
+        properties.set("Color", color(self.color));
+        properties.set("Align", Object::Name(align.to_vec()));
+        if let Some((width, border)) = self.border {
+            properties.set("BorderWidth", Object::Real(width));
+            properties.set("BorderColor", color(border));
+        }
+        if let Some(background) = self.background {
+            properties.set("Background", color(background));
+        }
+        if let Some(ref tooltip) = self.tooltip {
+            properties.set("Tooltip", string(tooltip));
+        }
+        properties
+    }
+
+    pub fn from_properties(properties: &Dictionary) -> Option<FormField> {
+        let string = |key: &[u8]| {
+            properties.get(key).ok()
+                .and_then(|o| o.as_str().ok())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+        };
+        let flag = |key: &[u8]| properties.get(key).and_then(|o| o.as_bool()).unwrap_or(false);
+        let color = |key: &[u8]| -> Option<[f32; 3]> {
+            let values: Vec<f32> = properties.get(key).ok()?
+                .as_array().ok()?
+                .iter()
+                .filter_map(|o| o.as_float().ok())
+                .collect();
+            values.try_into().ok()
+        };
+        let kind = match properties.get(b"Kind").and_then(|o| o.as_name()).ok()? {
+            b"Checkbox" => NodeType::Checkbox,
+            b"Radio" => NodeType::RadioGroup,
+            b"Dropdown" => NodeType::Dropdown,
+            b"Signature" => NodeType::SignatureField,
+            _ => NodeType::TextField,
+        };
+        let align = match properties.get(b"Align").and_then(|o| o.as_name()) {
+            Ok(b"Center") => TextAlign::Center,
+            Ok(b"Right") => TextAlign::Right,
+            _ => TextAlign::Left,
+        };
+        let options = properties.get(b"Options")
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(|o| o.as_str().ok()).map(|s| String::from_utf8_lossy(s).into_owned()).collect())
+            .unwrap_or_default();
+        let border_width = properties.get(b"BorderWidth").and_then(|o| o.as_float()).ok();
+
+        Some(FormField {
+            kind,
+            name: string(b"Name")?,
+            value: string(b"Value"),
+            checked: flag(b"Checked"),
+            options,
+            option: properties.get(b"Option").and_then(|o| o.as_i64()).ok().map(|i| i as usize),
+            required: flag(b"Required"),
+            read_only: flag(b"ReadOnly"),
+            multiline: flag(b"Multiline"),
+            font_size: properties.get(b"FontSize").and_then(|o| o.as_float()).unwrap_or(12.0),
+            color: color(b"Color").unwrap_or([0.0; 3]),
+            align,
+            border: border_width.zip(color(b"BorderColor")),
+            background: color(b"Background"),
+            tooltip: string(b"Tooltip"),
+        })
+    }
+
+    /// Variable text settings: font, size and color
+    fn default_appearance(&self) -> String {
+        let [r, g, b] = self.color;
+        format!("/Helv {} Tf {} {} {} rg", self.font_size, r, g, b)
+    }
+}
+
+/// Forms use fonts PDF/A would need embedded, so they can't be combined
+pub fn check_conformance(root: &JsonNode, options: &DocumentOptions) -> Result<(), String> {
+    fn has_field(node: &JsonNode) -> bool {
+        node.node_type.is_form_field() || node.children.iter().any(has_field)
+    }
+    if options.conformance.is_some() && has_field(root) {
+        return Err("form fields use the standard fonts, which can't be embedded for PDF/A".to_string());
+    }
+    Ok(())
+}
+
+/// Add the widgets of the field marks (page, rectangle, field and structure
+/// element) and the AcroForm listing their fields. Returns the page, widget
+/// and structure element of each.
+pub fn apply(
+    doc: &mut Document,
+    placed: Vec<(ObjectId, [f32; 4], FormField, Option<usize>)>,
+) -> Result<Vec<(ObjectId, ObjectId, Option<usize>)>, String> {
+    if placed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut helvetica = Dictionary::new();
+    helvetica.set("Type", Object::Name(b"Font".to_vec()));
+    helvetica.set("Subtype", Object::Name(b"Type1".to_vec()));
+    helvetica.set("BaseFont", Object::Name(b"Helvetica".to_vec()));
+    helvetica.set("Encoding", Object::Name(b"WinAnsiEncoding".to_vec()));
+    let mut dingbats = Dictionary::new();
+    dingbats.set("Type", Object::Name(b"Font".to_vec()));
+    dingbats.set("Subtype", Object::Name(b"Type1".to_vec()));
+    dingbats.set("BaseFont", Object::Name(b"ZapfDingbats".to_vec()));
+    let mut fonts = Dictionary::new();
+    fonts.set("Helv", Object::Reference(doc.add_object(helvetica)));
+    fonts.set("ZaDb", Object::Reference(doc.add_object(dingbats)));
+    let mut resources = Dictionary::new();
+    resources.set("Font", Object::Dictionary(fonts));
+    let resources_id = doc.add_object(resources);
+
+    // Widgets of the same name make up one field, in document order
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    for (i, (_, _, field, _)) in placed.iter().enumerate() {
+        let mut name = if field.name.is_empty() {
+            eprintln!("Form field without a name, calling it {:?}", format!("field{}", groups.len() + 1));
+            format!("field{}", groups.len() + 1)
+        } else {
+            field.name.clone()
+        };
+        if let Some(&group) = by_name.get(&name) {
+            let first = &placed[groups[group].1[0]].2;
+            if first.kind != field.kind || first.options != field.options {
+                eprintln!("Form fields named {:?} differ, renaming one", name);
+                name = format!("{}_{}", name, groups.len() + 1);
+            }
+        }
+        match by_name.get(&name) {
+            Some(&group) => groups[group].1.push(i),
+            None => {
+                by_name.insert(name.clone(), groups.len());
+                groups.push((name, vec![i]));
+            }
+        }
+    }
+
+    let mut fields = Vec::new();
+    let mut annotations: HashMap<ObjectId, Vec<Object>> = HashMap::new();
+    let mut widgets = Vec::new();
+    for (name, members) in groups {
+        let field = &placed[members[0]].2;
+        let mut dict = field_dict(&name, field);
+
+        let mut kids = Vec::new();
+        for &i in &members {
+            let (page_id, rect, ref widget_field, element) = placed[i];
+            let mut widget = widget_dict(doc, resources_id, rect, widget_field);
+            widget.set("P", Object::Reference(page_id));
+            kids.push((page_id, widget, element));
+        }
+
+        // A field with one widget is a single dictionary, others have kids
+        if kids.len() == 1 && field.kind != NodeType::RadioGroup {
+            let (page_id, mut widget, element) = kids.pop().unwrap();
+            for (key, value) in &dict {
+                widget.set(key.clone(), value.clone());
+            }
+            let id = doc.add_object(widget);
+            annotations.entry(page_id).or_default().push(Object::Reference(id));
+            widgets.push((page_id, id, element));
+            fields.push(Object::Reference(id));
+            continue;
+        }
+        let field_id = doc.new_object_id();
+        let mut refs = Vec::new();
+        for (page_id, mut widget, element) in kids {
+            widget.set("Parent", Object::Reference(field_id));
+            let id = doc.add_object(widget);
+            annotations.entry(page_id).or_default().push(Object::Reference(id));
+            widgets.push((page_id, id, element));
+            refs.push(Object::Reference(id));
+        }
+        dict.set("Kids", Object::Array(refs));
+        doc.objects.insert(field_id, Object::Dictionary(dict));
+        fields.push(Object::Reference(field_id));
+    }
+    pdf_resources::add_annotations(doc, annotations)?;
+
+    let mut form = Dictionary::new();
+    form.set("Fields", Object::Array(fields));
+    form.set("DR", Object::Reference(resources_id));
+    form.set("DA", Object::string_literal("/Helv 0 Tf 0 g"));
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    catalog.set("AcroForm", Object::Dictionary(form));
+    Ok(widgets)
+}
+
+/// The field's type, name, flags and value
+fn field_dict(name: &str, field: &FormField) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("T", text_string(name));
+    if let Some(ref tooltip) = field.tooltip {
+        dict.set("TU", text_string(tooltip));
+    }
+    let mut flags = 0;
+    if field.read_only {
+        flags |= READ_ONLY;
+    }
+    if field.required {
+        flags |= REQUIRED;
+    }
+
+    match field.kind {
+        NodeType::Checkbox => {
+            dict.set("FT", Object::Name(b"Btn".to_vec()));
+            let state: &[u8] = if field.checked { b"Yes" } else { b"Off" };
+            dict.set("V", Object::Name(state.to_vec()));
+            dict.set("DV", Object::Name(state.to_vec()));
+        }
+        NodeType::RadioGroup => {
+            dict.set("FT", Object::Name(b"Btn".to_vec()));
+            flags |= RADIO | NO_TOGGLE_TO_OFF;
+            // Buttons are named by their option's index, and Opt maps them to the option text
+            let chosen = field.value.as_ref().and_then(|value| {
+                let index = field.options.iter().position(|o| o == value);
+                if index.is_none() {
+                    eprintln!("Radio group {:?} has value {:?}, which isn't one of its options", name, value);
+                }
+                index
+            });
+            let state = chosen.map_or(b"Off".to_vec(), |i| i.to_string().into_bytes());
+            dict.set("V", Object::Name(state.clone()));
+            dict.set("DV", Object::Name(state));
+            dict.set("Opt", Object::Array(field.options.iter().map(|o| text_string(o)).collect()));
+        }
+        NodeType::Dropdown => {
+            dict.set("FT", Object::Name(b"Ch".to_vec()));
+            flags |= COMBO;
+            dict.set("Opt", Object::Array(field.options.iter().map(|o| text_string(o)).collect()));
+            if let Some(ref value) = field.value {
+                dict.set("V", text_string(value));
+                dict.set("DV", text_string(value));
+            }
+            dict.set("DA", Object::string_literal(field.default_appearance()));
+        }
+        NodeType::SignatureField => {
+            dict.set("FT", Object::Name(b"Sig".to_vec()));
+        }
+        _ => {
+            dict.set("FT", Object::Name(b"Tx".to_vec()));
+            if field.multiline {
+                flags |= MULTILINE;
+            }
+            if let Some(ref value) = field.value {
+                dict.set("V", text_string(value));
+                dict.set("DV", text_string(value));
+            }
+            dict.set("DA", Object::string_literal(field.default_appearance()));
+        }
+    }
+    if matches!(field.kind, NodeType::TextField | NodeType::Dropdown) {
+        let quadding = match field.align {
+            TextAlign::Center => 1,
+            TextAlign::Right => 2,
+            _ => 0,
+        };
+        dict.set("Q", Object::Integer(quadding));
+    }
+    if flags != 0 {
+        dict.set("Ff", Object::Integer(flags));
+    }
+    dict
+}
+
+/// The widget annotation, with its border, background and appearances
+fn widget_dict(doc: &mut Document, resources_id: ObjectId, rect: [f32; 4], field: &FormField) -> Dictionary {
+    let color = |c: [f32; 3]| Object::Array(c.into_iter().map(Object::Real).collect());
+    let (width, height) = (rect[2] - rect[0], rect[3] - rect[1]);
+
+    let mut widget = Dictionary::new();
+    widget.set("Type", Object::Name(b"Annot".to_vec()));
+    widget.set("Subtype", Object::Name(b"Widget".to_vec()));
+    widget.set("Rect", Object::Array(rect.into_iter().map(Object::Real).collect()));
+    // Printable, as PDF/A requires of every annotation
+    widget.set("F", Object::Integer(4));
+
+    // Colors viewers redraw the field with once it's edited
+    let mut characteristics = Dictionary::new();
+    if let Some((border_width, border)) = field.border {
+        characteristics.set("BC", color(border));
+        let mut style = Dictionary::new();
+        style.set("W", Object::Real(border_width));
+        style.set("S", Object::Name(b"S".to_vec()));
+        widget.set("BS", Object::Dictionary(style));
+    } else {
+        let mut style = Dictionary::new();
+        style.set("W", Object::Integer(0));
+        widget.set("BS", Object::Dictionary(style));
+    }
+    if let Some(background) = field.background {
+        characteristics.set("BG", color(background));
+    }
+
+    let mut add_appearance = |content: String| {
+        let mut form = Dictionary::new();
+        form.set("Type", Object::Name(b"XObject".to_vec()));
+        form.set("Subtype", Object::Name(b"Form".to_vec()));
+        form.set("BBox", Object::Array(vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)]));
+        form.set("Resources", Object::Reference(resources_id));
+        Object::Reference(doc.add_object(Stream::new(form, content.into_bytes())))
+    };
+    let mut appearance = Dictionary::new();
+    match field.kind {
+        NodeType::Checkbox | NodeType::RadioGroup => {
+            let radio = field.kind == NodeType::RadioGroup;
+            let on = match (radio, field.option) {
+                (true, Some(option)) => option.to_string().into_bytes(),
+                _ => b"Yes".to_vec(),
+            };
+            let is_on = if radio {
+                field.option.is_some() && field.value.as_ref() == field.option.and_then(|i| field.options.get(i))
+            } else {
+                field.checked
+            };
+            // ZapfDingbats check mark and dot, used by viewers that redraw the button
+            let mark: &[u8] = if radio { b"l" } else { b"4" };
+            characteristics.set("CA", Object::String(mark.to_vec(), StringFormat::Literal));
+
+            let mut states = Dictionary::new();
+            states.set(on.clone(), add_appearance(button_appearance(field, width, height, true)));
+            states.set("Off", add_appearance(button_appearance(field, width, height, false)));
+            appearance.set("N", Object::Dictionary(states));
+            widget.set("AS", Object::Name(if is_on { on } else { b"Off".to_vec() }));
+        }
+        _ => {
+            let text = match field.kind {
+                NodeType::TextField | NodeType::Dropdown => field.value.as_deref(),
+                _ => None,
+            };
+            appearance.set("N", add_appearance(text_appearance(field, width, height, text)));
+        }
+    }
+    widget.set("MK", Object::Dictionary(characteristics));
+    widget.set("AP", Object::Dictionary(appearance));
+    widget
+}
+
+/// Background and border of a widget, round for radio buttons
+fn frame(field: &FormField, width: f32, height: f32) -> String {
+    let mut out = String::new();
+    let radio = field.kind == NodeType::RadioGroup;
+    let border_width = field.border.map_or(0.0, |(w, _)| w);
+    let shape = |inset: f32| {
+        if radio {
+            let r = (width.min(height) / 2.0 - inset).max(0.0);
+            let mut path = format!("{:.3} {:.3} m\n", width / 2.0 + r, height / 2.0);
+            half_circle(&mut path, width / 2.0, height / 2.0, r, 0.0);
+            half_circle(&mut path, width / 2.0, height / 2.0, r, 180.0);
+            path
+        } else {
+            format!("{} {} {} {} re\n", inset, inset, width - 2.0 * inset, height - 2.0 * inset)
+        }
+    };
+    if let Some([r, g, b]) = field.background {
+        out.push_str(&format!("{} {} {} rg\n{}f\n", r, g, b, shape(0.0)));
+    }
+    if let Some((w, [r, g, b])) = field.border {
+        out.push_str(&format!("{} {} {} RG\n{} w\n{}S\n", r, g, b, w, shape(border_width / 2.0)));
+    }
+    out
+}
+
+/// A check box or radio button, checked or not
+fn button_appearance(field: &FormField, width: f32, height: f32, on: bool) -> String {
+    let mut out = frame(field, width, height);
+    if !on {
+        return out;
+    }
+    let [r, g, b] = field.color;
+    let side = width.min(height);
+    if field.kind == NodeType::RadioGroup {
+        let radius = side / 4.0;
+        let mut path = format!("{:.3} {:.3} m\n", width / 2.0 + radius, height / 2.0);
+        half_circle(&mut path, width / 2.0, height / 2.0, radius, 0.0);
+        half_circle(&mut path, width / 2.0, height / 2.0, radius, 180.0);
+        out.push_str(&format!("{} {} {} rg\n{}f\n", r, g, b, path));
+    } else {
+        // The check mark glyph is 0.846 em wide and 0.695 em high
+        let size = side * 0.8;
+        out.push_str(&format!(
+            "q\nBT\n/ZaDb {} Tf\n{} {} {} rg\n{} {} Td\n(4) Tj\nET\nQ\n",
+            size, r, g, b,
+            (width - 0.846 * size) / 2.0,
+            (height - 0.695 * size) / 2.0
+        ));
+    }
+    out
+}
+
+/// A text field, dropdown or signature field showing `text`
+fn text_appearance(field: &FormField, width: f32, height: f32, text: Option<&str>) -> String {
+    let mut out = frame(field, width, height);
+    let Some(text) = text.filter(|t| !t.is_empty()) else {
+        return out;
+    };
+
+    let metrics = font_metrics::get_metrics(false, false);
+    let size = field.font_size;
+    let ascent = metrics.ascent(size);
+    let descent = -(metrics.descender as f32) / metrics.units_per_em as f32 * size;
+    let inset = field.border.map_or(0.0, |(w, _)| w) + FIELD_PADDING;
+    let line_height = size * 1.15;
+    // One line is centered vertically, several start at the top
+    let lines: Vec<&str> = if field.multiline { text.lines().collect() } else { vec![text] };
+    let first = if field.multiline {
+        height - inset - ascent
+    } else {
+        (height - ascent - descent) / 2.0 + descent
+    };
+
+    let [r, g, b] = field.color;
+    out.push_str(&format!(
+        "/Tx BMC\nq\n{0} {0} {1} {2} re\nW\nn\nBT\n/Helv {3} Tf\n{4} {5} {6} rg\n",
+        inset / 2.0,
+        width - inset,
+        height - inset,
+        size, r, g, b
+    ));
+    for (i, line) in lines.iter().enumerate() {
+        let line_width = metrics.string_width(line, size);
+        let x = match field.align {
+            TextAlign::Center => (width - line_width) / 2.0,
+            TextAlign::Right => width - inset - line_width,
+            _ => inset,
+        };
+        out.push_str(&format!("1 0 0 1 {} {} Tm\n{} Tj\n", x, first - i as f32 * line_height, win_ansi_literal(line)));
+    }
+    out.push_str("ET\nQ\nEMC\n");
+    out
+}
+
+/// A literal string in WinAnsiEncoding, the encoding of the form font; other
+/// characters show as `?`
+fn win_ansi_literal(text: &str) -> String {
+    let mut out = String::from("(");
+    for c in text.chars() {
+        let byte = match c {
+            '€' => 0x80,
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            _ => b'?',
+        };
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_field(name: &str) -> FormField {
+        FormField {
+            kind: NodeType::TextField,
+            name: name.to_string(),
+            value: Some("Ada".to_string()),
+            checked: false,
+            options: Vec::new(),
+            option: None,
+            required: true,
+            read_only: false,
+            multiline: false,
+            font_size: 10.0,
+            color: [0.0; 3],
+            align: TextAlign::Left,
+            border: Some(DEFAULT_BORDER),
+            background: None,
+            tooltip: Some("First name".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_field_properties_round_trip() {
+        let field = FormField {
+            kind: NodeType::RadioGroup,
+            options: vec!["Yes".to_string(), "No".to_string()],
+            option: Some(1),
+            background: Some([1.0, 1.0, 0.9]),
+            ..text_field("consent")
+        };
+        assert_eq!(FormField::from_properties(&field.to_properties()), Some(field));
+    }
+
+    #[test]
+    fn test_win_ansi_literal() {
+        assert_eq!(win_ansi_literal("a(b)\\"), "(a\\(b\\)\\\\)");
+        assert_eq!(win_ansi_literal("Grüße €5 漢"), "(Gr\\374\\337e \\2005 ?)");
+    }
+
+    #[test]
+    fn test_widgets_sharing_a_name_become_kids() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let mut page = Dictionary::new();
+        page.set("Type", Object::Name(b"Page".to_vec()));
+        page.set("Parent", Object::Reference(pages_id));
+        let page_id = doc.add_object(page);
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+        catalog.set("Pages", Object::Reference(pages_id));
+        let catalog_id = doc.add_object(catalog);
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+
+        let radio = |option| FormField {
+            kind: NodeType::RadioGroup,
+            value: Some("No".to_string()),
+            options: vec!["Yes".to_string(), "No".to_string()],
+            option: Some(option),
+            ..text_field("consent")
+        };
+        let placed = vec![
+            (page_id, [0.0, 0.0, 100.0, 20.0], text_field("first"), None),
+            (page_id, [0.0, 30.0, 10.0, 40.0], radio(0), Some(3)),
+            (page_id, [0.0, 50.0, 10.0, 60.0], radio(1), Some(3)),
+        ];
+        let widgets = apply(&mut doc, placed).unwrap();
+        assert_eq!(widgets.len(), 3);
+        assert_eq!(widgets[1].2, Some(3));
+
+        let form = doc.catalog().unwrap().get(b"AcroForm").unwrap().as_dict().unwrap();
+        let fields = form.get(b"Fields").unwrap().as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+
+        let text = doc.get_dictionary(fields[0].as_reference().unwrap()).unwrap();
+        assert_eq!(text.get(b"FT").unwrap().as_name().unwrap(), b"Tx");
+        assert_eq!(text.get(b"Ff").unwrap().as_i64().unwrap(), REQUIRED);
+        assert_eq!(text.get(b"Subtype").unwrap().as_name().unwrap(), b"Widget");
+
+        let group = doc.get_dictionary(fields[1].as_reference().unwrap()).unwrap();
+        assert_eq!(group.get(b"V").unwrap().as_name().unwrap(), b"1");
+        let kids = group.get(b"Kids").unwrap().as_array().unwrap();
+        let second = doc.get_dictionary(kids[1].as_reference().unwrap()).unwrap();
+        assert_eq!(second.get(b"AS").unwrap().as_name().unwrap(), b"1");
+        let first = doc.get_dictionary(kids[0].as_reference().unwrap()).unwrap();
+        assert_eq!(first.get(b"AS").unwrap().as_name().unwrap(), b"Off");
+    }
+}
@@ -0,0 +1,412 @@
+//! Document-level settings applied to the saved PDF: the Info dictionary, the
+//! matching XMP metadata stream (with the Factur-X properties of an embedded
+//! e-invoice), the document language, viewer preferences and PDF/A conformance.
+//!
+//! printpdf only writes XMP for some conformance levels and has no setting for
+//! the language or viewer preferences, so these are written with lopdf.
+
+use printpdf::lopdf::{Dictionary, Document, Object, Stream};
+
+use crate::layout::{DocumentOptions, InvoiceProfile, Metadata, ViewerPreferences};
+use crate::pdf_a;
+use crate::pdf_resources::text_string;
+
+pub fn apply(doc: &mut Document, options: &DocumentOptions) -> Result<(), String> {
+    // PDF/A requires XMP metadata, even if there is nothing to put in it but the dates
+    if options.metadata.is_some() || options.conformance.is_some() {
+        let metadata = options.metadata.clone().unwrap_or_default();
+        write_metadata(doc, &metadata, options)?;
+    }
+    if let Some(ref preferences) = options.viewer_preferences {
+        write_viewer_preferences(doc, preferences)?;
+    }
+    if options.conformance.is_some() {
+        pdf_a::apply(doc)?;
+    }
+    Ok(())
+}
+
+/// Replace the Info dictionary with the given metadata (keeping printpdf's dates
+/// as defaults) and add the same fields as XMP
+fn write_metadata(doc: &mut Document, metadata: &Metadata, options: &DocumentOptions) -> Result<(), String> {
+    let info_id = doc.trailer.get(b"Info").and_then(|o| o.as_reference()).ok();
+    let existing = info_id.and_then(|id| doc.get_dictionary(id).ok());
+    let existing_date = |key: &[u8]| {
+        existing
+            .and_then(|info| info.get(key).ok())
+            .and_then(|o| o.as_str().ok())
+            .and_then(|s| DateTime::parse_pdf(&String::from_utf8_lossy(s)))
+    };
+    let date = |value: &Option<String>, key: &[u8]| match value {
+        Some(iso) => DateTime::parse_iso(iso).or_else(|| {
+            eprintln!("Invalid date {:?}, using the current time", iso);
+            existing_date(key)
+        }),
+        None => existing_date(key),
+    };
+    let created = date(&metadata.creation_date, b"CreationDate");
+    let modified = date(&metadata.modification_date, b"ModDate").or_else(|| created.clone());
+    let keywords = metadata.keywords.as_ref().map(|k| k.join(", "));
+
+    let mut info = Dictionary::new();
+    let fields = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Subject", &metadata.subject),
+        ("Keywords", &keywords),
+        ("Creator", &metadata.creator),
+        ("Producer", &metadata.producer),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            info.set(key, text_string(value));
+        }
+    }
+    if let Some(ref created) = created {
+        info.set("CreationDate", Object::string_literal(created.to_pdf()));
+    }
+    if let Some(ref modified) = modified {
+        info.set("ModDate", Object::string_literal(modified.to_pdf()));
+    }
+    match info_id {
+        Some(id) => {
+            doc.objects.insert(id, Object::Dictionary(info));
+        }
+        None => {
+            let id = doc.add_object(info);
+            doc.trailer.set("Info", Object::Reference(id));
+        }
+    }
+
+    let xmp = xmp_packet(metadata, keywords.as_deref(), created.as_ref(), modified.as_ref(), options);
+    let mut stream_dict = Dictionary::new();
+    stream_dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    stream_dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    // Left uncompressed so tools that don't parse PDF can still find it
+    let stream = Stream::new(stream_dict, xmp.into_bytes()).with_compression(false);
+    let xmp_id = doc.add_object(stream);
+
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    catalog.set("Metadata", Object::Reference(xmp_id));
+    if let Some(ref language) = metadata.language {
+        catalog.set("Lang", text_string(language));
+    }
+    Ok(())
+}
+
+fn write_viewer_preferences(doc: &mut Document, preferences: &ViewerPreferences) -> Result<(), String> {
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    if let Some(ref layout) = preferences.page_layout {
+        catalog.set("PageLayout", Object::Name(layout.pdf_name().as_bytes().to_vec()));
+    }
+    if let Some(open) = preferences.open_outline {
+        let mode: &[u8] = if open { b"UseOutlines" } else { b"UseNone" };
+        catalog.set("PageMode", Object::Name(mode.to_vec()));
+    }
+    if let Some(display) = preferences.display_doc_title {
+        let mut viewer = match catalog.get(b"ViewerPreferences") {
+            Ok(Object::Dictionary(dict)) => dict.clone(),
+            _ => Dictionary::new(),
+        };
+        viewer.set("DisplayDocTitle", Object::Boolean(display));
+        catalog.set("ViewerPreferences", Object::Dictionary(viewer));
+    }
+    Ok(())
+}
+
+/// XMP packet with the Dublin Core, XMP basic and Adobe PDF properties that
+/// mirror the Info dictionary, the PDF/A identification and the Factur-X
+/// description of an embedded invoice
+fn xmp_packet(
+    metadata: &Metadata,
+    keywords: Option<&str>,
+    created: Option<&DateTime>,
+    modified: Option<&DateTime>,
+    options: &DocumentOptions,
+) -> String {
+    let mut properties = String::from("      <dc:format>application/pdf</dc:format>\n");
+    let mut add = |name: &str, value: String| {
+        properties.push_str(&format!("      <{0}>{1}</{0}>\n", name, value));
+    };
+    let alt = |text: &str| {
+        format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>", xml_escape(text))
+    };
+
+    if let Some(ref title) = metadata.title {
+        add("dc:title", alt(title));
+    }
+    if let Some(ref author) = metadata.author {
+        add("dc:creator", format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", xml_escape(author)));
+    }
+    if let Some(ref subject) = metadata.subject {
+        add("dc:description", alt(subject));
+    }
+    if let Some(ref language) = metadata.language {
+        add("dc:language", format!("<rdf:Bag><rdf:li>{}</rdf:li></rdf:Bag>", xml_escape(language)));
+    }
+    if let Some(keywords) = keywords {
+        add("pdf:Keywords", xml_escape(keywords));
+    }
+    if let Some(ref producer) = metadata.producer {
+        add("pdf:Producer", xml_escape(producer));
+    }
+    if let Some(ref creator) = metadata.creator {
+        add("xmp:CreatorTool", xml_escape(creator));
+    }
+    if let Some(created) = created {
+        add("xmp:CreateDate", created.to_iso());
+    }
+    if let Some(modified) = modified {
+        add("xmp:ModifyDate", modified.to_iso());
+        add("xmp:MetadataDate", modified.to_iso());
+    }
+    if let Some(conformance) = options.conformance {
+        add("pdfaid:part", conformance.part().to_string());
+        add("pdfaid:conformance", "B".to_string());
+    }
+    let invoice = options.invoice.as_ref().map(|invoice| {
+        factur_x_description(invoice.profile.unwrap_or(InvoiceProfile::En16931))
+    });
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n  \
+           <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n    \
+             <rdf:Description rdf:about=\"\"\n        \
+               xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n        \
+               xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n        \
+               xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"\n        \
+               xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n\
+         {}    </rdf:Description>\n\
+         {}  </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        properties,
+        invoice.unwrap_or_default()
+    )
+}
+
+/// The Factur-X properties, and the extension schema PDF/A needs to accept them
+fn factur_x_description(profile: InvoiceProfile) -> String {
+    const NAMESPACE: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+    let properties = [
+        ("DocumentFileName", "The name of the embedded XML document", profile.file_name()),
+        ("DocumentType", "The type of the hybrid document", "INVOICE"),
+        ("Version", "The version of the Factur-X XML schema", "1.0"),
+        ("ConformanceLevel", "The conformance level of the embedded data", profile.level_name()),
+    ];
+
+    let mut values = String::new();
+    let mut schema = String::new();
+    for (name, description, value) in properties {
+        values.push_str(&format!("      <fx:{0}>{1}</fx:{0}>\n", name, value));
+        schema.push_str(&format!(
+            "                <rdf:li rdf:parseType=\"Resource\">\n                  \
+                  <pdfaProperty:name>{}</pdfaProperty:name>\n                  \
+                  <pdfaProperty:valueType>Text</pdfaProperty:valueType>\n                  \
+                  <pdfaProperty:category>external</pdfaProperty:category>\n                  \
+                  <pdfaProperty:description>{}</pdfaProperty:description>\n                \
+                </rdf:li>\n",
+            name, description
+        ));
+    }
+
+    format!(
+        "    <rdf:Description rdf:about=\"\" xmlns:fx=\"{0}\">\n\
+         {1}    </rdf:Description>\n    \
+             <rdf:Description rdf:about=\"\"\n        \
+               xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\"\n        \
+               xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\"\n        \
+               xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\n      \
+                 <pdfaExtension:schemas>\n        \
+                   <rdf:Bag>\n          \
+                     <rdf:li rdf:parseType=\"Resource\">\n            \
+                       <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\n            \
+                       <pdfaSchema:namespaceURI>{0}</pdfaSchema:namespaceURI>\n            \
+                       <pdfaSchema:prefix>fx</pdfaSchema:prefix>\n            \
+                       <pdfaSchema:property>\n              \
+                         <rdf:Seq>\n\
+         {2}              </rdf:Seq>\n            \
+                       </pdfaSchema:property>\n          \
+                     </rdf:li>\n        \
+                   </rdf:Bag>\n      \
+                 </pdfaExtension:schemas>\n    \
+             </rdf:Description>\n",
+        NAMESPACE, values, schema
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// ============================================================================
+// DATES
+// ============================================================================
+
+/// A date and time, with its offset from UTC in minutes when known
+#[derive(Debug, Clone, PartialEq)]
+struct DateTime {
+    year: u32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    offset: Option<i32>,
+}
+
+impl DateTime {
+    /// ISO 8601, as in "2024-05-01", "2024-05-01T10:30" or "2024-05-01T10:30:00.5+02:00"
+    fn parse_iso(value: &str) -> Option<DateTime> {
+        let value = value.trim();
+        let (date, time) = value.split_once(['T', ' ']).unwrap_or((value, ""));
+
+        let mut parts = date.split('-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let (clock, offset) = if let Some(clock) = time.strip_suffix('Z') {
+            (clock, Some(0))
+        } else if let Some(i) = time.rfind(['+', '-']) {
+            let sign = if time[i..].starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = time[i + 1..].split_once(':').unwrap_or((&time[i + 1..], "0"));
+            let minutes = hours.parse::<i32>().ok()? * 60 + minutes.parse::<i32>().ok()?;
+            (&time[..i], Some(sign * minutes))
+        } else {
+            (time, None)
+        };
+
+        let mut fields = clock.split(':').filter(|f| !f.is_empty());
+        let hour = fields.next().map_or(Some(0), |f| f.parse().ok())?;
+        let minute = fields.next().map_or(Some(0), |f| f.parse().ok())?;
+        let second = fields.next().map_or(Some(0.0), |f| f.parse::<f32>().ok())? as u32;
+
+        DateTime { year, month, day, hour, minute, second, offset }.validated()
+    }
+
+    /// PDF date string, as in "D:20240501103000+02'00'"
+    fn parse_pdf(value: &str) -> Option<DateTime> {
+        let value = value.strip_prefix("D:").unwrap_or(value);
+        let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+        let (stamp, zone) = value.split_at(digits);
+        let field = |start: usize, len: usize, default: u32| {
+            stamp.get(start..start + len).map_or(Some(default), |s| s.parse().ok())
+        };
+
+        let offset = match zone.chars().next() {
+            Some('Z') => Some(0),
+            Some(sign @ ('+' | '-')) => {
+                let numbers: Vec<i32> = zone[1..].split('\'').filter_map(|n| n.parse().ok()).collect();
+                let minutes = numbers.first().copied().unwrap_or(0) * 60 + numbers.get(1).copied().unwrap_or(0);
+                Some(if sign == '-' { -minutes } else { minutes })
+            }
+            _ => None,
+        };
+
+        DateTime {
+            year: field(0, 4, 0)?,
+            month: field(4, 2, 1)?,
+            day: field(6, 2, 1)?,
+            hour: field(8, 2, 0)?,
+            minute: field(10, 2, 0)?,
+            second: field(12, 2, 0)?,
+            offset,
+        }
+        .validated()
+    }
+
+    fn validated(self) -> Option<DateTime> {
+        let valid = self.year >= 1000
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+            && self.offset.is_none_or(|o| o.abs() < 24 * 60);
+        valid.then_some(self)
+    }
+
+    fn to_pdf(&self) -> String {
+        let offset = match self.offset {
+            Some(0) => "Z".to_string(),
+            Some(o) => format!("{}{:02}'{:02}'", if o < 0 { '-' } else { '+' }, o.abs() / 60, o.abs() % 60),
+            None => String::new(),
+        };
+        format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}{}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, offset
+        )
+    }
+
+    fn to_iso(&self) -> String {
+        let offset = match self.offset {
+            Some(0) => "Z".to_string(),
+            Some(o) => format!("{}{:02}:{:02}", if o < 0 { '-' } else { '+' }, o.abs() / 60, o.abs() % 60),
+            None => String::new(),
+        };
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, offset
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::{Mm, PdfDocument};
+
+    #[test]
+    fn test_dates_convert_between_iso_and_pdf() {
+        let date = DateTime::parse_iso("2024-05-01T10:30:15.25-02:30").unwrap();
+        assert_eq!(date.to_pdf(), "D:20240501103015-02'30'");
+        assert_eq!(DateTime::parse_pdf(&date.to_pdf()), Some(date));
+        assert_eq!(DateTime::parse_iso("2024-05-01").unwrap().to_iso(), "2024-05-01T00:00:00");
+        assert_eq!(DateTime::parse_pdf("D:20261018152625+00'00'").unwrap().to_iso(), "2026-10-18T15:26:25Z");
+        assert_eq!(DateTime::parse_iso("2024-13-01"), None);
+        assert_eq!(DateTime::parse_iso("yesterday"), None);
+    }
+
+    #[test]
+    fn test_metadata_and_viewer_preferences() {
+        let json = r#"{
+            "metadata": { "title": "Årsrapport", "author": "Ada <Finance>", "keywords": ["report", "2024"],
+                          "lang": "sv-SE", "creationDate": "2024-05-01T10:30:00Z" },
+            "viewerPreferences": { "pageLayout": "twoPageRight", "displayDocTitle": true, "openOutline": true }
+        }"#;
+        let options: DocumentOptions = serde_json::from_str(json).unwrap();
+        let (doc, _, _) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let mut doc = Document::load_mem(&doc.save_to_bytes().unwrap()).unwrap();
+        apply(&mut doc, &options).unwrap();
+
+        let info_id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = doc.get_dictionary(info_id).unwrap();
+        assert_eq!(info.get(b"Title").unwrap().as_str().unwrap(), text_string("Årsrapport").as_str().unwrap());
+        assert_eq!(info.get(b"Keywords").unwrap().as_str().unwrap(), b"report, 2024");
+        assert_eq!(info.get(b"CreationDate").unwrap().as_str().unwrap(), b"D:20240501103000Z");
+        // Not given: modified now
+        assert!(info.has(b"ModDate"));
+        assert!(!info.has(b"Subject"));
+
+        let catalog = doc.catalog().unwrap();
+        assert_eq!(catalog.get(b"Lang").unwrap().as_str().unwrap(), b"sv-SE");
+        assert_eq!(catalog.get(b"PageLayout").unwrap().as_name_str().unwrap(), "TwoPageRight");
+        assert_eq!(catalog.get(b"PageMode").unwrap().as_name_str().unwrap(), "UseOutlines");
+        let viewer = catalog.get(b"ViewerPreferences").unwrap().as_dict().unwrap();
+        assert!(viewer.get(b"DisplayDocTitle").unwrap().as_bool().unwrap());
+
+        let xmp_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = String::from_utf8(doc.get_object(xmp_id).unwrap().as_stream().unwrap().content.clone()).unwrap();
+        assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Årsrapport</rdf:li>"));
+        assert!(xmp.contains("<rdf:li>Ada &lt;Finance&gt;</rdf:li>"));
+        assert!(xmp.contains("<xmp:CreateDate>2024-05-01T10:30:00Z</xmp:CreateDate>"));
+    }
+}
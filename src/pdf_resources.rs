@@ -0,0 +1,965 @@
+//! Post-processing of the saved PDF.
+//!
+//! printpdf 0.7 can't register arbitrary page resources (ExtGStates, form
+//! XObjects), so the drawing code emits placeholders and this pass turns them
+//! into real resources with lopdf:
+//!
+//! - `gs` operators naming `InkA<fill>_<stroke>` (alphas in thousandths) get a
+//!   matching ExtGState setting `ca`/`CA`.
+//! - Marked content tagged `/InkOpacity << /ca 0.5 >> BDC ... EMC` is moved into
+//!   a form XObject with a transparency group, painted at that alpha, so
+//!   overlapping children of a translucent box don't show through each other.
+//! - `/InkShading << ... >> DP` marks a gradient; it becomes an axial or radial
+//!   shading painted with `sh` (through a luminosity soft mask if stops have alpha).
+//! - `/InkLink` and `/InkAnchor` marks are taken out of the content and become
+//!   Link annotations, once every page's anchors are known. Their coordinates go
+//!   through the `cm` transforms in effect where they were emitted.
+//! - `/InkBookmark` marks become the document outline, nested by level.
+//! - `/InkAttachment` marks become FileAttachment annotations, embedding their file.
+//! - `/InkField` marks become form field widgets (see `pdf_forms`).
+//! - `/InkElement` and `/InkArtifact` marked content becomes the structure tree
+//!   of a tagged document (see `pdf_tags`).
+
+use std::collections::{BTreeSet, HashMap};
+
+use printpdf::lopdf::content::{Content, Operation};
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::layout::{Attachment, Color, DocumentOptions};
+use crate::pdf_attachments;
+use crate::pdf_forms::{self, FormField};
+use crate::pdf_metadata;
+use crate::pdf_tags::{self, PageStructure};
+
+const ALPHA_STATE_PREFIX: &str = "InkA";
+const OPACITY_GROUP_TAG: &str = "InkOpacity";
+const SHADING_TAG: &str = "InkShading";
+const LINK_TAG: &str = "InkLink";
+const ANCHOR_TAG: &str = "InkAnchor";
+const BOOKMARK_TAG: &str = "InkBookmark";
+const ATTACHMENT_TAG: &str = "InkAttachment";
+const FIELD_TAG: &str = "InkField";
+
+/// A gradient, in page coordinates
+pub struct Shading {
+    /// Radial: circles `[x0 y0 r0 x1 y1 r1]`; axial: a line `[x0 y0 x1 y1]`
+    pub radial: bool,
+    pub coords: Vec<f32>,
+    /// Offsets from 0 to 1, in order
+    pub stops: Vec<(f32, Color)>,
+}
+
+/// Name of the ExtGState that sets these fill and stroke alphas
+pub fn alpha_state_name(fill: f32, stroke: f32) -> String {
+    let thousandths = |a: f32| (a.clamp(0.0, 1.0) * 1000.0).round() as u32;
+    format!("{}{}_{}", ALPHA_STATE_PREFIX, thousandths(fill), thousandths(stroke))
+}
+
+fn parse_alpha_state_name(name: &str) -> Option<(f32, f32)> {
+    let (fill, stroke) = name.strip_prefix(ALPHA_STATE_PREFIX)?.split_once('_')?;
+    Some((fill.parse::<u32>().ok()? as f32 / 1000.0, stroke.parse::<u32>().ok()? as f32 / 1000.0))
+}
+
+/// `gs` operator selecting the fill/stroke alpha state
+pub fn set_alpha_state(fill: f32, stroke: f32) -> Operation {
+    Operation::new("gs", vec![Object::Name(alpha_state_name(fill, stroke).into_bytes())])
+}
+
+/// Opens a group whose content is composited as a whole at `opacity`
+pub fn begin_opacity_group(opacity: f32) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set("ca", Object::Real(opacity.clamp(0.0, 1.0)));
+    Operation::new(
+        "BDC",
+        vec![Object::Name(OPACITY_GROUP_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+pub fn end_opacity_group() -> Operation {
+    Operation::new("EMC", vec![])
+}
+
+/// Paints `shading` over the current clipping path
+pub fn paint_shading(shading: &Shading) -> Operation {
+    let reals = |values: Vec<f32>| Object::Array(values.into_iter().map(Object::Real).collect());
+    let channel = |c: u8| c as f32 / 255.0;
+
+    let mut properties = Dictionary::new();
+    properties.set("Radial", Object::Boolean(shading.radial));
+    properties.set("Coords", reals(shading.coords.clone()));
+    properties.set("Offsets", reals(shading.stops.iter().map(|s| s.0).collect()));
+    properties.set(
+        "Colors",
+        reals(shading.stops.iter().flat_map(|(_, c)| [channel(c.r), channel(c.g), channel(c.b)]).collect()),
+    );
+    properties.set("Alphas", reals(shading.stops.iter().map(|(_, c)| c.a.clamp(0.0, 1.0)).collect()));
+    Operation::new(
+        "DP",
+        vec![Object::Name(SHADING_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Marks the rectangle (bottom-left corner and size, in points) as a link to
+/// `href`: a URL, `mailto:` address or `#name` of an anchor
+pub fn link(x: f32, y: f32, width: f32, height: f32, href: &str) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set(
+        "Rect",
+        Object::Array([x, y, x + width, y + height].into_iter().map(Object::Real).collect()),
+    );
+    properties.set("Href", Object::String(href.as_bytes().to_vec(), StringFormat::Literal));
+    Operation::new(
+        "DP",
+        vec![Object::Name(LINK_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Marks the point that `#name` links jump to (the top-left of the target)
+pub fn anchor(name: &str, x: f32, y: f32) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set("Name", Object::String(name.as_bytes().to_vec(), StringFormat::Literal));
+    properties.set("X", Object::Real(x));
+    properties.set("Y", Object::Real(y));
+    Operation::new(
+        "DP",
+        vec![Object::Name(ANCHOR_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Marks the point (the top-left of a node) an outline entry jumps to
+pub fn bookmark(title: &str, level: u32, x: f32, y: f32) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set("Title", Object::String(title.as_bytes().to_vec(), StringFormat::Literal));
+    properties.set("Level", Object::Integer(level as i64));
+    properties.set("X", Object::Real(x));
+    properties.set("Y", Object::Real(y));
+    Operation::new(
+        "DP",
+        vec![Object::Name(BOOKMARK_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Marks the rectangle as a file attachment annotation embedding `file`
+pub fn file_attachment(x: f32, y: f32, width: f32, height: f32, file: &Attachment) -> Operation {
+    let string = |value: &str| Object::String(value.as_bytes().to_vec(), StringFormat::Literal);
+    let mut properties = Dictionary::new();
+    properties.set(
+        "Rect",
+        Object::Array([x, y, x + width, y + height].into_iter().map(Object::Real).collect()),
+    );
+    properties.set("Name", string(&file.name));
+    properties.set("Src", string(&file.src));
+    if let Some(ref mime_type) = file.mime_type {
+        properties.set("MimeType", string(mime_type));
+    }
+    if let Some(ref description) = file.description {
+        properties.set("Desc", string(description));
+    }
+    Operation::new(
+        "DP",
+        vec![Object::Name(ATTACHMENT_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Marks the rectangle as a widget of the form field
+pub fn form_field(x: f32, y: f32, width: f32, height: f32, field: &FormField) -> Operation {
+    let mut properties = field.to_properties();
+    properties.set(
+        "Rect",
+        Object::Array([x, y, x + width, y + height].into_iter().map(Object::Real).collect()),
+    );
+    Operation::new(
+        "DP",
+        vec![Object::Name(FIELD_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Replace the placeholders on every page with real resources and annotations,
+/// then apply the document settings
+pub fn finish(bytes: &[u8], document: &DocumentOptions) -> Result<Vec<u8>, String> {
+    let mut doc = Document::load_mem(bytes).map_err(|e| format!("Failed to reload PDF: {}", e))?;
+
+    let mut links = Vec::new();
+    let mut anchors: HashMap<String, Destination> = HashMap::new();
+    let mut outline = Vec::new();
+    let mut attachments = Vec::new();
+    let mut fields = Vec::new();
+    let mut structure = Vec::new();
+    let tagged = document.tagged == Some(true);
+    let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    for page_id in page_ids {
+        let marks = finish_page(&mut doc, page_id, tagged)?;
+        links.extend(marks.links.into_iter().map(|link| (page_id, link)));
+        for (name, x, y) in marks.anchors {
+            // The first node with an id wins, like in HTML
+            anchors.entry(name).or_insert(Destination { page_id, x, y });
+        }
+        outline.extend(marks.bookmarks.into_iter().map(|(title, level, x, y)| {
+            (title, level, Destination { page_id, x, y })
+        }));
+        attachments.extend(marks.attachments.into_iter().map(|(rect, file)| (page_id, rect, file)));
+        fields.extend(marks.fields.into_iter().map(|(rect, field, element)| (page_id, rect, field, element)));
+        structure.push((page_id, marks.structure));
+    }
+    let mut annotations = add_links(&mut doc, links, &anchors)?;
+    add_outline(&mut doc, outline)?;
+    pdf_metadata::apply(&mut doc, document)?;
+    pdf_attachments::apply(&mut doc, document, attachments)?;
+    annotations.extend(pdf_forms::apply(&mut doc, fields)?);
+    pdf_tags::apply(&mut doc, document, structure, annotations)?;
+
+    let mut out = Vec::new();
+    doc.save_to(&mut out).map_err(|e| format!("Failed to save PDF: {}", e))?;
+    Ok(out)
+}
+
+/// A link mark, in page coordinates
+struct Link {
+    rect: [f32; 4],
+    href: String,
+    /// Structure element the link was drawn in
+    element: Option<usize>,
+}
+
+/// Link and anchor marks taken out of one page's content
+#[derive(Default)]
+struct PageMarks {
+    links: Vec<Link>,
+    anchors: Vec<(String, f32, f32)>,
+    /// Title, level and position
+    bookmarks: Vec<(String, u32, f32, f32)>,
+    attachments: Vec<([f32; 4], Attachment)>,
+    /// Widget rectangle, field and structure element
+    fields: Vec<([f32; 4], FormField, Option<usize>)>,
+    structure: PageStructure,
+}
+
+/// Where an anchor ended up
+struct Destination {
+    page_id: ObjectId,
+    x: f32,
+    y: f32,
+}
+
+/// Resources referenced by the rewritten content of one page
+struct PageResources {
+    resources_id: ObjectId,
+    bbox: Object,
+    alpha_states: BTreeSet<String>,
+    /// Graphics states that aren't plain alphas (soft masks)
+    states: Vec<(String, Object)>,
+    groups: Vec<(String, ObjectId)>,
+    shadings: Vec<(String, ObjectId)>,
+}
+
+fn finish_page(doc: &mut Document, page_id: ObjectId, tagged: bool) -> Result<PageMarks, String> {
+    let content = doc.get_page_content(page_id).map_err(|e| format!("Failed to read page content: {}", e))?;
+    let operations = Content::decode(&content)
+        .map_err(|e| format!("Failed to decode page content: {}", e))?
+        .operations;
+    if !operations.iter().any(is_placeholder) {
+        return Ok(PageMarks::default());
+    }
+    let (operations, structure) = pdf_tags::mark_content(operations, tagged);
+    let (operations, mut marks) = extract_marks(operations);
+    marks.structure = structure;
+
+    let bbox = doc.get_dictionary(page_id)
+        .and_then(|page| page.get(b"MediaBox"))
+        .cloned()
+        .unwrap_or_else(|_| vec![0.into(), 0.into(), 595.into(), 842.into()].into());
+    let mut page = PageResources {
+        resources_id: page_resources_id(doc, page_id)?,
+        bbox,
+        alpha_states: BTreeSet::new(),
+        states: Vec::new(),
+        groups: Vec::new(),
+        shadings: Vec::new(),
+    };
+
+    let operations = rewrite(doc, operations, &mut page)?;
+    let content = encode(operations)?;
+    doc.change_page_content(page_id, content).map_err(|e| format!("Failed to write page content: {}", e))?;
+
+    let mut states: Vec<(String, Object)> = page.alpha_states.iter()
+        .filter_map(|name| {
+            let (fill, stroke) = parse_alpha_state_name(name)?;
+            let mut state = Dictionary::new();
+            state.set("Type", Object::Name(b"ExtGState".to_vec()));
+            state.set("ca", Object::Real(fill));
+            state.set("CA", Object::Real(stroke));
+            Some((name.clone(), Object::Dictionary(state)))
+        })
+        .collect();
+    states.append(&mut page.states);
+    add_resources(doc, page.resources_id, "ExtGState", states)?;
+
+    let references = |entries: &[(String, ObjectId)]| {
+        entries.iter().map(|(name, id)| (name.clone(), Object::Reference(*id))).collect()
+    };
+    add_resources(doc, page.resources_id, "XObject", references(&page.groups))?;
+    add_resources(doc, page.resources_id, "Shading", references(&page.shadings))?;
+    Ok(marks)
+}
+
+fn is_placeholder(op: &Operation) -> bool {
+    match op.operator.as_str() {
+        "gs" => alpha_state_operand(op).is_some(),
+        "BDC" => opacity_group(op).is_some() || pdf_tags::is_placeholder(op),
+        "BMC" => pdf_tags::is_placeholder(op),
+        "DP" => shading_placeholder(op).is_some()
+            || [LINK_TAG, ANCHOR_TAG, BOOKMARK_TAG, ATTACHMENT_TAG, FIELD_TAG].iter().any(|tag| mark_placeholder(op, tag).is_some()),
+        _ => false,
+    }
+}
+
+pub fn is_opacity_group(op: &Operation) -> bool {
+    opacity_group(op).is_some()
+}
+
+pub fn is_shading(op: &Operation) -> bool {
+    shading_placeholder(op).is_some()
+}
+
+/// Whether the mark becomes an annotation (a link, file attachment or form field)
+pub fn is_annotation_mark(op: &Operation) -> bool {
+    [LINK_TAG, ATTACHMENT_TAG, FIELD_TAG].iter().any(|tag| mark_placeholder(op, tag).is_some())
+}
+
+fn mark_placeholder<'a>(op: &'a Operation, tag: &str) -> Option<&'a Dictionary> {
+    match op.operands.as_slice() {
+        [Object::Name(name), Object::Dictionary(properties)] if name == tag.as_bytes() => Some(properties),
+        _ => None,
+    }
+}
+
+/// Take the link, anchor, bookmark, attachment and field marks out of `operations`, following `q`/`Q`/`cm`
+/// to map their coordinates onto the page
+fn extract_marks(operations: Vec<Operation>) -> (Vec<Operation>, PageMarks) {
+    const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    let mut ctm = IDENTITY;
+    let mut saved = Vec::new();
+    let mut marks = PageMarks::default();
+    let mut out = Vec::with_capacity(operations.len());
+
+    let apply = |m: &[f32; 6], x: f32, y: f32| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]);
+    let string = |properties: &Dictionary, key: &[u8]| {
+        properties.get(key).ok()
+            .and_then(|o| o.as_str().ok())
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+    };
+    let float = |properties: &Dictionary, key: &[u8]| properties.get(key).and_then(|o| o.as_float()).unwrap_or(0.0);
+    // Bounding box of the transformed Rect
+    let bounds = |m: &[f32; 6], properties: &Dictionary| {
+        let rect: Vec<f32> = properties.get(b"Rect")
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(|o| o.as_float().ok()).collect())
+            .unwrap_or_default();
+        let &[x0, y0, x1, y1] = &rect[..] else {
+            return None;
+        };
+        let corners = [apply(m, x0, y0), apply(m, x1, y0), apply(m, x1, y1), apply(m, x0, y1)];
+        let xs = corners.map(|c| c.0);
+        let ys = corners.map(|c| c.1);
+        let min = |v: [f32; 4]| v.into_iter().fold(f32::INFINITY, f32::min);
+        let max = |v: [f32; 4]| v.into_iter().fold(f32::NEG_INFINITY, f32::max);
+        Some([min(xs), min(ys), max(xs), max(ys)])
+    };
+
+    for op in operations {
+        match op.operator.as_str() {
+            "q" => saved.push(ctm),
+            "Q" => ctm = saved.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                let m: Vec<f32> = op.operands.iter().filter_map(|o| o.as_float().ok()).collect();
+                if let [a, b, c, d, e, f] = m[..] {
+                    ctm = [
+                        a * ctm[0] + b * ctm[2],
+                        a * ctm[1] + b * ctm[3],
+                        c * ctm[0] + d * ctm[2],
+                        c * ctm[1] + d * ctm[3],
+                        e * ctm[0] + f * ctm[2] + ctm[4],
+                        e * ctm[1] + f * ctm[3] + ctm[5],
+                    ];
+                }
+            }
+            "DP" => {
+                if let Some(properties) = mark_placeholder(&op, LINK_TAG) {
+                    if let (Some(rect), Some(href)) = (bounds(&ctm, properties), string(properties, b"Href")) {
+                        let element = properties.get(b"Elem").and_then(|o| o.as_i64()).ok().map(|id| id as usize);
+                        marks.links.push(Link { rect, href, element });
+                    }
+                    continue;
+                }
+                if let Some(properties) = mark_placeholder(&op, ANCHOR_TAG) {
+                    if let Some(name) = string(properties, b"Name") {
+                        let (x, y) = apply(&ctm, float(properties, b"X"), float(properties, b"Y"));
+                        marks.anchors.push((name, x, y));
+                    }
+                    continue;
+                }
+                if let Some(properties) = mark_placeholder(&op, ATTACHMENT_TAG) {
+                    let file = string(properties, b"Name").zip(string(properties, b"Src")).map(|(name, src)| Attachment {
+                        name,
+                        src,
+                        mime_type: string(properties, b"MimeType"),
+                        description: string(properties, b"Desc"),
+                    });
+                    if let (Some(rect), Some(file)) = (bounds(&ctm, properties), file) {
+                        marks.attachments.push((rect, file));
+                    }
+                    continue;
+                }
+                if let Some(properties) = mark_placeholder(&op, FIELD_TAG) {
+                    if let (Some(rect), Some(field)) = (bounds(&ctm, properties), FormField::from_properties(properties)) {
+                        let element = properties.get(b"Elem").and_then(|o| o.as_i64()).ok().map(|id| id as usize);
+                        marks.fields.push((rect, field, element));
+                    }
+                    continue;
+                }
+                if let Some(properties) = mark_placeholder(&op, BOOKMARK_TAG) {
+                    if let Some(title) = string(properties, b"Title") {
+                        let level = properties.get(b"Level").and_then(|o| o.as_i64()).unwrap_or(1).max(1) as u32;
+                        let (x, y) = apply(&ctm, float(properties, b"X"), float(properties, b"Y"));
+                        marks.bookmarks.push((title, level, x, y));
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        out.push(op);
+    }
+    (out, marks)
+}
+
+/// Add a Link annotation for every link mark: URLs open a URI action, `#name`
+/// jumps to the anchor of that name. Returns the page, annotation and
+/// structure element of each.
+fn add_links(
+    doc: &mut Document,
+    links: Vec<(ObjectId, Link)>,
+    anchors: &HashMap<String, Destination>,
+) -> Result<Vec<(ObjectId, ObjectId, Option<usize>)>, String> {
+    let mut annotations: HashMap<ObjectId, Vec<Object>> = HashMap::new();
+    let mut added = Vec::new();
+    for (page_id, link) in links {
+        let mut annotation = Dictionary::new();
+        annotation.set("Type", Object::Name(b"Annot".to_vec()));
+        annotation.set("Subtype", Object::Name(b"Link".to_vec()));
+        annotation.set("Rect", Object::Array(link.rect.into_iter().map(Object::Real).collect()));
+        annotation.set("Border", Object::Array(vec![0.into(), 0.into(), 0.into()]));
+        // Printable, as PDF/A requires of every annotation
+        annotation.set("F", Object::Integer(4));
+
+        if let Some(name) = link.href.strip_prefix('#') {
+            let Some(target) = anchors.get(name) else {
+                eprintln!("Link to unknown anchor #{}, skipping", name);
+                continue;
+            };
+            annotation.set("Dest", Object::Array(vec![
+                Object::Reference(target.page_id),
+                Object::Name(b"XYZ".to_vec()),
+                Object::Real(target.x),
+                Object::Real(target.y),
+                Object::Null,
+            ]));
+        } else {
+            let mut action = Dictionary::new();
+            action.set("S", Object::Name(b"URI".to_vec()));
+            action.set("URI", Object::String(link.href.into_bytes(), StringFormat::Literal));
+            annotation.set("A", Object::Dictionary(action));
+        }
+
+        let id = doc.add_object(annotation);
+        annotations.entry(page_id).or_default().push(Object::Reference(id));
+        added.push((page_id, id, link.element));
+    }
+    add_annotations(doc, annotations)?;
+    Ok(added)
+}
+
+/// Append annotation references to the pages' Annots
+pub fn add_annotations(doc: &mut Document, annotations: HashMap<ObjectId, Vec<Object>>) -> Result<(), String> {
+    for (page_id, mut refs) in annotations {
+        let existing = doc.get_dictionary(page_id)
+            .map_err(|e| format!("Failed to read page: {}", e))?
+            .get(b"Annots")
+            .ok()
+            .cloned();
+        let mut annots = match existing {
+            Some(Object::Array(annots)) => annots,
+            Some(Object::Reference(id)) => doc.get_object(id).and_then(|o| o.as_array()).cloned().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        annots.append(&mut refs);
+        doc.get_dictionary_mut(page_id)
+            .map_err(|e| format!("Failed to update page: {}", e))?
+            .set("Annots", Object::Array(annots));
+    }
+    Ok(())
+}
+
+fn shading_placeholder(op: &Operation) -> Option<&Dictionary> {
+    match op.operands.as_slice() {
+        [Object::Name(tag), Object::Dictionary(properties)] if tag == SHADING_TAG.as_bytes() => Some(properties),
+        _ => None,
+    }
+}
+
+fn alpha_state_operand(op: &Operation) -> Option<String> {
+    match op.operands.first() {
+        Some(Object::Name(name)) if name.starts_with(ALPHA_STATE_PREFIX.as_bytes()) => {
+            String::from_utf8(name.clone()).ok()
+        }
+        _ => None,
+    }
+}
+
+fn opacity_group(op: &Operation) -> Option<f32> {
+    match op.operands.as_slice() {
+        [Object::Name(tag), Object::Dictionary(properties)] if tag == OPACITY_GROUP_TAG.as_bytes() => {
+            properties.get(b"ca").ok().and_then(|ca| ca.as_float().ok())
+        }
+        _ => None,
+    }
+}
+
+/// Replace the placeholders in `operations`, moving every opacity group
+/// (innermost first) into its own form XObject
+fn rewrite(doc: &mut Document, operations: Vec<Operation>, page: &mut PageResources) -> Result<Vec<Operation>, String> {
+    let mut out = Vec::with_capacity(operations.len());
+    let mut ops = operations.into_iter();
+
+    while let Some(op) = ops.next() {
+        if let Some(properties) = shading_placeholder(&op) {
+            out.extend(build_shading(doc, properties, page)?);
+            continue;
+        }
+        let Some(opacity) = opacity_group(&op) else {
+            if let Some(name) = alpha_state_operand(&op) {
+                page.alpha_states.insert(name);
+            }
+            out.push(op);
+            continue;
+        };
+
+        // Collect everything up to the matching EMC
+        let mut inner = Vec::new();
+        let mut depth = 0;
+        for op in ops.by_ref() {
+            match op.operator.as_str() {
+                "BDC" | "BMC" => depth += 1,
+                "EMC" if depth == 0 => break,
+                "EMC" => depth -= 1,
+                _ => {}
+            }
+            inner.push(op);
+        }
+        let inner = rewrite(doc, inner, page)?;
+
+        let mut group = Dictionary::new();
+        group.set("Type", Object::Name(b"Group".to_vec()));
+        group.set("S", Object::Name(b"Transparency".to_vec()));
+        let mut form = Dictionary::new();
+        form.set("Type", Object::Name(b"XObject".to_vec()));
+        form.set("Subtype", Object::Name(b"Form".to_vec()));
+        form.set("BBox", page.bbox.clone());
+        form.set("Group", Object::Dictionary(group));
+        form.set("Resources", Object::Reference(page.resources_id));
+        let form_id = doc.add_object(Stream::new(form, encode(inner)?));
+
+        let name = format!("InkG{}", form_id.0);
+        page.groups.push((name.clone(), form_id));
+        page.alpha_states.insert(alpha_state_name(opacity, opacity));
+
+        out.push(Operation::new("q", vec![]));
+        out.push(set_alpha_state(opacity, opacity));
+        out.push(Operation::new("Do", vec![Object::Name(name.into_bytes())]));
+        out.push(Operation::new("Q", vec![]));
+    }
+    Ok(out)
+}
+
+/// Shading object and the operators painting it, for a shading placeholder
+fn build_shading(doc: &mut Document, properties: &Dictionary, page: &mut PageResources) -> Result<Vec<Operation>, String> {
+    let floats = |key: &[u8]| -> Vec<f32> {
+        properties.get(key)
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(|o| o.as_float().ok()).collect())
+            .unwrap_or_default()
+    };
+    let radial = properties.get(b"Radial").and_then(|o| o.as_bool()).unwrap_or(false);
+    let coords = floats(b"Coords");
+    let offsets = floats(b"Offsets");
+    let colors = floats(b"Colors");
+    let alphas = floats(b"Alphas");
+    if offsets.is_empty() || colors.len() != offsets.len() * 3 || alphas.len() != offsets.len() {
+        return Ok(Vec::new());
+    }
+
+    let rgb: Vec<Vec<f32>> = colors.chunks(3).map(|c| c.to_vec()).collect();
+    let shading = shading_dict(radial, &coords, "DeviceRGB", gradient_function(&offsets, &rgb));
+    let shading_id = doc.add_object(shading);
+    let name = format!("InkSh{}", shading_id.0);
+    page.shadings.push((name.clone(), shading_id));
+    let paint = Operation::new("sh", vec![Object::Name(name.into_bytes())]);
+
+    if alphas.iter().all(|&a| a >= 1.0) {
+        return Ok(vec![paint]);
+    }
+
+    // Translucent stops: paint through a soft mask whose luminosity is the alpha gradient
+    let gray: Vec<Vec<f32>> = alphas.iter().map(|&a| vec![a]).collect();
+    let mut mask_shadings = Dictionary::new();
+    mask_shadings.set("S0", shading_dict(radial, &coords, "DeviceGray", gradient_function(&offsets, &gray)));
+    let mut mask_resources = Dictionary::new();
+    mask_resources.set("Shading", Object::Dictionary(mask_shadings));
+
+    let mut group = Dictionary::new();
+    group.set("Type", Object::Name(b"Group".to_vec()));
+    group.set("S", Object::Name(b"Transparency".to_vec()));
+    group.set("CS", Object::Name(b"DeviceGray".to_vec()));
+    let mut form = Dictionary::new();
+    form.set("Type", Object::Name(b"XObject".to_vec()));
+    form.set("Subtype", Object::Name(b"Form".to_vec()));
+    form.set("BBox", page.bbox.clone());
+    form.set("Group", Object::Dictionary(group));
+    form.set("Resources", Object::Dictionary(mask_resources));
+    let mask_content = encode(vec![Operation::new("sh", vec![Object::Name(b"S0".to_vec())])])?;
+    let form_id = doc.add_object(Stream::new(form, mask_content));
+
+    let mut mask = Dictionary::new();
+    mask.set("Type", Object::Name(b"Mask".to_vec()));
+    mask.set("S", Object::Name(b"Luminosity".to_vec()));
+    mask.set("G", Object::Reference(form_id));
+    let mut state = Dictionary::new();
+    state.set("Type", Object::Name(b"ExtGState".to_vec()));
+    state.set("SMask", Object::Dictionary(mask));
+    let state_name = format!("InkM{}", form_id.0);
+    page.states.push((state_name.clone(), Object::Dictionary(state)));
+
+    Ok(vec![
+        Operation::new("q", vec![]),
+        Operation::new("gs", vec![Object::Name(state_name.into_bytes())]),
+        paint,
+        Operation::new("Q", vec![]),
+    ])
+}
+
+fn shading_dict(radial: bool, coords: &[f32], color_space: &str, function: Object) -> Object {
+    let mut shading = Dictionary::new();
+    shading.set("ShadingType", Object::Integer(if radial { 3 } else { 2 }));
+    shading.set("ColorSpace", Object::Name(color_space.as_bytes().to_vec()));
+    shading.set("Coords", Object::Array(coords.iter().map(|&c| Object::Real(c)).collect()));
+    shading.set("Function", function);
+    shading.set("Extend", Object::Array(vec![Object::Boolean(true), Object::Boolean(true)]));
+    Object::Dictionary(shading)
+}
+
+/// Function from 0-1 to the stop values: one linear segment per pair of stops,
+/// stitched together when there are more than two
+fn gradient_function(offsets: &[f32], values: &[Vec<f32>]) -> Object {
+    let mut stops: Vec<(f32, &Vec<f32>)> = offsets.iter().copied().zip(values).collect();
+    // Hold the first and last colors out to the ends
+    if stops[0].0 > 0.0 {
+        stops.insert(0, (0.0, stops[0].1));
+    }
+    if stops[stops.len() - 1].0 < 1.0 {
+        stops.push((1.0, stops[stops.len() - 1].1));
+    }
+
+    let segment = |from: &Vec<f32>, to: &Vec<f32>| {
+        let mut f = Dictionary::new();
+        f.set("FunctionType", Object::Integer(2));
+        f.set("Domain", Object::Array(vec![Object::Real(0.0), Object::Real(1.0)]));
+        f.set("C0", Object::Array(from.iter().map(|&v| Object::Real(v)).collect()));
+        f.set("C1", Object::Array(to.iter().map(|&v| Object::Real(v)).collect()));
+        f.set("N", Object::Integer(1));
+        Object::Dictionary(f)
+    };
+
+    match stops.len() {
+        1 => segment(stops[0].1, stops[0].1),
+        2 => segment(stops[0].1, stops[1].1),
+        n => {
+            let mut f = Dictionary::new();
+            f.set("FunctionType", Object::Integer(3));
+            f.set("Domain", Object::Array(vec![Object::Real(0.0), Object::Real(1.0)]));
+            f.set("Functions", Object::Array(stops.windows(2).map(|w| segment(w[0].1, w[1].1)).collect()));
+            f.set("Bounds", Object::Array(stops[1..n - 1].iter().map(|s| Object::Real(s.0)).collect()));
+            f.set("Encode", Object::Array((0..n - 1).flat_map(|_| [Object::Real(0.0), Object::Real(1.0)]).collect()));
+            Object::Dictionary(f)
+        }
+    }
+}
+
+/// Build the document outline from the bookmarks in page order: each entry
+/// nests under the closest entry before it with a lower level
+fn add_outline(doc: &mut Document, bookmarks: Vec<(String, u32, Destination)>) -> Result<(), String> {
+    if bookmarks.is_empty() {
+        return Ok(());
+    }
+
+    let root_id = doc.new_object_id();
+    let ids: Vec<ObjectId> = bookmarks.iter().map(|_| doc.new_object_id()).collect();
+
+    // Parent of every entry (None for top level)
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(bookmarks.len());
+    let mut open: Vec<usize> = Vec::new();
+    for (i, (_, level, _)) in bookmarks.iter().enumerate() {
+        while open.last().is_some_and(|&j| bookmarks[j].1 >= *level) {
+            open.pop();
+        }
+        parents.push(open.last().copied());
+        open.push(i);
+    }
+    let children = |parent: Option<usize>| -> Vec<usize> {
+        (0..bookmarks.len()).filter(|&i| parents[i] == parent).collect()
+    };
+    // Entries are in document order, so an entry's descendants are the run of deeper entries after it
+    let descendants = |i: usize| bookmarks[i + 1..].iter().take_while(|b| b.1 > bookmarks[i].1).count();
+
+    // Links between siblings, and to each item's first and last children
+    let set_family = |dict: &mut Dictionary, kids: &[usize]| {
+        if let (Some(&first), Some(&last)) = (kids.first(), kids.last()) {
+            dict.set("First", Object::Reference(ids[first]));
+            dict.set("Last", Object::Reference(ids[last]));
+        }
+    };
+    for (i, (title, _, target)) in bookmarks.iter().enumerate() {
+        let siblings = children(parents[i]);
+        let position = siblings.iter().position(|&j| j == i).unwrap_or(0);
+
+        let mut item = Dictionary::new();
+        item.set("Title", text_string(title));
+        item.set("Parent", Object::Reference(parents[i].map_or(root_id, |p| ids[p])));
+        if position > 0 {
+            item.set("Prev", Object::Reference(ids[siblings[position - 1]]));
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.set("Next", Object::Reference(ids[next]));
+        }
+        set_family(&mut item, &children(Some(i)));
+        let count = descendants(i);
+        if count > 0 {
+            // Positive: shown expanded
+            item.set("Count", Object::Integer(count as i64));
+        }
+        item.set("Dest", Object::Array(vec![
+            Object::Reference(target.page_id),
+            Object::Name(b"XYZ".to_vec()),
+            Object::Real(target.x),
+            Object::Real(target.y),
+            Object::Null,
+        ]));
+        doc.objects.insert(ids[i], Object::Dictionary(item));
+    }
+
+    let mut root = Dictionary::new();
+    root.set("Type", Object::Name(b"Outlines".to_vec()));
+    set_family(&mut root, &children(None));
+    root.set("Count", Object::Integer(bookmarks.len() as i64));
+    doc.objects.insert(root_id, Object::Dictionary(root));
+
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    catalog.set("Outlines", Object::Reference(root_id));
+    Ok(())
+}
+
+/// A PDF text string: literal when ASCII, UTF-16BE with a byte order mark otherwise
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::String(text.as_bytes().to_vec(), StringFormat::Literal);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+fn encode(operations: Vec<Operation>) -> Result<Vec<u8>, String> {
+    Content { operations }.encode().map_err(|e| format!("Failed to encode content: {}", e))
+}
+
+/// The page's resource dictionary as its own object, so form XObjects can share it
+fn page_resources_id(doc: &mut Document, page_id: ObjectId) -> Result<ObjectId, String> {
+    let existing = doc.get_dictionary(page_id)
+        .map_err(|e| format!("Failed to read page: {}", e))?
+        .get(b"Resources")
+        .ok()
+        .cloned();
+
+    let resources = match existing {
+        Some(Object::Reference(id)) => return Ok(id),
+        Some(Object::Dictionary(dict)) => dict,
+        _ => Dictionary::new(),
+    };
+    let id = doc.add_object(resources);
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to update page: {}", e))?
+        .set("Resources", Object::Reference(id));
+    Ok(id)
+}
+
+/// Merge entries into one category (ExtGState, XObject, ...) of a resource dictionary
+fn add_resources(doc: &mut Document, resources_id: ObjectId, category: &str, entries: Vec<(String, Object)>) -> Result<(), String> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let existing = doc.get_dictionary(resources_id)
+        .map_err(|e| format!("Failed to read resources: {}", e))?
+        .get(category.as_bytes())
+        .ok()
+        .cloned();
+    let mut dict = match existing {
+        Some(Object::Dictionary(dict)) => dict,
+        Some(Object::Reference(id)) => doc.get_dictionary(id).cloned().unwrap_or_default(),
+        _ => Dictionary::new(),
+    };
+    for (name, value) in entries {
+        dict.set(name, value);
+    }
+
+    doc.get_dictionary_mut(resources_id)
+        .map_err(|e| format!("Failed to update resources: {}", e))?
+        .set(category, Object::Dictionary(dict));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::{Mm, PdfDocument};
+
+    #[test]
+    fn test_alpha_state_names_round_trip() {
+        assert_eq!(alpha_state_name(0.5, 1.0), "InkA500_1000");
+        assert_eq!(parse_alpha_state_name("InkA250_1000"), Some((0.25, 1.0)));
+        assert_eq!(parse_alpha_state_name("GS1"), None);
+    }
+
+    #[test]
+    fn test_opacity_groups_become_form_xobjects() {
+        let (doc, page, layer) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let layer = doc.get_page(page).get_layer(layer);
+        layer.add_operation(begin_opacity_group(0.5));
+        layer.add_operation(set_alpha_state(0.25, 1.0));
+        layer.add_operation(end_opacity_group());
+        let bytes = doc.save_to_bytes().unwrap();
+
+        let finished = Document::load_mem(&finish(&bytes, &DocumentOptions::default()).unwrap()).unwrap();
+        let page_id = *finished.get_pages().values().next().unwrap();
+        let (_, resource_ids) = finished.get_page_resources(page_id);
+        let resources = finished.get_dictionary(resource_ids[0]).unwrap();
+
+        let states = resources.get(b"ExtGState").unwrap().as_dict().unwrap();
+        assert!(states.has(b"InkA500_500"));
+        assert!(states.has(b"InkA250_1000"));
+        let groups = resources.get(b"XObject").unwrap().as_dict().unwrap();
+        assert_eq!(groups.len(), 1);
+
+        let content = Content::decode(&finished.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.operations.iter().any(|op| op.operator == "Do"));
+        assert!(!content.operations.iter().any(|op| opacity_group(op).is_some()));
+    }
+
+    #[test]
+    fn test_translucent_gradient_becomes_masked_shading() {
+        let (doc, page, layer) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let layer = doc.get_page(page).get_layer(layer);
+        let color = |a: f32| Color { r: 255, g: 0, b: 0, a };
+        layer.add_operation(paint_shading(&Shading {
+            radial: false,
+            coords: vec![0.0, 0.0, 100.0, 0.0],
+            stops: vec![(0.0, color(1.0)), (0.5, color(1.0)), (1.0, color(0.0))],
+        }));
+        let bytes = doc.save_to_bytes().unwrap();
+
+        let finished = Document::load_mem(&finish(&bytes, &DocumentOptions::default()).unwrap()).unwrap();
+        let page_id = *finished.get_pages().values().next().unwrap();
+        let (_, resource_ids) = finished.get_page_resources(page_id);
+        let resources = finished.get_dictionary(resource_ids[0]).unwrap();
+
+        let shadings = resources.get(b"Shading").unwrap().as_dict().unwrap();
+        assert_eq!(shadings.len(), 1);
+        let (_, shading) = shadings.iter().next().unwrap();
+        let shading = finished.get_dictionary(shading.as_reference().unwrap()).unwrap();
+        assert_eq!(shading.get(b"ShadingType").unwrap().as_i64().unwrap(), 2);
+        let function = shading.get(b"Function").unwrap().as_dict().unwrap();
+        assert_eq!(function.get(b"FunctionType").unwrap().as_i64().unwrap(), 3);
+
+        let states = resources.get(b"ExtGState").unwrap().as_dict().unwrap();
+        assert!(states.iter().any(|(_, state)| state.as_dict().is_ok_and(|d| d.has(b"SMask"))));
+
+        let content = Content::decode(&finished.get_page_content(page_id).unwrap()).unwrap();
+        let operators: Vec<&str> = content.operations.iter().map(|op| op.operator.as_str()).collect();
+        assert!(operators.windows(4).any(|w| w == ["q", "gs", "sh", "Q"]));
+        assert!(!operators.contains(&"DP"));
+    }
+
+    #[test]
+    fn test_links_become_annotations() {
+        let (doc, page, layer) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let layer = doc.get_page(page).get_layer(layer);
+        layer.add_operation(link(10.0, 20.0, 30.0, 40.0, "https://example.com"));
+        layer.add_operation(link(10.0, 20.0, 30.0, 40.0, "#missing"));
+        layer.add_operation(Operation::new("q", vec![]));
+        layer.add_operation(Operation::new("cm", [1, 0, 0, 1, 100, 200].into_iter().map(Object::Integer).collect()));
+        layer.add_operation(link(0.0, 0.0, 5.0, 5.0, "#top"));
+        layer.add_operation(anchor("top", 0.0, 50.0));
+        layer.add_operation(Operation::new("Q", vec![]));
+        let bytes = doc.save_to_bytes().unwrap();
+
+        let finished = Document::load_mem(&finish(&bytes, &DocumentOptions::default()).unwrap()).unwrap();
+        let page_id = *finished.get_pages().values().next().unwrap();
+        let annots = finished.get_dictionary(page_id).unwrap().get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annots.len(), 2);
+
+        let annot = |i: usize| finished.get_dictionary(annots[i].as_reference().unwrap()).unwrap();
+        let uri = annot(0).get(b"A").unwrap().as_dict().unwrap().get(b"URI").unwrap();
+        assert_eq!(uri.as_str().unwrap(), b"https://example.com");
+
+        let rect: Vec<f32> = annot(1).get(b"Rect").unwrap().as_array().unwrap()
+            .iter().map(|o| o.as_float().unwrap()).collect();
+        assert_eq!(rect, vec![100.0, 200.0, 105.0, 205.0]);
+        let dest = annot(1).get(b"Dest").unwrap().as_array().unwrap();
+        assert_eq!(dest[0].as_reference().unwrap(), page_id);
+        assert_eq!(dest[3].as_float().unwrap(), 250.0);
+
+        let content = Content::decode(&finished.get_page_content(page_id).unwrap()).unwrap();
+        assert!(!content.operations.iter().any(|op| op.operator == "DP"));
+    }
+
+    #[test]
+    fn test_bookmarks_nest_by_level() {
+        let (doc, page, layer) = PdfDocument::new("test", Mm(210.0), Mm(297.0), "Layer 1");
+        let layer = doc.get_page(page).get_layer(layer);
+        layer.add_operation(bookmark("Intro", 1, 0.0, 800.0));
+        layer.add_operation(bookmark("Scope", 2, 0.0, 700.0));
+        layer.add_operation(bookmark("Détails", 3, 0.0, 600.0));
+        layer.add_operation(bookmark("Terms", 1, 0.0, 500.0));
+        let bytes = doc.save_to_bytes().unwrap();
+
+        let finished = Document::load_mem(&finish(&bytes, &DocumentOptions::default()).unwrap()).unwrap();
+        let outlines = finished.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let entry = |dict: &Dictionary, key: &[u8]| finished.get_dictionary(dict.get(key).unwrap().as_reference().unwrap()).unwrap();
+        let title = |dict: &Dictionary| dict.get(b"Title").unwrap().as_str().unwrap().to_vec();
+
+        let root = finished.get_dictionary(outlines).unwrap();
+        assert_eq!(root.get(b"Count").unwrap().as_i64().unwrap(), 4);
+        let intro = entry(root, b"First");
+        assert_eq!(title(intro), b"Intro");
+        assert_eq!(intro.get(b"Count").unwrap().as_i64().unwrap(), 2);
+        assert_eq!(title(entry(intro, b"Next")), b"Terms");
+        assert_eq!(title(entry(root, b"Last")), b"Terms");
+
+        let scope = entry(intro, b"First");
+        assert_eq!(title(scope), b"Scope");
+        let details = entry(scope, b"First");
+        // Non-ASCII titles are UTF-16 with a byte order mark
+        assert_eq!(&title(details)[..4], &[0xFE, 0xFF, 0, b'D']);
+        let dest = details.get(b"Dest").unwrap().as_array().unwrap();
+        assert_eq!(dest[3].as_float().unwrap(), 600.0);
+    }
+}
@@ -0,0 +1,566 @@
+//! Tagged PDF: a structure tree over the page content, so screen readers can
+//! read the document in order and know its headings, tables and figures.
+//!
+//! The drawing code wraps structural nodes in `/InkElement << /S /P >> BDC ... EMC`
+//! and decoration (backgrounds, borders, fixed boxes) in `/InkArtifact BMC ... EMC`.
+//! In a tagged document every run of painting operators is then marked as
+//! content of the innermost element (`/P << /MCID 0 >> BDC ... EMC`) or as an
+//! `/Artifact`, and the elements become StructElems under a Document element,
+//! in paint order. Otherwise the placeholders are dropped.
+//!
+//! An opacity group is painted as a single form XObject, so everything drawn
+//! inside it belongs to the element around the group.
+
+use std::collections::HashMap;
+
+use printpdf::lopdf::content::Operation;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+
+use crate::layout::{DocumentOptions, NodeType, Role};
+use crate::layout_box::LayoutBox;
+use crate::pdf_resources::{self, text_string};
+
+const ELEMENT_TAG: &str = "InkElement";
+const ARTIFACT_TAG: &str = "InkArtifact";
+/// Language declared when the metadata doesn't give one
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// Structure type of a box, or None for boxes that only group others (and artifacts)
+pub fn structure_type(layout: &LayoutBox) -> Option<&'static str> {
+    const HEADINGS: [&str; 6] = ["H1", "H2", "H3", "H4", "H5", "H6"];
+    let node = &layout.node;
+    match layout.semantics.role {
+        Some(Role::Heading) => return Some(HEADINGS[layout.semantics.heading_level.unwrap_or(1).clamp(1, 6) as usize - 1]),
+        Some(Role::Paragraph) => return Some("P"),
+        Some(Role::Caption) => return Some("Caption"),
+        Some(Role::List) => return Some("L"),
+        Some(Role::Artifact) => return None,
+        None => {}
+    }
+    match node.node_type {
+        NodeType::Image | NodeType::Svg => Some("Figure"),
+        NodeType::Table => Some("Table"),
+        NodeType::Row => Some("TR"),
+        NodeType::Cell if node.header == Some(true) => Some("TH"),
+        NodeType::Cell => Some("TD"),
+        NodeType::ListItem => Some("LI"),
+        ref t if t.is_form_field() => Some("Form"),
+        _ => None,
+    }
+}
+
+/// Opens a structure element of type `kind` (P, H1, Figure, ...)
+pub fn begin_element(kind: &str, alt: Option<&str>, lang: Option<&str>) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set("S", Object::Name(kind.as_bytes().to_vec()));
+    if let Some(alt) = alt {
+        properties.set("Alt", text_string(alt));
+    }
+    if let Some(lang) = lang {
+        properties.set("Lang", text_string(lang));
+    }
+    Operation::new(
+        "BDC",
+        vec![Object::Name(ELEMENT_TAG.as_bytes().to_vec()), Object::Dictionary(properties)],
+    )
+}
+
+/// Opens decoration that isn't part of the content
+pub fn begin_artifact() -> Operation {
+    Operation::new("BMC", vec![Object::Name(ARTIFACT_TAG.as_bytes().to_vec())])
+}
+
+/// Closes an element or artifact
+pub fn end() -> Operation {
+    Operation::new("EMC", vec![])
+}
+
+pub fn is_placeholder(op: &Operation) -> bool {
+    matches!(op.operator.as_str(), "BDC" | "BMC")
+        && matches!(op.operands.first(), Some(Object::Name(tag)) if tag == ELEMENT_TAG.as_bytes() || tag == ARTIFACT_TAG.as_bytes())
+}
+
+fn element_placeholder(op: &Operation) -> Option<&Dictionary> {
+    match op.operands.as_slice() {
+        [Object::Name(tag), Object::Dictionary(properties)] if tag == ELEMENT_TAG.as_bytes() => Some(properties),
+        _ => None,
+    }
+}
+
+/// The structure elements of one page
+#[derive(Default)]
+pub struct PageStructure {
+    elements: Vec<Element>,
+    /// Top-level elements, in reading order
+    roots: Vec<usize>,
+    /// Element owning each marked-content id
+    mcids: Vec<usize>,
+}
+
+struct Element {
+    kind: Vec<u8>,
+    alt: Option<Object>,
+    lang: Option<Vec<u8>>,
+    kids: Vec<Kid>,
+}
+
+enum Kid {
+    Content(usize),
+    Element(usize),
+    Annotation(ObjectId),
+}
+
+impl PageStructure {
+    fn add(&mut self, kind: Vec<u8>, alt: Option<Object>, lang: Option<Vec<u8>>, parent: Option<usize>) -> usize {
+        let id = self.elements.len();
+        self.elements.push(Element { kind, alt, lang, kids: Vec::new() });
+        match parent {
+            Some(parent) => self.elements[parent].kids.push(Kid::Element(id)),
+            None => self.roots.push(id),
+        }
+        id
+    }
+}
+
+/// What an EMC closes
+enum Open {
+    /// An element placeholder, and whether it was kept
+    Element(bool),
+    /// An artifact placeholder, and whether it was kept
+    Artifact(bool),
+    OpacityGroup,
+    Other,
+}
+
+/// State of the marking pass over one page
+struct Marking {
+    out: Vec<Operation>,
+    page: PageStructure,
+    /// Open elements, innermost last
+    path: Vec<usize>,
+    /// Whether a content or artifact run is open
+    run: bool,
+}
+
+impl Marking {
+    /// Make sure the painting that follows is in a run of the innermost element
+    /// (an artifact outside any element)
+    fn open_run(&mut self) {
+        if self.run {
+            return;
+        }
+        self.run = true;
+        let Some(&id) = self.path.last() else {
+            self.out.push(Operation::new("BMC", vec![Object::Name(b"Artifact".to_vec())]));
+            return;
+        };
+        let mcid = self.page.mcids.len();
+        self.page.mcids.push(id);
+        self.page.elements[id].kids.push(Kid::Content(mcid));
+        let mut properties = Dictionary::new();
+        properties.set("MCID", Object::Integer(mcid as i64));
+        let kind = self.page.elements[id].kind.clone();
+        self.out.push(Operation::new("BDC", vec![Object::Name(kind), Object::Dictionary(properties)]));
+    }
+
+    fn close_run(&mut self) {
+        if self.run {
+            self.run = false;
+            self.out.push(Operation::new("EMC", vec![]));
+        }
+    }
+}
+
+/// Replace the element and artifact placeholders of a page: with marked content
+/// when `tagged`, by nothing otherwise. Annotation marks inside an element get
+/// its index as `Elem`, so their annotations can join it.
+pub fn mark_content(operations: Vec<Operation>, tagged: bool) -> (Vec<Operation>, PageStructure) {
+    let mut marking = Marking {
+        out: Vec::with_capacity(operations.len()),
+        page: PageStructure::default(),
+        path: Vec::new(),
+        run: false,
+    };
+    let mut stack = Vec::new();
+    // Depth of kept artifacts and opacity groups, whose content is already marked
+    let mut covered = 0;
+
+    let mut ops = operations.into_iter().peekable();
+    while let Some(mut op) = ops.next() {
+        match op.operator.as_str() {
+            "BDC" | "BMC" if is_placeholder(&op) => {
+                // Nothing was drawn inside
+                if ops.peek().is_some_and(|next| next.operator == "EMC") {
+                    ops.next();
+                    continue;
+                }
+                let keep = tagged && covered == 0;
+                if keep {
+                    marking.close_run();
+                }
+                if let Some(properties) = element_placeholder(&op) {
+                    if keep {
+                        let kind = properties.get(b"S").and_then(|o| o.as_name()).unwrap_or(b"Span").to_vec();
+                        let alt = properties.get(b"Alt").ok().cloned();
+                        let lang = properties.get(b"Lang").and_then(|o| o.as_str()).ok().map(<[u8]>::to_vec);
+                        let id = marking.page.add(kind, alt, lang, marking.path.last().copied());
+                        marking.path.push(id);
+                    }
+                    stack.push(Open::Element(keep));
+                } else {
+                    if keep {
+                        marking.out.push(Operation::new("BMC", vec![Object::Name(b"Artifact".to_vec())]));
+                        covered += 1;
+                    }
+                    stack.push(Open::Artifact(keep));
+                }
+            }
+            "BDC" | "BMC" => {
+                let group = pdf_resources::is_opacity_group(&op);
+                if tagged && covered == 0 {
+                    // The group is painted by one Do; other marked content stays outside runs
+                    if group {
+                        marking.open_run();
+                    } else {
+                        marking.close_run();
+                    }
+                }
+                if group {
+                    covered += 1;
+                    stack.push(Open::OpacityGroup);
+                } else {
+                    stack.push(Open::Other);
+                }
+                marking.out.push(op);
+            }
+            "EMC" => match stack.pop() {
+                Some(Open::Element(kept)) => {
+                    if kept {
+                        marking.close_run();
+                        marking.path.pop();
+                    }
+                }
+                Some(Open::Artifact(kept)) => {
+                    if kept {
+                        covered -= 1;
+                        marking.out.push(op);
+                    }
+                }
+                Some(Open::OpacityGroup) => {
+                    covered -= 1;
+                    marking.out.push(op);
+                }
+                Some(Open::Other) | None => {
+                    if covered == 0 {
+                        marking.close_run();
+                    }
+                    marking.out.push(op);
+                }
+            },
+            // Runs stay inside one level of saved graphics state
+            "q" | "Q" => {
+                if covered == 0 {
+                    marking.close_run();
+                }
+                marking.out.push(op);
+            }
+            "BT" | "m" | "re" | "Do" | "sh" | "BI" => {
+                if tagged && covered == 0 {
+                    marking.open_run();
+                }
+                marking.out.push(op);
+            }
+            "DP" => {
+                if pdf_resources::is_shading(&op) && tagged && covered == 0 {
+                    marking.open_run();
+                }
+                if let (true, Some(&id)) = (tagged && pdf_resources::is_annotation_mark(&op), marking.path.last()) {
+                    if let Some(Object::Dictionary(properties)) = op.operands.get_mut(1) {
+                        properties.set("Elem", Object::Integer(id as i64));
+                    }
+                }
+                marking.out.push(op);
+            }
+            _ => marking.out.push(op),
+        }
+    }
+    marking.close_run();
+    (marking.out, marking.page)
+}
+
+/// Write the structure tree of a tagged document from the pages' structure,
+/// with every annotation as an element of its own: the annotations drawn in
+/// an element (page, annotation and owning element) inside that element, the
+/// others at the end of their page. A field widget belongs to the Form
+/// element of its field node itself.
+pub fn apply(
+    doc: &mut Document,
+    options: &DocumentOptions,
+    pages: Vec<(ObjectId, PageStructure)>,
+    annotations: Vec<(ObjectId, ObjectId, Option<usize>)>,
+) -> Result<(), String> {
+    if options.tagged != Some(true) {
+        return Ok(());
+    }
+
+    // Declare the document language first, so elements only repeat it where it changes
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    if !catalog.has(b"Lang") {
+        eprintln!("Tagged PDF has no metadata language, declaring \"{}\"", DEFAULT_LANGUAGE);
+        catalog.set("Lang", text_string(DEFAULT_LANGUAGE));
+    }
+    let document_lang = catalog.get(b"Lang").and_then(|o| o.as_str()).map(<[u8]>::to_vec).unwrap_or_default();
+
+    let root_id = doc.new_object_id();
+    let document_id = doc.new_object_id();
+    let page_numbers: HashMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+    let owners: HashMap<ObjectId, Option<usize>> = annotations.into_iter().map(|(_, annot, owner)| (annot, owner)).collect();
+
+    let mut document_kids = Vec::new();
+    let mut parent_tree = Vec::new();
+    let mut annotation_entries = Vec::new();
+    let mut next_key = pages.len() as i64;
+    for (index, (page_id, mut page)) in pages.into_iter().enumerate() {
+        for annot_id in page_annotations(doc, page_id) {
+            let subtype = doc.get_dictionary(annot_id)
+                .and_then(|annot| annot.get(b"Subtype"))
+                .and_then(|o| o.as_name())
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default();
+            let kind: &[u8] = match subtype.as_slice() {
+                b"Link" => b"Link",
+                b"Widget" => b"Form",
+                _ => b"Annot",
+            };
+            let owner = owners.get(&annot_id).copied().flatten().filter(|&id| id < page.elements.len());
+            let id = match owner {
+                Some(owner) if kind == b"Form" && page.elements[owner].kind == b"Form" => owner,
+                _ => page.add(kind.to_vec(), None, None, owner),
+            };
+            page.elements[id].kids.push(Kid::Annotation(annot_id));
+        }
+
+        let ids: Vec<ObjectId> = page.elements.iter().map(|_| doc.new_object_id()).collect();
+        let mut parents = vec![None; ids.len()];
+        for (id, element) in page.elements.iter().enumerate() {
+            for kid in &element.kids {
+                if let Kid::Element(kid) = kid {
+                    parents[*kid] = Some(id);
+                }
+            }
+        }
+        // Language each element inherits
+        let mut inherited = vec![document_lang.clone(); ids.len()];
+        for id in 0..ids.len() {
+            if let Some(parent) = parents[id] {
+                inherited[id] = page.elements[parent].lang.clone().unwrap_or_else(|| inherited[parent].clone());
+            }
+        }
+
+        for (id, element) in page.elements.into_iter().enumerate() {
+            let mut kids = Vec::new();
+            for kid in element.kids {
+                match kid {
+                    Kid::Content(mcid) => kids.push(Object::Integer(mcid as i64)),
+                    Kid::Element(kid) => kids.push(Object::Reference(ids[kid])),
+                    Kid::Annotation(annot_id) => {
+                        let mut reference = Dictionary::new();
+                        reference.set("Type", Object::Name(b"OBJR".to_vec()));
+                        reference.set("Obj", Object::Reference(annot_id));
+                        kids.push(Object::Dictionary(reference));
+                        describe_annotation(doc, annot_id, next_key, &page_numbers)?;
+                        annotation_entries.push(Object::Integer(next_key));
+                        annotation_entries.push(Object::Reference(ids[id]));
+                        next_key += 1;
+                    }
+                }
+            }
+
+            let mut dict = Dictionary::new();
+            dict.set("Type", Object::Name(b"StructElem".to_vec()));
+            dict.set("S", Object::Name(element.kind));
+            dict.set("P", Object::Reference(parents[id].map_or(document_id, |parent| ids[parent])));
+            dict.set("Pg", Object::Reference(page_id));
+            dict.set("K", Object::Array(kids));
+            if let Some(alt) = element.alt {
+                dict.set("Alt", alt);
+            }
+            if let Some(lang) = element.lang.filter(|lang| *lang != inherited[id]) {
+                dict.set("Lang", Object::String(lang, StringFormat::Literal));
+            }
+            doc.objects.insert(ids[id], Object::Dictionary(dict));
+        }
+
+        document_kids.extend(page.roots.iter().map(|&id| Object::Reference(ids[id])));
+        parent_tree.push(Object::Integer(index as i64));
+        parent_tree.push(Object::Array(page.mcids.iter().map(|&id| Object::Reference(ids[id])).collect()));
+
+        let page_dict = doc.get_dictionary_mut(page_id).map_err(|e| format!("Failed to update page: {}", e))?;
+        page_dict.set("StructParents", Object::Integer(index as i64));
+        // Tab through annotations in structure order
+        page_dict.set("Tabs", Object::Name(b"S".to_vec()));
+    }
+    parent_tree.append(&mut annotation_entries);
+
+    let mut document = Dictionary::new();
+    document.set("Type", Object::Name(b"StructElem".to_vec()));
+    document.set("S", Object::Name(b"Document".to_vec()));
+    document.set("P", Object::Reference(root_id));
+    document.set("K", Object::Array(document_kids));
+    doc.objects.insert(document_id, Object::Dictionary(document));
+
+    let mut nums = Dictionary::new();
+    nums.set("Nums", Object::Array(parent_tree));
+    let parent_tree_id = doc.add_object(nums);
+
+    let mut root = Dictionary::new();
+    root.set("Type", Object::Name(b"StructTreeRoot".to_vec()));
+    root.set("K", Object::Reference(document_id));
+    root.set("ParentTree", Object::Reference(parent_tree_id));
+    root.set("ParentTreeNextKey", Object::Integer(next_key));
+    doc.objects.insert(root_id, Object::Dictionary(root));
+
+    let catalog = doc.catalog_mut().map_err(|e| format!("Failed to update catalog: {}", e))?;
+    catalog.set("StructTreeRoot", Object::Reference(root_id));
+    let mut mark_info = Dictionary::new();
+    mark_info.set("Marked", Object::Boolean(true));
+    catalog.set("MarkInfo", Object::Dictionary(mark_info));
+    // Assistive technology announces the title rather than the file name
+    let mut viewer = match catalog.get(b"ViewerPreferences") {
+        Ok(Object::Dictionary(dict)) => dict.clone(),
+        _ => Dictionary::new(),
+    };
+    if !viewer.has(b"DisplayDocTitle") {
+        viewer.set("DisplayDocTitle", Object::Boolean(true));
+        catalog.set("ViewerPreferences", Object::Dictionary(viewer));
+    }
+    Ok(())
+}
+
+fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let annots = match doc.get_dictionary(page_id).and_then(|page| page.get(b"Annots")) {
+        Ok(Object::Array(annots)) => annots.clone(),
+        Ok(Object::Reference(id)) => doc.get_object(*id).and_then(|o| o.as_array()).cloned().unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    annots.iter().filter_map(|o| o.as_reference().ok()).collect()
+}
+
+/// Point the annotation at its parent tree entry and give links the text
+/// alternative screen readers announce
+fn describe_annotation(doc: &mut Document, annot_id: ObjectId, key: i64, page_numbers: &HashMap<ObjectId, u32>) -> Result<(), String> {
+    let annot = doc.get_dictionary(annot_id).map_err(|e| format!("Failed to read annotation: {}", e))?;
+    let is_link = annot.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Link".as_slice());
+    let contents = if is_link && !annot.has(b"Contents") {
+        let uri = annot.get(b"A")
+            .and_then(|o| o.as_dict())
+            .and_then(|action| action.get(b"URI"))
+            .and_then(|o| o.as_str())
+            .map(|s| String::from_utf8_lossy(s).into_owned());
+        let page = annot.get(b"Dest")
+            .and_then(|o| o.as_array())
+            .ok()
+            .and_then(|dest| dest.first())
+            .and_then(|o| o.as_reference().ok())
+            .and_then(|id| page_numbers.get(&id));
+        uri.ok().or_else(|| page.map(|n| format!("Go to page {}", n)))
+    } else {
+        None
+    };
+
+    let annot = doc.get_dictionary_mut(annot_id).map_err(|e| format!("Failed to update annotation: {}", e))?;
+    annot.set("StructParent", Object::Integer(key));
+    if let Some(contents) = contents {
+        annot.set("Contents", text_string(&contents));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operators(operations: &[Operation]) -> Vec<String> {
+        operations.iter().map(|op| op.operator.clone()).collect()
+    }
+
+    #[test]
+    fn test_untagged_placeholders_are_dropped() {
+        let ops = vec![
+            begin_artifact(),
+            Operation::new("re", vec![]),
+            Operation::new("f", vec![]),
+            end(),
+            begin_element("P", None, None),
+            Operation::new("BT", vec![]),
+            Operation::new("ET", vec![]),
+            end(),
+        ];
+        let (out, page) = mark_content(ops, false);
+        assert_eq!(operators(&out), ["re", "f", "BT", "ET"]);
+        assert!(page.elements.is_empty());
+    }
+
+    #[test]
+    fn test_content_is_marked_in_its_element() {
+        let ops = vec![
+            Operation::new("re", vec![]),
+            Operation::new("f", vec![]),
+            begin_element("L", None, None),
+            begin_artifact(),
+            end(),
+            begin_element("LI", None, None),
+            Operation::new("q", vec![]),
+            Operation::new("BT", vec![]),
+            Operation::new("ET", vec![]),
+            Operation::new("Q", vec![]),
+            end(),
+            end(),
+            begin_element("Figure", Some("Logo"), None),
+            begin_artifact(),
+            // Nested placeholders inside an artifact are dropped
+            begin_element("P", None, None),
+            Operation::new("re", vec![]),
+            Operation::new("S", vec![]),
+            end(),
+            end(),
+            Operation::new("Do", vec![]),
+            end(),
+        ];
+        let (out, page) = mark_content(ops, true);
+        assert_eq!(
+            operators(&out),
+            ["BMC", "re", "f", "EMC", "q", "BDC", "BT", "ET", "EMC", "Q", "BMC", "re", "S", "EMC", "BDC", "Do", "EMC"]
+        );
+        assert_eq!(out[5].operands[0].as_name().unwrap(), b"LI");
+        assert_eq!(out[14].operands[0].as_name().unwrap(), b"Figure");
+
+        assert_eq!(page.roots, [0, 2]);
+        assert_eq!(page.mcids, [1, 2]);
+        assert!(matches!(page.elements[0].kids[..], [Kid::Element(1)]));
+        assert!(matches!(page.elements[1].kids[..], [Kid::Content(0)]));
+        assert_eq!(page.elements[2].alt.as_ref().unwrap().as_str().unwrap(), b"Logo");
+    }
+
+    #[test]
+    fn test_structure_types_follow_semantics() {
+        let json = r#"{ "type": "view", "lang": "de", "children": [
+            { "type": "text", "text": "Intro", "bookmark": { "title": "Intro", "level": 2 } },
+            { "type": "text", "text": "Summary", "headingLevel": 9 },
+            { "type": "text", "text": "Body" },
+            { "type": "text", "text": "Figure 1", "role": "caption", "lang": "en" },
+            { "type": "image", "src": "logo.png", "role": "artifact" },
+            { "type": "svg", "src": "chart.svg", "alt": "Sales by month" },
+            { "type": "cell", "header": true }
+        ] }"#;
+        let node: crate::layout::JsonNode = serde_json::from_str(json).unwrap();
+        let layout = crate::layout_box::build_layout(&node);
+        let types: Vec<_> = layout.children.iter().map(structure_type).collect();
+        assert_eq!(types, [Some("H2"), Some("H6"), Some("P"), Some("Caption"), None, Some("Figure"), Some("TH")]);
+        assert_eq!(structure_type(&layout), None);
+
+        assert_eq!(layout.children[2].semantics.lang.as_deref(), Some("de"));
+        assert_eq!(layout.children[3].semantics.lang.as_deref(), Some("en"));
+        assert_eq!(layout.children[5].semantics.alt.as_deref(), Some("Sales by month"));
+    }
+}
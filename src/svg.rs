@@ -215,6 +215,15 @@ pub fn parse_svg(svg_content: &str) -> Result<SvgDocument, String> {
 fn parse_elements(content: &str) -> Vec<SvgElement> {
     let mut elements = Vec::new();
 
+    // Shapes inside groups are parsed with their group (below), not at this level
+    let groups = extract_all_tags(content, "g");
+    let full_content = content;
+    let mut top_level = content.to_string();
+    for group in &groups {
+        top_level = top_level.replacen(group.as_str(), "", 1);
+    }
+    let content = top_level.as_str();
+
     // Parse paths
     for path_tag in extract_all_tags(content, "path") {
         if let Some(d) = extract_attr(&path_tag, "d") {
@@ -303,7 +312,7 @@ fn parse_elements(content: &str) -> Vec<SvgElement> {
     }
 
     // Parse groups (g elements)
-    for group_tag in extract_all_tags(content, "g") {
+    for group_tag in extract_all_tags(full_content, "g") {
         let inner_content = extract_inner_content(&group_tag);
         let inner_elements = parse_elements(&inner_content);
         if !inner_elements.is_empty() {
@@ -470,7 +479,7 @@ fn parse_transform(tag: &str) -> Transform {
     transform
 }
 
-fn parse_color(color_str: &str) -> Option<Color> {
+pub fn parse_color(color_str: &str) -> Option<Color> {
     let color_str = color_str.trim();
 
     // Handle hex colors
@@ -816,21 +825,18 @@ fn extract_inner_content(tag: &str) -> String {
 }
 
 fn extract_attr(tag: &str, attr_name: &str) -> Option<String> {
-    // Try attr="value"
-    let pattern1 = format!("{}=\"", attr_name);
-    if let Some(start) = tag.find(&pattern1) {
-        let rest = &tag[start + pattern1.len()..];
-        if let Some(end) = rest.find('"') {
-            return Some(rest[..end].to_string());
-        }
-    }
-
-    // Try attr='value'
-    let pattern2 = format!("{}='", attr_name);
-    if let Some(start) = tag.find(&pattern2) {
-        let rest = &tag[start + pattern2.len()..];
-        if let Some(end) = rest.find('\'') {
-            return Some(rest[..end].to_string());
+    // Try attr="value", then attr='value'
+    for quote in ['"', '\''] {
+        let pattern = format!("{}={}", attr_name, quote);
+        // The name must start the attribute ("opacity" must not match "fill-opacity")
+        let start = tag.match_indices(&pattern)
+            .map(|(i, _)| i)
+            .find(|&i| tag[..i].ends_with(char::is_whitespace));
+        if let Some(start) = start {
+            let rest = &tag[start + pattern.len()..];
+            if let Some(end) = rest.find(quote) {
+                return Some(rest[..end].to_string());
+            }
         }
     }
 
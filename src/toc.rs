@@ -0,0 +1,151 @@
+//! Tables of contents.
+//!
+//! A `toc` node lists the bookmarked nodes and headings of the document, each entry linking
+//! to its node, with a dot leader and the page number the node lands on. Page
+//! numbers are only known after pagination, so the renderer lays the document
+//! out with the numbers it knows, reads the real ones off the pages and repeats
+//! until they stop changing (filling them in can change the TOC's own length).
+
+use crate::layout::{CrossAlign, Dimension, Direction, JsonNode, NodeType, Position, Style, TextAlign};
+
+/// Entries of each level are indented by this many ems
+const LEVEL_INDENT_EM: f32 = 1.5;
+
+/// One bookmarked node or heading, in document order
+pub struct TocEntry {
+    pub title: String,
+    pub level: u32,
+    /// Id of the node, the link target and the key for finding its page
+    pub id: String,
+}
+
+pub fn has_toc(node: &JsonNode) -> bool {
+    node.node_type == NodeType::Toc || node.children.iter().any(has_toc)
+}
+
+/// Collect the bookmarked nodes and headings, giving the ones without an id a generated one
+pub fn collect_entries(root: &mut JsonNode) -> Vec<TocEntry> {
+    fn visit(node: &mut JsonNode, entries: &mut Vec<TocEntry>) {
+        if let Some((title, level)) = node.outline_entry() {
+            let id = node.id.get_or_insert_with(|| format!("toc-entry-{}", entries.len() + 1)).clone();
+            entries.push(TocEntry { title, level, id });
+        }
+        // Spans are never drawn as boxes, and fixed boxes repeat on every page
+        if node.node_type == NodeType::Text || matches!(node.style.position, Some(Position::Fixed)) {
+            return;
+        }
+        for child in &mut node.children {
+            visit(child, entries);
+        }
+    }
+
+    let mut entries = Vec::new();
+    visit(root, &mut entries);
+    entries
+}
+
+/// Copy of the tree with every `toc` node filled with entries. `pages` holds
+/// each entry's page number, if known yet.
+pub fn fill(root: &JsonNode, entries: &[TocEntry], pages: &[Option<usize>]) -> JsonNode {
+    let mut node = root.clone();
+    if node.node_type == NodeType::Toc {
+        node.children = entries.iter()
+            .zip(pages)
+            .map(|(entry, page)| entry_row(&node.style, entry, *page))
+            .collect();
+    } else {
+        node.children = root.children.iter().map(|child| fill(child, entries, pages)).collect();
+    }
+    node
+}
+
+/// A row of title, leader and page number. The leader is an empty box that
+/// grows to fill the row; the renderer draws the dots in it.
+fn entry_row(toc_style: &Style, entry: &TocEntry, page: Option<usize>) -> JsonNode {
+    let text = |content: String| {
+        let mut node = JsonNode::new(NodeType::Text);
+        node.text = Some(content);
+        node.style.font_size = toc_style.font_size;
+        node.style.font_weight = toc_style.font_weight.clone();
+        node.style.font_style = toc_style.font_style.clone();
+        node.style.color = toc_style.color.clone();
+        node.style.line_height = toc_style.line_height;
+        node
+    };
+    let font_size = toc_style.font_size.unwrap_or(12.0);
+
+    let title = text(entry.title.clone());
+
+    let mut leader = JsonNode::new(NodeType::View);
+    leader.style.flex = Some(1.0);
+    leader.style.height = Some(Dimension::Pt(font_size * toc_style.line_height.unwrap_or(1.4)));
+
+    let mut number = text(page.map(|p| p.to_string()).unwrap_or_default());
+    number.style.text_align = Some(TextAlign::Right);
+    number.style.flex_shrink = Some(0.0);
+    // Keep wrapped titles clear of the number
+    number.style.margin_left = Some(font_size);
+
+    let mut row = JsonNode::new(NodeType::View);
+    row.href = Some(format!("#{}", entry.id));
+    row.style = Style {
+        direction: Some(Direction::Row),
+        cross_align: Some(CrossAlign::End),
+        width: Some(Dimension::Percent(100.0)),
+        padding_left: Some(entry.level.saturating_sub(1) as f32 * LEVEL_INDENT_EM * font_size),
+        ..Style::default()
+    };
+    row.children = vec![title, leader, number];
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_fill_the_toc() {
+        let json = r#"{ "type": "page", "children": [
+            { "type": "toc", "style": { "fontSize": 10 } },
+            { "type": "text", "text": "Intro", "id": "intro", "bookmark": { "level": 1 } },
+            { "type": "view", "bookmark": { "title": "Details", "level": 2 } }
+        ] }"#;
+        let mut root: JsonNode = serde_json::from_str(json).unwrap();
+        assert!(has_toc(&root));
+
+        let entries = collect_entries(&mut root);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, "intro");
+        assert_eq!(entries[1].title, "Details");
+        // Nodes without an id get one to link to
+        assert_eq!(root.children[2].id.as_deref(), Some(entries[1].id.as_str()));
+
+        let filled = fill(&root, &entries, &[Some(2), None]);
+        let rows = &filled.children[0].children;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].href.as_deref(), Some("#intro"));
+        assert_eq!(rows[0].children[0].text.as_deref(), Some("Intro"));
+        assert_eq!(rows[0].children[2].text.as_deref(), Some("2"));
+        assert_eq!(rows[1].children[2].text.as_deref(), Some(""));
+        assert_eq!(rows[1].style.padding_left, Some(15.0));
+    }
+
+    #[test]
+    fn test_headings_are_entries_at_their_level() {
+        let json = r#"{ "type": "page", "children": [
+            { "type": "toc" },
+            { "type": "text", "text": "Overview", "role": "heading", "headingLevel": 2 },
+            { "type": "text", "text": "Scope", "headingLevel": 3 },
+            { "type": "view", "role": "heading", "children": [{ "type": "text", "text": "Card" }] },
+            { "type": "text", "text": "Not a heading", "role": "paragraph", "headingLevel": 2 },
+            { "type": "text", "text": "Plain" }
+        ] }"#;
+        let mut root: JsonNode = serde_json::from_str(json).unwrap();
+        assert_eq!(root.children[1].outline_entry(), Some(("Overview".to_string(), 2)));
+
+        let entries = collect_entries(&mut root);
+        let found: Vec<(&str, u32)> = entries.iter().map(|e| (e.title.as_str(), e.level)).collect();
+        assert_eq!(found, [("Overview", 2), ("Scope", 3), ("Card", 1)]);
+        assert_eq!(root.children[1].id.as_deref(), Some(entries[0].id.as_str()));
+    }
+}